@@ -0,0 +1,762 @@
+/*!
+ * Archive inspection, extraction and bundling for downloaded attachments
+ */
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, CompressionMethod, ZipWriter};
+
+use crate::ephemeral::{self, EphemeralSession};
+use crate::{AppState, Error, FileInfo};
+
+/// Length of generated bundle passwords (alphanumeric, ~190 bits of entropy)
+const GENERATED_PASSWORD_LEN: usize = 32;
+
+/// Archive formats that can be inspected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    Zip,
+    SevenZip,
+    Rar,
+}
+
+/// A file or directory inside an archive
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveEntry {
+    /// Last path component
+    pub name: String,
+    /// Full path inside the archive, using `/` separators
+    pub path: String,
+    pub is_dir: bool,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    /// Size of the entry inside the archive in bytes
+    pub compressed_size: u64,
+    /// Entries nested below a directory
+    pub children: Vec<ArchiveEntry>,
+}
+
+/// Result of `list_archive_contents`
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveListing {
+    pub id: String,
+    pub format: ArchiveFormat,
+    /// Number of files (directories excluded)
+    pub file_count: usize,
+    pub total_size: u64,
+    pub total_compressed_size: u64,
+    /// Top-level entries of the archive
+    pub entries: Vec<ArchiveEntry>,
+}
+
+/// Result of `create_protected_bundle`
+#[derive(Debug, Clone, Serialize)]
+pub struct ProtectedBundle {
+    /// The archive, registered like any downloaded file
+    pub file: FileInfo,
+    /// Password needed to open the archive; share it with the recipient separately
+    pub password: String,
+}
+
+/// Result of `extract_archive`
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractedArchive {
+    pub id: String,
+    /// Folder the archive was unpacked into
+    pub folder: String,
+    /// Extracted files, registered like any downloaded file
+    pub files: Vec<FileInfo>,
+    /// Entries left out because they could escape the folder or aren't regular files
+    pub skipped: Vec<String>,
+}
+
+/// Flat entry as read from the archive headers
+struct RawEntry {
+    path: String,
+    is_dir: bool,
+    size: u64,
+    compressed_size: u64,
+}
+
+// Command to list the contents of a downloaded archive without extracting it
+#[tauri::command]
+pub async fn list_archive_contents(app_handle: AppHandle, id: String) -> Result<ArchiveListing, Error> {
+    let file_info = {
+        let state = app_handle.state::<Arc<Mutex<AppState>>>();
+        let app_state = state.lock().unwrap();
+        app_state.downloaded_files
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Error::FileNotFound(id.clone()))?
+    };
+
+    let path = crate::shareable_path(&app_handle, &file_info.file_path)?;
+
+    tokio::task::spawn_blocking(move || inspect_archive(&id, Path::new(&path)))
+        .await
+        .map_err(|e| Error::IoError(std::io::Error::other(e)))?
+}
+
+// Command to bundle downloaded files into an AES-256 encrypted ZIP
+#[tauri::command]
+pub async fn create_protected_bundle(
+    app_handle: AppHandle,
+    ids: Vec<String>,
+    password: Option<String>,
+) -> Result<ProtectedBundle, Error> {
+    // Only keep caller-supplied passwords that are not blank
+    let password = password
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(generate_password);
+
+    let file = create_bundle(&app_handle, &ids, Some(password.clone())).await?;
+
+    Ok(ProtectedBundle { file, password })
+}
+
+// Command to bundle downloaded files into a plain ZIP
+#[tauri::command]
+pub async fn zip_files(app_handle: AppHandle, ids: Vec<String>) -> Result<FileInfo, Error> {
+    create_bundle(&app_handle, &ids, None).await
+}
+
+// Command to unpack a downloaded ZIP or tar.gz into its own folder
+#[tauri::command]
+pub async fn extract_archive(app_handle: AppHandle, id: String) -> Result<ExtractedArchive, Error> {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let (archive, folder, session) = {
+        let app_state = state.lock().unwrap();
+        let archive = app_state.downloaded_files
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Error::FileNotFound(id.clone()))?;
+        let folder = app_state.temp_dir.path().join(format!("extracted_{}", id));
+        (archive, folder, app_state.ephemeral.clone())
+    };
+
+    let path = PathBuf::from(crate::shareable_path(&app_handle, &archive.file_path)?);
+
+    let target = folder.clone();
+    let (extracted, skipped) = tokio::task::spawn_blocking(move || {
+        std::fs::create_dir_all(&target)?;
+        let (extracted, skipped) = unpack(&path, &target)?;
+        // Sniff before the content may get encrypted
+        let extracted: Vec<_> = extracted
+            .into_iter()
+            .map(|file| {
+                let mime_types = crate::content_type::detect(&file);
+                (file, mime_types)
+            })
+            .collect();
+        // Extracted content gets the same protection as the archive it came from
+        if let Some(session) = session.as_ref().filter(|_| archive.encrypted) {
+            for (file, _) in &extracted {
+                session.encrypt_file(file)?;
+            }
+        }
+        Ok::<_, Error>((extracted, skipped))
+    })
+    .await
+    .map_err(|e| Error::IoError(std::io::Error::other(e)))??;
+
+    let mut files = Vec::with_capacity(extracted.len());
+    for (path, mime_types) in extracted {
+        let size = std::fs::metadata(&path)?.len();
+        files.push(FileInfo {
+            id: crate::generate_file_id(),
+            original_url: archive.original_url.clone(),
+            file_name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            mime_type: mime_types.mime_type,
+            detected_mime_type: mime_types.detected,
+            extension_mime_type: mime_types.from_extension,
+            file_path: path.to_string_lossy().to_string(),
+            size,
+            encrypted: archive.encrypted,
+            sha256: None,
+            source_url: None,
+            resolved_url: None,
+            redirect_chain: Vec::new(),
+            short_url: None,
+            etag: None,
+            last_modified: None,
+            cached: false,
+            conversation: archive.conversation.clone(),
+            tags: archive.tags.clone(),
+        });
+    }
+
+    {
+        let mut app_state = state.lock().unwrap();
+        for file in &files {
+            app_state.downloaded_files.insert(file.id.clone(), file.clone());
+        }
+    }
+
+    Ok(ExtractedArchive {
+        id,
+        folder: folder.to_string_lossy().to_string(),
+        files,
+        skipped,
+    })
+}
+
+/// Write the files behind `ids` into a new ZIP in the temp dir and register it
+async fn create_bundle(
+    app_handle: &AppHandle,
+    ids: &[String],
+    password: Option<String>,
+) -> Result<FileInfo, Error> {
+    if ids.is_empty() {
+        return Err(Error::IoError(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "No files selected for the bundle",
+        )));
+    }
+
+    let id = crate::generate_file_id();
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+
+    let (sources, destination, session) = {
+        let app_state = state.lock().unwrap();
+        let sources = ids
+            .iter()
+            .map(|id| {
+                app_state.downloaded_files
+                    .get(id)
+                    .map(|f| BundleSource {
+                        path: PathBuf::from(&f.file_path),
+                        name: f.file_name.clone(),
+                        encrypted: f.encrypted,
+                    })
+                    .ok_or_else(|| Error::FileNotFound(id.clone()))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        (
+            sources,
+            app_state.temp_dir.path().join(format!("bundle_{}.zip", id)),
+            app_state.ephemeral.clone(),
+        )
+    };
+
+    let zip_path = destination.clone();
+    let size = tokio::task::spawn_blocking(move || {
+        let size = write_zip(&sources, &zip_path, password.as_deref(), session.as_deref())?;
+        // The bundle gets the same protection as its ephemeral sources
+        if let Some(session) = &session {
+            session.encrypt_file(&zip_path)?;
+        }
+        Ok::<u64, Error>(size)
+    })
+    .await
+    .map_err(|e| Error::IoError(std::io::Error::other(e)))??;
+
+    let file_name = destination
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let file_info = FileInfo {
+        id: id.clone(),
+        original_url: String::new(),
+        file_path: destination.to_string_lossy().to_string(),
+        file_name,
+        mime_type: "application/zip".to_string(),
+        detected_mime_type: None,
+        extension_mime_type: None,
+        size,
+        encrypted: ephemeral::active_session(app_handle).is_some(),
+        sha256: None,
+        source_url: None,
+        resolved_url: None,
+        redirect_chain: Vec::new(),
+        short_url: None,
+        etag: None,
+        last_modified: None,
+        cached: false,
+        conversation: None,
+        tags: Vec::new(),
+    };
+
+    state.lock().unwrap().downloaded_files.insert(id, file_info.clone());
+
+    Ok(file_info)
+}
+
+/// A file to be added to a bundle
+pub struct BundleSource {
+    pub path: PathBuf,
+    /// Entry name inside the archive
+    pub name: String,
+    /// Whether the file is encrypted by the ephemeral session
+    pub encrypted: bool,
+}
+
+/// Stream `sources` into a ZIP at `destination`, returning its size.
+///
+/// Entries are AES-256 encrypted when a password is given.
+pub fn write_zip(
+    sources: &[BundleSource],
+    destination: &Path,
+    password: Option<&str>,
+    session: Option<&EphemeralSession>,
+) -> Result<u64, Error> {
+    let mut writer = ZipWriter::new(BufWriter::new(File::create(destination)?));
+    let mut used_names = HashSet::new();
+
+    for BundleSource { path, name, encrypted } in sources {
+        let size = std::fs::metadata(path)?.len();
+        let mut source: Box<dyn Read> = match session.filter(|_| *encrypted) {
+            Some(session) => Box::new(session.open_reader(path)?),
+            None => Box::new(File::open(path)?),
+        };
+
+        let mut options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(size > u32::MAX as u64);
+        if let Some(password) = password {
+            options = options.with_aes_encryption(AesMode::Aes256, password);
+        }
+
+        writer
+            .start_file(unique_entry_name(name, &mut used_names), options)
+            .map_err(|e| Error::InvalidArchive(e.to_string()))?;
+        std::io::copy(&mut source, &mut writer)?;
+    }
+
+    writer
+        .finish()
+        .map_err(|e| Error::InvalidArchive(e.to_string()))?;
+
+    Ok(std::fs::metadata(destination)?.len())
+}
+
+/// Unpack the ZIP or gzip-compressed tar at `path` into `folder`, returning the written
+/// files and the names of the entries that were skipped
+fn unpack(path: &Path, folder: &Path) -> Result<(Vec<PathBuf>, Vec<String>), Error> {
+    let mut header = [0u8; 4];
+    let read = File::open(path)?.read(&mut header)?;
+    let header = &header[..read];
+
+    if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+        unpack_zip(path, folder)
+    } else if header.starts_with(b"\x1F\x8B") {
+        unpack_tar_gz(path, folder)
+    } else {
+        Err(Error::UnsupportedArchive(path.to_string_lossy().to_string()))
+    }
+}
+
+fn unpack_zip(path: &Path, folder: &Path) -> Result<(Vec<PathBuf>, Vec<String>), Error> {
+    let mut archive = zip::ZipArchive::new(BufReader::new(File::open(path)?))
+        .map_err(|e| Error::InvalidArchive(e.to_string()))?;
+
+    let mut extracted = Vec::new();
+    let mut skipped = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| Error::InvalidArchive(e.to_string()))?;
+
+        // `enclosed_name` rejects absolute paths and `..` components
+        let relative = match entry.enclosed_name() {
+            Some(relative) if !entry.is_symlink() => relative,
+            _ => {
+                skipped.push(entry.name().to_string());
+                continue;
+            }
+        };
+
+        let destination = folder.join(relative);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&destination)?;
+            continue;
+        }
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut writer = BufWriter::new(File::create(&destination)?);
+        std::io::copy(&mut entry, &mut writer)?;
+        writer.flush()?;
+        extracted.push(destination);
+    }
+
+    Ok((extracted, skipped))
+}
+
+fn unpack_tar_gz(path: &Path, folder: &Path) -> Result<(Vec<PathBuf>, Vec<String>), Error> {
+    let decoder = flate2::read::GzDecoder::new(BufReader::new(File::open(path)?));
+    let mut archive = tar::Archive::new(decoder);
+
+    let mut extracted = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+
+        let relative = entry.path()?.into_owned();
+        let enclosed = relative
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir));
+        let kind = entry.header().entry_type();
+
+        // Links could point anywhere on the system, so only plain files and folders are kept
+        if !enclosed || !(kind.is_file() || kind.is_dir()) {
+            skipped.push(name);
+            continue;
+        }
+
+        let destination = folder.join(&relative);
+        if kind.is_dir() {
+            std::fs::create_dir_all(&destination)?;
+            continue;
+        }
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut writer = BufWriter::new(File::create(&destination)?);
+        std::io::copy(&mut entry, &mut writer)?;
+        writer.flush()?;
+        extracted.push(destination);
+    }
+
+    Ok((extracted, skipped))
+}
+
+/// Rename `name` to `name (1).ext`, `name (2).ext`, ... if it is already in `used`
+pub fn unique_entry_name(name: &str, used: &mut HashSet<String>) -> String {
+    let path = Path::new(name);
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy()));
+
+    let mut candidate = name.to_string();
+    let mut counter = 1;
+    while !used.insert(candidate.to_lowercase()) {
+        candidate = format!("{} ({}){}", stem, counter, extension.as_deref().unwrap_or(""));
+        counter += 1;
+    }
+
+    candidate
+}
+
+/// Generate a random password from the OS CSPRNG
+fn generate_password() -> String {
+    Alphanumeric.sample_string(&mut OsRng, GENERATED_PASSWORD_LEN)
+}
+
+/// Read the entry list of the archive at `path`
+pub fn inspect_archive(id: &str, path: &Path) -> Result<ArchiveListing, Error> {
+    let format = detect_format(path)?;
+
+    let raw_entries = match format {
+        ArchiveFormat::Zip => read_zip_entries(path)?,
+        ArchiveFormat::SevenZip => read_7z_entries(path)?,
+        ArchiveFormat::Rar => read_rar_entries(path)?,
+    };
+
+    let files = raw_entries.iter().filter(|e| !e.is_dir);
+    let file_count = files.clone().count();
+    let total_size = files.clone().map(|e| e.size).sum();
+    let total_compressed_size = files.map(|e| e.compressed_size).sum();
+
+    let mut entries = Vec::new();
+    for raw in &raw_entries {
+        let parts: Vec<&str> = raw.path.split('/').filter(|p| !p.is_empty()).collect();
+        if !parts.is_empty() {
+            insert_entry(&mut entries, &parts, "", raw);
+        }
+    }
+
+    Ok(ArchiveListing {
+        id: id.to_string(),
+        format,
+        file_count,
+        total_size,
+        total_compressed_size,
+        entries,
+    })
+}
+
+/// Identify the archive format from its signature bytes
+fn detect_format(path: &Path) -> Result<ArchiveFormat, Error> {
+    let mut header = [0u8; 8];
+    let mut file = File::open(path)?;
+    let read = file.read(&mut header)?;
+    let header = &header[..read];
+
+    if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+        Ok(ArchiveFormat::Zip)
+    } else if header.starts_with(b"7z\xBC\xAF\x27\x1C") {
+        Ok(ArchiveFormat::SevenZip)
+    } else if header.starts_with(b"Rar!\x1A\x07") {
+        Ok(ArchiveFormat::Rar)
+    } else {
+        Err(Error::UnsupportedArchive(path.to_string_lossy().to_string()))
+    }
+}
+
+/// Place `raw` into the tree, creating intermediate directories as needed
+fn insert_entry(nodes: &mut Vec<ArchiveEntry>, parts: &[&str], parent: &str, raw: &RawEntry) {
+    let name = parts[0];
+    let path = if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    };
+    let is_leaf = parts.len() == 1;
+
+    let index = match nodes.iter().position(|n| n.name == name) {
+        Some(index) => index,
+        None => {
+            nodes.push(ArchiveEntry {
+                name: name.to_string(),
+                path: path.clone(),
+                is_dir: true,
+                size: 0,
+                compressed_size: 0,
+                children: Vec::new(),
+            });
+            nodes.len() - 1
+        }
+    };
+
+    let node = &mut nodes[index];
+    if is_leaf {
+        node.is_dir = raw.is_dir;
+        node.size = raw.size;
+        node.compressed_size = raw.compressed_size;
+    } else {
+        insert_entry(&mut node.children, &parts[1..], &path, raw);
+    }
+}
+
+fn read_zip_entries(path: &Path) -> Result<Vec<RawEntry>, Error> {
+    let file = BufReader::new(File::open(path)?);
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| Error::InvalidArchive(e.to_string()))?;
+
+    let mut entries = Vec::with_capacity(archive.len());
+    for index in 0..archive.len() {
+        // Raw access skips decryption so password-protected entries can still be listed
+        let entry = archive
+            .by_index_raw(index)
+            .map_err(|e| Error::InvalidArchive(e.to_string()))?;
+        entries.push(RawEntry {
+            path: entry.name().replace('\\', "/"),
+            is_dir: entry.is_dir(),
+            size: entry.size(),
+            compressed_size: entry.compressed_size(),
+        });
+    }
+
+    Ok(entries)
+}
+
+fn read_7z_entries(path: &Path) -> Result<Vec<RawEntry>, Error> {
+    let archive = sevenz_rust::Archive::open(path)
+        .map_err(|e| Error::InvalidArchive(e.to_string()))?;
+
+    Ok(archive
+        .files
+        .iter()
+        .map(|entry| RawEntry {
+            path: entry.name.replace('\\', "/"),
+            is_dir: entry.is_directory,
+            size: entry.size,
+            compressed_size: entry.compressed_size,
+        })
+        .collect())
+}
+
+/// Walk the block headers of a RAR 4.x or 5.x archive.
+///
+/// There is no pure-Rust RAR decoder, but listing only needs the file headers,
+/// which are stored uncompressed unless the archive encrypts its headers.
+fn read_rar_entries(path: &Path) -> Result<Vec<RawEntry>, Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut signature = [0u8; 7];
+    reader.read_exact(&mut signature)?;
+
+    // RAR 5 signatures carry an extra 0x01 0x00, RAR 4 ends with 0x00
+    if signature[6] == 0x01 {
+        let mut rest = [0u8; 1];
+        reader.read_exact(&mut rest)?;
+        read_rar5_entries(&mut reader)
+    } else {
+        read_rar4_entries(&mut reader)
+    }
+}
+
+fn read_rar4_entries<R: Read + Seek>(reader: &mut R) -> Result<Vec<RawEntry>, Error> {
+    const FILE_HEADER: u8 = 0x74;
+    const END_OF_ARCHIVE: u8 = 0x7B;
+    const LONG_BLOCK: u16 = 0x8000;
+    const LARGE_FILE: u16 = 0x0100;
+    const DIRECTORY: u16 = 0x00E0;
+
+    let mut entries = Vec::new();
+
+    loop {
+        let block_start = reader.stream_position()?;
+        let mut base = [0u8; 7];
+        if reader.read_exact(&mut base).is_err() {
+            break;
+        }
+
+        let block_type = base[2];
+        let flags = u16::from_le_bytes([base[3], base[4]]);
+        let header_size = u16::from_le_bytes([base[5], base[6]]) as u64;
+        if header_size < 7 {
+            return Err(Error::InvalidArchive("corrupt RAR block header".to_string()));
+        }
+
+        let mut header = vec![0u8; header_size as usize - 7];
+        reader.read_exact(&mut header)?;
+
+        let mut data_size = if flags & LONG_BLOCK != 0 && header.len() >= 4 {
+            u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as u64
+        } else {
+            0
+        };
+
+        if block_type == FILE_HEADER {
+            if header.len() < 25 {
+                return Err(Error::InvalidArchive("truncated RAR file header".to_string()));
+            }
+            let mut size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+            let name_len = u16::from_le_bytes([header[19], header[20]]) as usize;
+            let mut name_start = 25;
+
+            if flags & LARGE_FILE != 0 && header.len() >= 33 {
+                let high_pack = u32::from_le_bytes([header[25], header[26], header[27], header[28]]) as u64;
+                let high_unpacked = u32::from_le_bytes([header[29], header[30], header[31], header[32]]) as u64;
+                data_size |= high_pack << 32;
+                size |= high_unpacked << 32;
+                name_start = 33;
+            }
+
+            let name_bytes = header
+                .get(name_start..name_start + name_len)
+                .ok_or_else(|| Error::InvalidArchive("truncated RAR file name".to_string()))?;
+            // Unicode names are stored after a NUL behind the legacy name
+            let legacy = name_bytes.split(|b| *b == 0).next().unwrap_or(name_bytes);
+
+            entries.push(RawEntry {
+                path: String::from_utf8_lossy(legacy).replace('\\', "/"),
+                is_dir: flags & DIRECTORY == DIRECTORY,
+                size,
+                compressed_size: data_size,
+            });
+        } else if block_type == END_OF_ARCHIVE {
+            break;
+        }
+
+        reader.seek(SeekFrom::Start(block_start + header_size + data_size))?;
+    }
+
+    Ok(entries)
+}
+
+fn read_rar5_entries<R: Read + Seek>(reader: &mut R) -> Result<Vec<RawEntry>, Error> {
+    const FILE_HEADER: u64 = 2;
+    const ENCRYPTION_HEADER: u64 = 4;
+    const END_OF_ARCHIVE: u64 = 5;
+    const HAS_EXTRA_AREA: u64 = 0x0001;
+    const HAS_DATA_AREA: u64 = 0x0002;
+    const FILE_IS_DIRECTORY: u64 = 0x0001;
+    const FILE_HAS_MTIME: u64 = 0x0002;
+    const FILE_HAS_CRC: u64 = 0x0004;
+    // The format caps a header at 2 MB; anything larger is a corrupt or hostile size
+    const MAX_HEADER_SIZE: u64 = 2 * 1024 * 1024;
+
+    let mut entries = Vec::new();
+
+    loop {
+        let mut crc = [0u8; 4];
+        if reader.read_exact(&mut crc).is_err() {
+            break;
+        }
+
+        let header_size = read_vint(reader)?;
+        if header_size > MAX_HEADER_SIZE {
+            return Err(Error::InvalidArchive(format!("RAR header of {} bytes exceeds the 2 MB limit", header_size)));
+        }
+        let header_start = reader.stream_position()?;
+        let mut header = vec![0u8; header_size as usize];
+        reader.read_exact(&mut header)?;
+
+        let mut cursor = std::io::Cursor::new(header.as_slice());
+        let block_type = read_vint(&mut cursor)?;
+        let flags = read_vint(&mut cursor)?;
+        if flags & HAS_EXTRA_AREA != 0 {
+            read_vint(&mut cursor)?;
+        }
+        let data_size = if flags & HAS_DATA_AREA != 0 {
+            read_vint(&mut cursor)?
+        } else {
+            0
+        };
+
+        match block_type {
+            FILE_HEADER => {
+                let file_flags = read_vint(&mut cursor)?;
+                let size = read_vint(&mut cursor)?;
+                read_vint(&mut cursor)?; // attributes
+                if file_flags & FILE_HAS_MTIME != 0 {
+                    cursor.seek(SeekFrom::Current(4))?;
+                }
+                if file_flags & FILE_HAS_CRC != 0 {
+                    cursor.seek(SeekFrom::Current(4))?;
+                }
+                read_vint(&mut cursor)?; // compression info
+                read_vint(&mut cursor)?; // host OS
+                let name_len = read_vint(&mut cursor)? as usize;
+                let mut name = vec![0u8; name_len];
+                cursor.read_exact(&mut name)?;
+
+                entries.push(RawEntry {
+                    path: String::from_utf8_lossy(&name).replace('\\', "/"),
+                    is_dir: file_flags & FILE_IS_DIRECTORY != 0,
+                    size,
+                    compressed_size: data_size,
+                });
+            }
+            ENCRYPTION_HEADER => {
+                return Err(Error::UnsupportedArchive(
+                    "RAR archive with encrypted headers cannot be listed without a password".to_string(),
+                ));
+            }
+            END_OF_ARCHIVE => break,
+            _ => {}
+        }
+
+        reader.seek(SeekFrom::Start(header_start + header_size + data_size))?;
+    }
+
+    Ok(entries)
+}
+
+/// Read a RAR 5 variable-length integer (7 bits per byte, high bit = continuation)
+fn read_vint<R: Read>(reader: &mut R) -> Result<u64, Error> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7F) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::InvalidArchive("malformed RAR variable-length integer".to_string()))
+}
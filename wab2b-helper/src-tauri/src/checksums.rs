@@ -0,0 +1,242 @@
+/*!
+ * Checksum manifests for sets of files, in the `sha256sum` layout the updater reads
+ */
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use tauri::{AppHandle, Manager};
+
+use crate::ephemeral::EphemeralSession;
+use crate::{AppState, Error, FileInfo};
+
+/// Hash algorithms available for manifests
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumAlgorithm {
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+impl ChecksumAlgorithm {
+    /// File name of the generated manifest
+    fn manifest_name(self) -> &'static str {
+        match self {
+            // Same name the updater looks for in release assets
+            ChecksumAlgorithm::Sha256 => "checksums.txt",
+            ChecksumAlgorithm::Sha512 => "checksums.sha512.txt",
+        }
+    }
+}
+
+/// One line of the manifest
+#[derive(Debug, Clone, Serialize)]
+pub struct ChecksumEntry {
+    /// Name as written in the manifest (relative path for folders)
+    pub name: String,
+    pub hash: String,
+    pub size: u64,
+}
+
+/// Result of `generate_checksums`
+#[derive(Debug, Clone, Serialize)]
+pub struct ChecksumManifest {
+    pub algorithm: ChecksumAlgorithm,
+    /// The manifest file, registered like any downloaded file
+    pub file: FileInfo,
+    pub entries: Vec<ChecksumEntry>,
+}
+
+/// A file to hash
+struct Source {
+    path: PathBuf,
+    name: String,
+    encrypted: bool,
+}
+
+// Command to write a checksums manifest for downloaded files (by ID) or for a folder
+#[tauri::command]
+pub async fn generate_checksums(
+    app_handle: AppHandle,
+    ids: Option<Vec<String>>,
+    folder: Option<String>,
+    algorithm: Option<ChecksumAlgorithm>,
+) -> Result<ChecksumManifest, Error> {
+    let algorithm = algorithm.unwrap_or_default();
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+
+    let (id_sources, temp_dir, session) = {
+        let app_state = state.lock().unwrap();
+        let mut used = HashSet::new();
+        let id_sources = ids
+            .iter()
+            .flatten()
+            .map(|id| {
+                let file = app_state.downloaded_files
+                    .get(id)
+                    .ok_or_else(|| Error::FileNotFound(id.clone()))?;
+                Ok(Source {
+                    path: PathBuf::from(&file.file_path),
+                    name: crate::archive::unique_entry_name(&file.file_name, &mut used),
+                    encrypted: file.encrypted,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        (id_sources, app_state.temp_dir.path().to_path_buf(), app_state.ephemeral.clone())
+    };
+
+    let sources = match folder {
+        _ if !id_sources.is_empty() => id_sources,
+        Some(folder) => collect_folder(Path::new(&folder))?,
+        None => {
+            return Err(Error::IoError(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Either file IDs or a folder must be given",
+            )));
+        }
+    };
+
+    let id = crate::generate_file_id();
+    let manifest_dir = temp_dir.join(&id);
+    let manifest_path = manifest_dir.join(algorithm.manifest_name());
+
+    let (entries, size) = {
+        let manifest_path = manifest_path.clone();
+        tokio::task::spawn_blocking(move || {
+            let entries = sources
+                .iter()
+                .map(|source| hash_source(source, algorithm, session.as_deref()))
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            let manifest: String = entries
+                .iter()
+                .map(|entry| format!("{}  {}\n", entry.hash, entry.name))
+                .collect();
+            fs::create_dir_all(&manifest_dir)?;
+            fs::write(&manifest_path, &manifest)?;
+
+            Ok::<_, Error>((entries, manifest.len() as u64))
+        })
+        .await
+        .map_err(|e| Error::IoError(io::Error::other(e)))??
+    };
+
+    let file = FileInfo {
+        id: id.clone(),
+        original_url: String::new(),
+        file_path: manifest_path.to_string_lossy().to_string(),
+        file_name: algorithm.manifest_name().to_string(),
+        mime_type: "text/plain".to_string(),
+        detected_mime_type: None,
+        extension_mime_type: None,
+        size,
+        encrypted: false,
+        sha256: None,
+        source_url: None,
+        resolved_url: None,
+        redirect_chain: Vec::new(),
+        short_url: None,
+        etag: None,
+        last_modified: None,
+        cached: false,
+        conversation: None,
+        tags: Vec::new(),
+    };
+
+    state.lock().unwrap().downloaded_files.insert(id, file.clone());
+
+    Ok(ChecksumManifest { algorithm, file, entries })
+}
+
+/// All files below `folder`, named by their `/`-separated relative path
+fn collect_folder(folder: &Path) -> Result<Vec<Source>, Error> {
+    if !folder.is_dir() {
+        return Err(Error::FileNotFound(folder.to_string_lossy().to_string()));
+    }
+
+    let mut sources = Vec::new();
+    let mut pending = vec![folder.to_path_buf()];
+
+    while let Some(directory) = pending.pop() {
+        for entry in fs::read_dir(&directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+
+            let name = path
+                .strip_prefix(folder)
+                .unwrap_or(&path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            // An existing manifest is not part of the set it describes
+            if name == ChecksumAlgorithm::Sha256.manifest_name() || name == ChecksumAlgorithm::Sha512.manifest_name() {
+                continue;
+            }
+
+            sources.push(Source { path, name, encrypted: false });
+        }
+    }
+
+    sources.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(sources)
+}
+
+fn hash_source(source: &Source, algorithm: ChecksumAlgorithm, session: Option<&EphemeralSession>) -> Result<ChecksumEntry, Error> {
+    let mut reader: Box<dyn Read> = match session.filter(|_| source.encrypted) {
+        Some(session) => Box::new(session.open_reader(&source.path)?),
+        None => Box::new(File::open(&source.path)?),
+    };
+
+    let (hash, size) = match algorithm {
+        ChecksumAlgorithm::Sha256 => hash_reader::<Sha256>(&mut reader)?,
+        ChecksumAlgorithm::Sha512 => hash_reader::<Sha512>(&mut reader)?,
+    };
+
+    Ok(ChecksumEntry {
+        name: source.name.clone(),
+        hash,
+        size,
+    })
+}
+
+/// Lowercase hex SHA-256 of a file, decrypting it first when `session` is given
+pub fn sha256_file(path: &Path, session: Option<&EphemeralSession>) -> Result<String, Error> {
+    let mut reader: Box<dyn Read> = match session {
+        Some(session) => Box::new(session.open_reader(path)?),
+        None => Box::new(File::open(path)?),
+    };
+    Ok(hash_reader::<Sha256>(&mut reader)?.0)
+}
+
+/// Hash everything `reader` yields, returning the lowercase hex digest and byte count
+fn hash_reader<D: Digest>(reader: &mut dyn Read) -> Result<(String, u64), Error> {
+    let mut hasher = D::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    let mut size = 0;
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok((to_hex(&hasher.finalize()), size))
+}
+
+/// Lowercase hex form of a digest
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
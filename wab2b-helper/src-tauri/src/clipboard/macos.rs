@@ -0,0 +1,98 @@
+/*!
+ * macOS clipboard: file URLs and text on the general NSPasteboard
+ */
+
+use std::path::PathBuf;
+use std::process::Command;
+use objc2_app_kit::{NSPasteboard, NSPasteboardTypeHTML, NSPasteboardTypeString};
+use objc2_foundation::NSString;
+
+use super::{ClipboardContents, ClipboardFileCopy};
+use crate::Error;
+
+/// Run a JavaScript for Automation script and return what it printed
+fn run_script(script: &str) -> Result<String, Error> {
+    let output = Command::new("osascript")
+        .args(["-l", "JavaScript", "-e", script])
+        .output()
+        .map_err(|e| Error::Clipboard(format!("Failed to run osascript: {}", e)))?;
+    if !output.status.success() {
+        return Err(Error::Clipboard(format!(
+            "Pasteboard script failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// File list backend writing `NSURL` file URLs, as Finder does when copying files
+pub struct FileClipboard;
+
+impl ClipboardFileCopy for FileClipboard {
+    fn copy_files(&self, paths: &[PathBuf]) -> Result<(), Error> {
+        let paths: Vec<String> = paths.iter().map(|p| p.to_string_lossy().to_string()).collect();
+        // A JSON array of strings is also a valid JavaScript literal
+        let paths = serde_json::to_string(&paths).map_err(|e| Error::Clipboard(e.to_string()))?;
+        let script = format!(
+            r#"ObjC.import("AppKit");
+const urls = {}.map(path => $.NSURL.fileURLWithPath(path));
+const pasteboard = $.NSPasteboard.generalPasteboard;
+pasteboard.clearContents;
+if (!pasteboard.writeObjects($(urls))) throw new Error("Pasteboard rejected the files");"#,
+            paths
+        );
+
+        run_script(&script).map(|_| ())
+    }
+}
+
+/// Formats, text and files currently on the pasteboard
+pub fn read_contents() -> Result<ClipboardContents, Error> {
+    // The result of the last expression is printed by osascript
+    let json = run_script(
+        r#"ObjC.import("AppKit");
+const pasteboard = $.NSPasteboard.generalPasteboard;
+const urls = pasteboard.readObjectsForClassesOptions($([$.NSURL]), $());
+const files = [];
+for (let i = 0; urls && i < urls.count; i++) {
+    const url = urls.objectAtIndex(i);
+    if (url.isFileURL) files.push(ObjC.unwrap(url.path));
+}
+JSON.stringify({
+    formats: ObjC.deepUnwrap(pasteboard.types) || [],
+    text: ObjC.unwrap(pasteboard.stringForType($.NSPasteboardTypeString)) || null,
+    files: files,
+});"#,
+    )?;
+
+    serde_json::from_str(json.trim()).map_err(|e| Error::Clipboard(format!("Unexpected pasteboard contents: {}", e)))
+}
+
+/// Counter the pasteboard bumps whenever its owner changes. Read in-process, as the clipboard
+/// monitor polls it every second.
+pub fn change_count() -> Result<u64, Error> {
+    let count = unsafe { NSPasteboard::generalPasteboard().changeCount() };
+    Ok(count as u64)
+}
+
+/// Put `text` on the pasteboard, with `html` for applications that take formatted text
+pub fn set_text(text: &str, html: Option<&str>) -> Result<(), Error> {
+    let written = unsafe {
+        let pasteboard = NSPasteboard::generalPasteboard();
+        pasteboard.clearContents();
+        let text_written = pasteboard.setString_forType(&NSString::from_str(text), NSPasteboardTypeString);
+        let html_written = html.is_none_or(|html| {
+            pasteboard.setString_forType(&NSString::from_str(html), NSPasteboardTypeHTML)
+        });
+        text_written && html_written
+    };
+    if !written {
+        return Err(Error::Clipboard("Pasteboard rejected the text".to_string()));
+    }
+    Ok(())
+}
+
+/// Empty the pasteboard
+pub fn clear() -> Result<(), Error> {
+    run_script(r#"ObjC.import("AppKit"); $.NSPasteboard.generalPasteboard.clearContents;"#).map(|_| ())
+}
@@ -0,0 +1,779 @@
+/*!
+ * Native clipboard access for copies that carry more than a single format
+ */
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::settings;
+use crate::{AppState, Error, FileInfo};
+
+#[cfg(windows)]
+mod windows;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod x11;
+
+/// Places files on the clipboard the way the platform's file manager does, so they paste as attachments
+pub trait ClipboardFileCopy {
+    fn copy_files(&self, paths: &[PathBuf]) -> Result<(), Error>;
+}
+
+/// File clipboard backend for this platform
+#[cfg(windows)]
+pub fn file_backend(app_handle: &AppHandle) -> impl ClipboardFileCopy {
+    windows::FileClipboard { owner: owner_window(app_handle) }
+}
+
+#[cfg(target_os = "macos")]
+pub fn file_backend(_app_handle: &AppHandle) -> impl ClipboardFileCopy {
+    macos::FileClipboard
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn file_backend(_app_handle: &AppHandle) -> impl ClipboardFileCopy {
+    x11::FileClipboard
+}
+
+/// How copied files are placed on the clipboard
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyMode {
+    /// As files, which paste as attachments
+    #[default]
+    FileList,
+    /// As their absolute paths in plain text, one per line
+    PathText,
+    /// As files, or as paths when files can't be placed
+    Auto,
+}
+
+/// Run clipboard work on a blocking thread: opening a clipboard another application holds is
+/// retried with sleeps, which must not stall the main thread or the async runtime
+pub async fn run_blocking<T: Send + 'static>(
+    app_handle: &AppHandle,
+    work: impl FnOnce(&AppHandle) -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    let app_handle = app_handle.clone();
+    tokio::task::spawn_blocking(move || work(&app_handle))
+        .await
+        .map_err(|e| Error::IoError(std::io::Error::other(e)))?
+}
+
+/// Put `paths` on the clipboard as `mode` asks, returning the mode used in the end
+pub fn copy_paths(app_handle: &AppHandle, paths: &[PathBuf], mode: CopyMode) -> Result<CopyMode, Error> {
+    match mode {
+        CopyMode::FileList => file_backend(app_handle).copy_files(paths).map(|_| CopyMode::FileList),
+        CopyMode::PathText => set_text(app_handle, &path_text(paths), None).map(|_| CopyMode::PathText),
+        CopyMode::Auto => match file_backend(app_handle).copy_files(paths) {
+            Ok(()) => Ok(CopyMode::FileList),
+            Err(e) => {
+                println!("Copying paths as text instead of files: {}", e);
+                set_text(app_handle, &path_text(paths), None).map(|_| CopyMode::PathText)
+            }
+        },
+    }
+}
+
+fn path_text(paths: &[PathBuf]) -> String {
+    let line_end = if cfg!(windows) { "\r\n" } else { "\n" };
+    paths
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join(line_end)
+}
+
+/// Remember a copy made by [`copy_paths`]; pasted paths are text, not our files
+pub fn record_paths_copy(app_handle: &AppHandle, ids: Vec<String>, paths: Vec<PathBuf>, used: CopyMode) {
+    match used {
+        CopyMode::PathText => record_copy(app_handle, ids, Vec::new()),
+        _ => record_copy(app_handle, ids, paths),
+    }
+}
+
+/// Longest text preview in a clipboard summary, in characters
+const TEXT_PREVIEW_CHARS: usize = 200;
+
+/// How long after a copy it is checked to still be on the clipboard
+const VERIFY_DELAY: Duration = Duration::from_millis(250);
+
+/// What the helper last put on the clipboard
+static LAST_COPY: Mutex<Option<OwnCopy>> = Mutex::new(None);
+
+struct OwnCopy {
+    /// Downloaded files that were copied
+    ids: Vec<String>,
+    /// Paths placed on the clipboard, which may be working copies of the files
+    paths: Vec<PathBuf>,
+    /// Clipboard stamp right after the copy, see [`stamp`]
+    stamp: Option<u64>,
+}
+
+/// Payload of the `clipboard-cleared` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardCleared {
+    pub file_ids: Vec<String>,
+    pub after_secs: u64,
+}
+
+/// Payload of the `clipboard-copy-confirmed` and `clipboard-copy-overwritten` events
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardCopyResult {
+    pub file_ids: Vec<String>,
+}
+
+/// Payload of the `clipboard-changed` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardChanged {
+    /// Whether the clipboard still holds the helper's last copy
+    pub holds_our_copy: bool,
+    /// Downloaded files in that copy, when `holds_our_copy`
+    pub file_ids: Vec<String>,
+}
+
+/// Remember that downloads `ids` were copied as `paths` (both empty for text and images),
+/// to recognise them on the clipboard later, and start the auto-clear timer
+pub fn record_copy(app_handle: &AppHandle, ids: Vec<String>, paths: Vec<PathBuf>) {
+    let copied = stamp(app_handle);
+    *LAST_COPY.lock().unwrap() = Some(OwnCopy {
+        ids: ids.clone(),
+        paths: paths.clone(),
+        stamp: copied.as_ref().ok().copied(),
+    });
+    let _ = app_handle.emit("clipboard-changed", ClipboardChanged { holds_our_copy: true, file_ids: ids.clone() });
+    verify_copy(app_handle, ids.clone(), paths, copied.as_ref().ok().copied());
+
+    let settings = match settings::load_settings(app_handle) {
+        Ok(settings) => settings.clipboard,
+        Err(_) => return,
+    };
+    let restore = settings.preserve_previous
+        && settings.restore_after_secs > 0
+        && PREVIOUS.lock().unwrap().is_some();
+    if settings.auto_clear_secs == 0 && !restore {
+        return;
+    }
+    let copied = match copied {
+        Ok(stamp) => stamp,
+        Err(e) => {
+            println!("Clipboard will not be cleared or restored automatically: {}", e);
+            return;
+        }
+    };
+
+    if restore {
+        let delay = settings.restore_after_secs;
+        after_copy(app_handle, copied, delay, move |app_handle| match restore_previous(app_handle) {
+            Ok(true) => println!("Restored the previous clipboard {} seconds after copying", delay),
+            Ok(false) => {}
+            Err(e) => println!("Failed to restore the previous clipboard: {}", e),
+        });
+    }
+    // Restoring replaces the copy anyway; clearing only comes into play when it happens first
+    if settings.auto_clear_secs > 0 && (!restore || settings.auto_clear_secs < settings.restore_after_secs) {
+        let delay = settings.auto_clear_secs;
+        after_copy(app_handle, copied, delay, move |app_handle| match clear(app_handle) {
+            Ok(()) => {
+                println!("Cleared the clipboard {} seconds after copying", delay);
+                let _ = app_handle.emit("clipboard-cleared", ClipboardCleared { file_ids: ids, after_secs: delay });
+            }
+            Err(e) => println!("Failed to clear the clipboard: {}", e),
+        });
+    }
+}
+
+/// Check, a moment after a copy, that nothing has replaced it and tell the webview with
+/// `clipboard-copy-confirmed` or `clipboard-copy-overwritten`
+fn verify_copy(app_handle: &AppHandle, ids: Vec<String>, paths: Vec<PathBuf>, copied: Option<u64>) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(VERIFY_DELAY).await;
+
+        let handle = app_handle.clone();
+        let intact = tokio::task::spawn_blocking(move || {
+            if copied.is_some() && stamp(&handle).ok() != copied {
+                return false;
+            }
+            // Files can also be checked by content, which catches a copy made before the stamp was read
+            if paths.is_empty() {
+                return true;
+            }
+            match read_contents(&handle) {
+                Ok(contents) => {
+                    let mut current = contents.files;
+                    let mut ours = paths;
+                    current.sort();
+                    ours.sort();
+                    current == ours
+                }
+                // Nothing to compare with, so the successful write stands
+                Err(_) => true,
+            }
+        })
+        .await
+        .unwrap_or(true);
+
+        let event = if intact { "clipboard-copy-confirmed" } else { "clipboard-copy-overwritten" };
+        let _ = app_handle.emit(event, ClipboardCopyResult { file_ids: ids });
+    });
+}
+
+/// Run `action` `delay_secs` after a copy, unless the clipboard no longer holds that copy
+fn after_copy(
+    app_handle: &AppHandle,
+    copied: u64,
+    delay_secs: u64,
+    action: impl FnOnce(&AppHandle) + Send + 'static,
+) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(delay_secs)).await;
+
+        let _ = tokio::task::spawn_blocking(move || {
+            // Anything copied in the meantime is the user's, not ours to touch
+            if stamp(&app_handle).ok() != Some(copied) {
+                return;
+            }
+            action(&app_handle);
+        })
+        .await;
+    });
+}
+
+/// Clipboard contents from before the helper's copies, while `preserve_previous` is on
+static PREVIOUS: Mutex<Option<ClipboardContents>> = Mutex::new(None);
+
+/// Snapshot the clipboard before the helper copies over it, when `preserve_previous` is on.
+/// While the clipboard still holds an earlier copy of ours, the snapshot from before that copy is kept.
+pub fn preserve_previous(app_handle: &AppHandle) {
+    let enabled = settings::load_settings(app_handle)
+        .map(|s| s.clipboard.preserve_previous)
+        .unwrap_or(false);
+    if !enabled {
+        return;
+    }
+
+    let current = stamp(app_handle).ok();
+    let holds_our_copy = LAST_COPY
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|copy| copy.stamp.is_some() && copy.stamp == current);
+    if holds_our_copy {
+        return;
+    }
+
+    match read_contents(app_handle) {
+        // Only text and files can be put back
+        Ok(contents) if contents.text.is_some() || !contents.files.is_empty() => {
+            *PREVIOUS.lock().unwrap() = Some(contents);
+        }
+        Ok(_) => *PREVIOUS.lock().unwrap() = None,
+        Err(e) => println!("Failed to preserve the clipboard: {}", e),
+    }
+}
+
+/// Put the preserved contents back on the clipboard; false when nothing was preserved
+fn restore_previous(app_handle: &AppHandle) -> Result<bool, Error> {
+    let previous = match PREVIOUS.lock().unwrap().take() {
+        Some(previous) => previous,
+        None => return Ok(false),
+    };
+
+    if !previous.files.is_empty() {
+        file_backend(app_handle).copy_files(&previous.files)?;
+    } else if let Some(text) = &previous.text {
+        set_text(app_handle, text, None)?;
+    }
+    *LAST_COPY.lock().unwrap() = None;
+    Ok(true)
+}
+
+/// Tell the webview whether the clipboard, which just changed, still holds our copy
+fn notify_change(app_handle: &AppHandle) {
+    let current = stamp(app_handle).ok();
+    let file_ids = LAST_COPY
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|copy| copy.stamp.is_some() && copy.stamp == current)
+        .map(|copy| copy.ids.clone());
+
+    let _ = app_handle.emit("clipboard-changed", ClipboardChanged {
+        holds_our_copy: file_ids.is_some(),
+        file_ids: file_ids.unwrap_or_default(),
+    });
+}
+
+/// Start watching the clipboard, emitting `clipboard-changed` whenever its contents change
+#[cfg(windows)]
+pub fn start_monitor(app_handle: AppHandle) {
+    let result = windows::listen(move || {
+        let app_handle = app_handle.clone();
+        // Handled off the listener thread so its message loop keeps running
+        tauri::async_runtime::spawn(async move { notify_change(&app_handle) });
+    });
+    if let Err(e) = result {
+        println!("Clipboard changes will not be reported: {}", e);
+    }
+}
+
+/// Start watching the clipboard, emitting `clipboard-changed` whenever its contents change.
+/// There is no change notification to subscribe to here, so the clipboard stamp is polled;
+/// reading it is cheap on both platforms.
+#[cfg(not(windows))]
+pub fn start_monitor(app_handle: AppHandle) {
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    tauri::async_runtime::spawn(async move {
+        let mut last = None;
+        loop {
+            let handle = app_handle.clone();
+            let current = tokio::task::spawn_blocking(move || stamp(&handle).ok())
+                .await
+                .ok()
+                .flatten();
+            if current.is_some() && last.is_some() && current != last {
+                notify_change(&app_handle);
+            }
+            if current.is_some() {
+                last = current;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+/// What the platform clipboard holds
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ClipboardContents {
+    /// Format names, MIME types or UTIs, depending on the platform
+    pub formats: Vec<String>,
+    pub text: Option<String>,
+    pub files: Vec<PathBuf>,
+}
+
+/// Result of `read_clipboard_summary`
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardSummary {
+    pub formats: Vec<String>,
+    /// Start of the text on the clipboard, if any
+    pub text_preview: Option<String>,
+    pub files: Vec<String>,
+    /// Whether the clipboard still holds the files the helper copied last
+    pub holds_our_copy: bool,
+    /// Downloaded files those are, when `holds_our_copy`
+    pub file_ids: Vec<String>,
+}
+
+#[cfg(windows)]
+fn read_contents(app_handle: &AppHandle) -> Result<ClipboardContents, Error> {
+    windows::read_contents(owner_window(app_handle))
+}
+
+#[cfg(target_os = "macos")]
+fn read_contents(_app_handle: &AppHandle) -> Result<ClipboardContents, Error> {
+    macos::read_contents()
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn read_contents(_app_handle: &AppHandle) -> Result<ClipboardContents, Error> {
+    x11::read_contents()
+}
+
+/// Identifies the current clipboard contents; changes whenever anything is copied
+#[cfg(windows)]
+fn stamp(_app_handle: &AppHandle) -> Result<u64, Error> {
+    Ok(windows::sequence_number())
+}
+
+#[cfg(target_os = "macos")]
+fn stamp(_app_handle: &AppHandle) -> Result<u64, Error> {
+    macos::change_count()
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn stamp(_app_handle: &AppHandle) -> Result<u64, Error> {
+    x11::owner()
+}
+
+#[cfg(windows)]
+fn clear(app_handle: &AppHandle) -> Result<(), Error> {
+    windows::clear(owner_window(app_handle))
+}
+
+#[cfg(target_os = "macos")]
+fn clear(_app_handle: &AppHandle) -> Result<(), Error> {
+    macos::clear()
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn clear(_app_handle: &AppHandle) -> Result<(), Error> {
+    x11::clear()
+}
+
+/// IDs of the downloads whose copy `contents` still holds, or None when it holds something else
+fn own_copy_in(contents: &ClipboardContents) -> Option<Vec<String>> {
+    let last_copy = LAST_COPY.lock().unwrap();
+    let copy = last_copy.as_ref()?;
+
+    let mut ours = copy.paths.clone();
+    let mut current = contents.files.clone();
+    ours.sort();
+    current.sort();
+    (!current.is_empty() && ours == current).then(|| copy.ids.clone())
+}
+
+// Command to describe what is on the clipboard and whether it is still the attachment the helper copied
+#[tauri::command]
+pub async fn read_clipboard_summary(app_handle: AppHandle) -> Result<ClipboardSummary, Error> {
+    let contents = run_blocking(&app_handle, read_contents).await?;
+    let file_ids = own_copy_in(&contents);
+
+    Ok(ClipboardSummary {
+        text_preview: contents
+            .text
+            .as_ref()
+            .map(|text| text.chars().take(TEXT_PREVIEW_CHARS).collect()),
+        files: contents.files.iter().map(|p| p.to_string_lossy().to_string()).collect(),
+        holds_our_copy: file_ids.is_some(),
+        file_ids: file_ids.unwrap_or_default(),
+        formats: contents.formats,
+    })
+}
+
+/// Metadata placed on the clipboard next to the file
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardMetadata {
+    pub id: String,
+    pub original_url: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub sha256: String,
+    /// Conversation the attachment belongs to, as given by the dashboard
+    pub conversation: Option<String>,
+}
+
+/// Largest file `copy_as_data_uri` encodes unless told otherwise; base64 makes it a third bigger
+const DEFAULT_DATA_URI_MAX_BYTES: u64 = 1024 * 1024;
+
+/// Size of the `BITMAPINFOHEADER` that starts a `CF_DIB`
+const DIB_HEADER_SIZE: u32 = 40;
+
+/// An image in the formats placed on the clipboard
+#[cfg_attr(not(windows), allow(dead_code))]
+struct ClipboardImage {
+    /// `CF_DIB`: header followed by bottom-up 32-bit BGRA rows
+    dib: Vec<u8>,
+    /// PNG, which keeps transparency for applications that read it
+    png: Vec<u8>,
+}
+
+fn downloaded_file(app_handle: &AppHandle, id: &str) -> Result<FileInfo, Error> {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().unwrap();
+    app_state.downloaded_files
+        .get(id)
+        .cloned()
+        .ok_or_else(|| Error::FileNotFound(id.to_string()))
+}
+
+// Command to copy a downloaded file to the clipboard together with a JSON metadata format for automation tools
+#[tauri::command]
+pub async fn copy_file_with_metadata(
+    app_handle: AppHandle,
+    id: String,
+    conversation: Option<String>,
+) -> Result<ClipboardMetadata, Error> {
+    let file = downloaded_file(&app_handle, &id)?;
+
+    let sha256 = match &file.sha256 {
+        Some(sha256) => sha256.clone(),
+        None => {
+            let session = crate::encrypted_file_session(&app_handle, &file.file_path);
+            let path = PathBuf::from(&file.file_path);
+            tokio::task::spawn_blocking(move || crate::checksums::sha256_file(&path, session.as_deref()))
+                .await
+                .map_err(|e| Error::IoError(std::io::Error::other(e)))??
+        }
+    };
+
+    let metadata = ClipboardMetadata {
+        id: file.id.clone(),
+        original_url: file.original_url.clone(),
+        file_name: file.file_name.clone(),
+        mime_type: file.mime_type.clone(),
+        size: file.size,
+        sha256,
+        conversation: conversation.or_else(|| file.conversation.clone()),
+    };
+
+    let path = crate::shareable_path(&app_handle, &file.file_path)?;
+    let json = serde_json::to_vec(&metadata).map_err(|e| Error::Clipboard(e.to_string()))?;
+
+    let id = file.id.clone();
+    run_blocking(&app_handle, move |app_handle| {
+        preserve_previous(app_handle);
+        set_file_with_metadata(app_handle, &path, &json)?;
+        record_copy(app_handle, vec![id], vec![PathBuf::from(path)]);
+        Ok(())
+    })
+    .await?;
+
+    Ok(metadata)
+}
+
+// Command to copy several downloaded files to the clipboard as one file list, to paste them into a single message
+#[tauri::command]
+pub async fn copy_attachments(app_handle: AppHandle, ids: Vec<String>, mode: Option<CopyMode>) -> Result<(), Error> {
+    if ids.is_empty() {
+        return Err(Error::Clipboard("No files to copy".to_string()));
+    }
+
+    let mut paths = Vec::with_capacity(ids.len());
+    for id in &ids {
+        let file = downloaded_file(&app_handle, id)?;
+        paths.push(PathBuf::from(crate::shareable_path(&app_handle, &file.file_path)?));
+    }
+
+    run_blocking(&app_handle, move |app_handle| {
+        preserve_previous(app_handle);
+        let used = copy_paths(app_handle, &paths, mode.unwrap_or_default())?;
+        record_paths_copy(app_handle, ids, paths, used);
+        Ok(())
+    })
+    .await
+}
+
+// Command to copy a downloaded image to the clipboard as a bitmap, which chat apps paste inline
+#[tauri::command]
+pub async fn copy_image_to_clipboard(app_handle: AppHandle, id: String) -> Result<(), Error> {
+    let file = downloaded_file(&app_handle, &id)?;
+    if !file.mime_type.starts_with("image/") {
+        return Err(Error::Clipboard(format!("{} is not an image", file.file_name)));
+    }
+
+    run_blocking(&app_handle, |app_handle| {
+        preserve_previous(app_handle);
+        Ok(())
+    })
+    .await?;
+    copy_image(&app_handle, &file).await
+}
+
+/// Put an image attachment on the clipboard as a file and as a bitmap in one go: chat apps
+/// take the file, mail and office apps the bitmap
+pub async fn copy_image(app_handle: &AppHandle, file: &FileInfo) -> Result<(), Error> {
+    let session = crate::encrypted_file_session(app_handle, &file.file_path);
+    let path = PathBuf::from(&file.file_path);
+    let image = tokio::task::spawn_blocking(move || {
+        let bytes = match session {
+            Some(session) => session.decrypt_to_vec(&path)?,
+            None => std::fs::read(&path)?,
+        };
+        encode_image(&bytes)
+    })
+    .await
+    .map_err(|e| Error::IoError(std::io::Error::other(e)))??;
+
+    let path = PathBuf::from(crate::shareable_path(app_handle, &file.file_path)?);
+    let id = file.id.clone();
+    run_blocking(app_handle, move |app_handle| {
+        set_image(app_handle, &image, &path)?;
+        record_copy(app_handle, vec![id], vec![path]);
+        Ok(())
+    })
+    .await
+}
+
+/// Decode an image file into the clipboard bitmap formats
+fn encode_image(bytes: &[u8]) -> Result<ClipboardImage, Error> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| Error::Clipboard(format!("Failed to decode image: {}", e)))?
+        .into_rgba8();
+    let (width, height) = image.dimensions();
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| Error::Clipboard(format!("Failed to encode image: {}", e)))?;
+
+    let pixels_size = width as usize * height as usize * 4;
+    let mut dib = Vec::with_capacity(DIB_HEADER_SIZE as usize + pixels_size);
+    dib.extend_from_slice(&DIB_HEADER_SIZE.to_le_bytes());
+    dib.extend_from_slice(&(width as i32).to_le_bytes());
+    // A positive height means the rows are stored bottom-up
+    dib.extend_from_slice(&(height as i32).to_le_bytes());
+    dib.extend_from_slice(&1u16.to_le_bytes()); // planes
+    dib.extend_from_slice(&32u16.to_le_bytes()); // bits per pixel
+    dib.extend_from_slice(&0u32.to_le_bytes()); // BI_RGB
+    dib.extend_from_slice(&(pixels_size as u32).to_le_bytes());
+    dib.extend_from_slice(&[0; 16]); // resolution and palette
+    for row in image.rows().rev() {
+        for pixel in row {
+            let [r, g, b, a] = pixel.0;
+            dib.extend_from_slice(&[b, g, r, a]);
+        }
+    }
+
+    Ok(ClipboardImage { dib, png })
+}
+
+// Command to copy plain text, e.g. a reply template, to the clipboard
+#[tauri::command]
+pub async fn copy_text(app_handle: AppHandle, text: String) -> Result<(), Error> {
+    run_blocking(&app_handle, move |app_handle| {
+        preserve_previous(app_handle);
+        set_text(app_handle, &text, None)?;
+        record_copy(app_handle, Vec::new(), Vec::new());
+        Ok(())
+    })
+    .await
+}
+
+// Command to copy formatted text to the clipboard, with plain text for applications that don't take HTML
+#[tauri::command]
+pub async fn copy_html(app_handle: AppHandle, html: String, plain_fallback: String) -> Result<(), Error> {
+    run_blocking(&app_handle, move |app_handle| {
+        preserve_previous(app_handle);
+        set_text(app_handle, &plain_fallback, Some(&html))?;
+        record_copy(app_handle, Vec::new(), Vec::new());
+        Ok(())
+    })
+    .await
+}
+
+// Command to put back what was on the clipboard before the helper's copies; false when nothing was preserved
+#[tauri::command]
+pub async fn restore_clipboard(app_handle: AppHandle) -> Result<bool, Error> {
+    run_blocking(&app_handle, restore_previous).await
+}
+
+fn too_large_for_data_uri(file_name: &str, size: u64, max_size: u64) -> Error {
+    Error::Clipboard(format!(
+        "{} is {} bytes, more than the {} bytes allowed for a data URI",
+        file_name, size, max_size
+    ))
+}
+
+// Command to copy a small downloaded file as a base64 `data:` URI, for rich-text editors that paste those
+#[tauri::command]
+pub async fn copy_as_data_uri(app_handle: AppHandle, id: String, max_size: Option<u64>) -> Result<(), Error> {
+    let file = downloaded_file(&app_handle, &id)?;
+    let max_size = max_size.unwrap_or(DEFAULT_DATA_URI_MAX_BYTES);
+    if file.size > max_size {
+        return Err(too_large_for_data_uri(&file.file_name, file.size, max_size));
+    }
+
+    let session = crate::encrypted_file_session(&app_handle, &file.file_path);
+    let path = PathBuf::from(&file.file_path);
+    let bytes = tokio::task::spawn_blocking(move || match session {
+        Some(session) => session.decrypt_to_vec(&path),
+        None => std::fs::read(&path).map_err(Error::from),
+    })
+    .await
+    .map_err(|e| Error::IoError(std::io::Error::other(e)))??;
+    // The recorded size may be stale if the file was replaced since
+    if bytes.len() as u64 > max_size {
+        return Err(too_large_for_data_uri(&file.file_name, bytes.len() as u64, max_size));
+    }
+
+    let uri = format!(
+        "data:{};base64,{}",
+        file.mime_type,
+        base64::engine::general_purpose::STANDARD.encode(&bytes)
+    );
+    run_blocking(&app_handle, move |app_handle| {
+        preserve_previous(app_handle);
+        set_text(app_handle, &uri, None)?;
+        record_copy(app_handle, vec![file.id], Vec::new());
+        Ok(())
+    })
+    .await
+}
+
+/// Window that owns the clipboard while we write to it
+#[cfg(windows)]
+fn owner_window(app_handle: &AppHandle) -> *mut std::ffi::c_void {
+    // SetClipboardData may fail after EmptyClipboard when the clipboard has no owner window
+    app_handle
+        .get_webview_window("main")
+        .and_then(|w| w.hwnd().ok())
+        .map(|hwnd| hwnd.0)
+        .unwrap_or(std::ptr::null_mut())
+}
+
+#[cfg(windows)]
+fn set_file_with_metadata(app_handle: &AppHandle, path: &str, json: &[u8]) -> Result<(), Error> {
+    windows::set_file_with_data(owner_window(app_handle), path, windows::METADATA_FORMAT, json)
+}
+
+#[cfg(windows)]
+fn set_image(app_handle: &AppHandle, image: &ClipboardImage, path: &Path) -> Result<(), Error> {
+    windows::set_image(owner_window(app_handle), &image.dib, &image.png, path)
+}
+
+#[cfg(windows)]
+fn set_text(app_handle: &AppHandle, text: &str, html: Option<&str>) -> Result<(), Error> {
+    windows::set_text(owner_window(app_handle), text, html)
+}
+
+#[cfg(target_os = "macos")]
+fn set_text(_app_handle: &AppHandle, text: &str, html: Option<&str>) -> Result<(), Error> {
+    macos::set_text(text, html)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_text(_app_handle: &AppHandle, text: &str, html: Option<&str>) -> Result<(), Error> {
+    match x11::set_text(text, html) {
+        // Without XWayland there is no X server to take the clipboard, so paths copied as
+        // text and restored snapshots go through wl-copy, as plain text only
+        Err(e) if std::env::var_os("WAYLAND_DISPLAY").is_some() => {
+            println!("Copying text with wl-copy: {}", e);
+            wl_copy(text)
+        }
+        result => result,
+    }
+}
+
+/// Put `text` on the Wayland clipboard; wl-copy stays in the background to serve it
+#[cfg(all(unix, not(target_os = "macos")))]
+fn wl_copy(text: &str) -> Result<(), Error> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new("wl-copy")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Clipboard(format!("Failed to run wl-copy: {}", e)))?;
+    // Dropping stdin closes it, which tells wl-copy the text is complete
+    let written = child.stdin.take().map(|mut stdin| stdin.write_all(text.as_bytes()));
+    let status = child
+        .wait()
+        .map_err(|e| Error::Clipboard(format!("Failed to run wl-copy: {}", e)))?;
+    if let Some(Err(e)) = written {
+        return Err(Error::Clipboard(format!("Failed to pass the text to wl-copy: {}", e)));
+    }
+    if !status.success() {
+        return Err(Error::Clipboard(format!("wl-copy failed: {}", status)));
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn set_image(_app_handle: &AppHandle, _image: &ClipboardImage, _path: &Path) -> Result<(), Error> {
+    Err(Error::Clipboard("Copying images is only supported on Windows".to_string()))
+}
+
+#[cfg(not(windows))]
+fn set_file_with_metadata(_app_handle: &AppHandle, _path: &str, _json: &[u8]) -> Result<(), Error> {
+    Err(Error::Clipboard("Copying files with metadata is only supported on Windows".to_string()))
+}
+
@@ -0,0 +1,375 @@
+/*!
+ * Windows clipboard formats written through the Win32 API
+ */
+
+use std::ffi::c_void;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use windows_sys::Win32::Foundation::{ERROR_ACCESS_DENIED, GlobalFree, HGLOBAL, HWND, LPARAM, LRESULT, POINT, WPARAM};
+use windows_sys::Win32::System::DataExchange::{
+    AddClipboardFormatListener, CloseClipboard, EmptyClipboard, EnumClipboardFormats, GetClipboardData, GetClipboardFormatNameW,
+    GetClipboardSequenceNumber, OpenClipboard, RegisterClipboardFormatW, SetClipboardData,
+};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE};
+use windows_sys::Win32::System::Ole::{CF_DIB, CF_HDROP, CF_UNICODETEXT};
+use windows_sys::Win32::UI::Shell::{DragQueryFileW, DROPFILES};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW, TranslateMessage,
+    HWND_MESSAGE, MSG, WM_CLIPBOARDUPDATE, WNDCLASSW,
+};
+
+use super::{ClipboardContents, ClipboardFileCopy};
+use crate::Error;
+
+/// Registered clipboard format holding the attachment metadata as UTF-8 JSON
+pub const METADATA_FORMAT: &str = "WAB2B.AttachmentMetadata";
+
+/// Registered format name browsers and Office use for PNG images
+const PNG_FORMAT: &str = "PNG";
+
+/// Registered format for HTML fragments, see [`cf_html`]
+const HTML_FORMAT: &str = "HTML Format";
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+/// `CF_HDROP` payload: header, each path NUL-terminated, final NUL
+fn hdrop(paths: &[String]) -> Vec<u8> {
+    let header = DROPFILES {
+        pFiles: size_of::<DROPFILES>() as u32,
+        pt: POINT { x: 0, y: 0 },
+        fNC: 0,
+        fWide: 1,
+    };
+
+    // SAFETY: DROPFILES is a packed plain-data struct
+    let mut bytes = unsafe {
+        std::slice::from_raw_parts(&header as *const DROPFILES as *const u8, size_of::<DROPFILES>())
+    }
+    .to_vec();
+
+    for unit in paths.iter().flat_map(|path| wide(path)).chain(std::iter::once(0)) {
+        bytes.extend_from_slice(&unit.to_le_bytes());
+    }
+    bytes
+}
+
+/// `CF_UNICODETEXT` payload: NUL-terminated UTF-16
+fn unicode_text(text: &str) -> Vec<u8> {
+    wide(text).into_iter().flat_map(u16::to_le_bytes).collect()
+}
+
+/// `HTML Format` payload: a header with the byte offsets of the document and of the
+/// fragment inside it, followed by the UTF-8 document
+fn cf_html(fragment: &str) -> Vec<u8> {
+    const PREFIX: &str = "<html><body><!--StartFragment-->";
+    const SUFFIX: &str = "<!--EndFragment--></body></html>";
+    let header = |start_html: usize, end_html: usize, start_fragment: usize, end_fragment: usize| {
+        format!(
+            "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\nStartFragment:{:010}\r\nEndFragment:{:010}\r\n",
+            start_html, end_html, start_fragment, end_fragment
+        )
+    };
+
+    // Offsets have a fixed width, so the header is as long with placeholder values
+    let start_html = header(0, 0, 0, 0).len();
+    let start_fragment = start_html + PREFIX.len();
+    let end_fragment = start_fragment + fragment.len();
+    let end_html = end_fragment + SUFFIX.len();
+
+    let header = header(start_html, end_html, start_fragment, end_fragment);
+    let mut bytes = format!("{}{}{}{}", header, PREFIX, fragment, SUFFIX).into_bytes();
+    bytes.push(0);
+    bytes
+}
+
+/// Movable global memory holding `bytes`, as `SetClipboardData` expects
+unsafe fn global_copy(bytes: &[u8]) -> Result<HGLOBAL, Error> {
+    let handle = GlobalAlloc(GMEM_MOVEABLE, bytes.len());
+    if handle.is_null() {
+        return Err(Error::Clipboard("Failed to allocate clipboard memory".to_string()));
+    }
+
+    let target = GlobalLock(handle) as *mut u8;
+    if target.is_null() {
+        GlobalFree(handle);
+        return Err(Error::Clipboard("Failed to lock clipboard memory".to_string()));
+    }
+    ptr::copy_nonoverlapping(bytes.as_ptr(), target, bytes.len());
+    GlobalUnlock(handle);
+
+    Ok(handle)
+}
+
+/// Hand `bytes` to the clipboard under `format`; the clipboard owns the memory on success
+unsafe fn set_data(format: u32, bytes: &[u8]) -> Result<(), Error> {
+    let handle = global_copy(bytes)?;
+    if SetClipboardData(format, handle).is_null() {
+        GlobalFree(handle);
+        return Err(Error::Clipboard(format!(
+            "Failed to set clipboard format {}: {}",
+            format,
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+fn register_format(format_name: &str) -> Result<u32, Error> {
+    let format = unsafe { RegisterClipboardFormatW(wide(format_name).as_ptr()) };
+    if format == 0 {
+        return Err(Error::Clipboard(format!("Failed to register clipboard format {}", format_name)));
+    }
+    Ok(format)
+}
+
+/// Attempts at opening the clipboard before giving up
+const OPEN_ATTEMPTS: u32 = 8;
+
+/// Wait after the first failed attempt, doubled after each further one
+const OPEN_FIRST_DELAY: Duration = Duration::from_millis(10);
+
+unsafe fn open(owner: *mut c_void) -> Result<(), Error> {
+    // Another application holding the clipboard usually lets go within a few milliseconds
+    let mut delay = OPEN_FIRST_DELAY;
+    let mut attempt = 1;
+    while OpenClipboard(owner as HWND) == 0 {
+        let error = std::io::Error::last_os_error();
+        if attempt == OPEN_ATTEMPTS {
+            return Err(match error.raw_os_error() {
+                Some(code) if code == ERROR_ACCESS_DENIED as i32 => Error::ClipboardBusy,
+                _ => Error::Clipboard(format!("Failed to open clipboard: {}", error)),
+            });
+        }
+        std::thread::sleep(delay);
+        delay *= 2;
+        attempt += 1;
+    }
+    Ok(())
+}
+
+/// Open and empty the clipboard, run `fill` and close it again
+unsafe fn replace_contents(owner: *mut c_void, fill: impl FnOnce() -> Result<(), Error>) -> Result<(), Error> {
+    open(owner)?;
+
+    let result = if EmptyClipboard() == 0 {
+        Err(Error::Clipboard("Failed to empty clipboard".to_string()))
+    } else {
+        fill()
+    };
+
+    CloseClipboard();
+    result
+}
+
+/// Replace the clipboard with the file at `path` plus `data` in the registered format `format_name`
+pub fn set_file_with_data(owner: *mut c_void, path: &str, format_name: &str, data: &[u8]) -> Result<(), Error> {
+    let custom_format = register_format(format_name)?;
+    unsafe {
+        replace_contents(owner, || {
+            set_data(CF_HDROP as u32, &hdrop(&[path.to_string()]))?;
+            set_data(custom_format, data)
+        })
+    }
+}
+
+/// Replace the clipboard with `text`, plus `html` as `HTML Format` when given
+pub fn set_text(owner: *mut c_void, text: &str, html: Option<&str>) -> Result<(), Error> {
+    let html_format = html.map(|_| register_format(HTML_FORMAT)).transpose()?;
+    unsafe {
+        replace_contents(owner, || {
+            set_data(CF_UNICODETEXT as u32, &unicode_text(text))?;
+            match (html_format, html) {
+                (Some(format), Some(html)) => set_data(format, &cf_html(html)),
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+/// Replace the clipboard with the image file at `path`, as `CF_HDROP`, together with its
+/// pixels as `CF_DIB` and as registered `PNG`
+pub fn set_image(owner: *mut c_void, dib: &[u8], png: &[u8], path: &Path) -> Result<(), Error> {
+    let png_format = register_format(PNG_FORMAT)?;
+    let path = path.to_string_lossy().to_string();
+    unsafe {
+        replace_contents(owner, || {
+            set_data(CF_HDROP as u32, &hdrop(&[path]))?;
+            set_data(CF_DIB as u32, dib)?;
+            set_data(png_format, png)
+        })
+    }
+}
+
+/// File list backend: `CF_HDROP`, as Explorer uses
+pub struct FileClipboard {
+    pub owner: *mut c_void,
+}
+
+impl ClipboardFileCopy for FileClipboard {
+    fn copy_files(&self, paths: &[PathBuf]) -> Result<(), Error> {
+        let paths: Vec<String> = paths.iter().map(|p| p.to_string_lossy().to_string()).collect();
+        unsafe { replace_contents(self.owner, || set_data(CF_HDROP as u32, &hdrop(&paths))) }
+    }
+}
+
+/// Name of a clipboard format: the constant name for predefined ones, else the registered name
+fn format_name(format: u32) -> String {
+    let predefined = match format {
+        1 => "CF_TEXT",
+        2 => "CF_BITMAP",
+        3 => "CF_METAFILEPICT",
+        7 => "CF_OEMTEXT",
+        8 => "CF_DIB",
+        13 => "CF_UNICODETEXT",
+        14 => "CF_ENHMETAFILE",
+        15 => "CF_HDROP",
+        16 => "CF_LOCALE",
+        17 => "CF_DIBV5",
+        _ => "",
+    };
+    if !predefined.is_empty() {
+        return predefined.to_string();
+    }
+
+    let mut buffer = [0u16; 256];
+    let length = unsafe { GetClipboardFormatNameW(format, buffer.as_mut_ptr(), buffer.len() as i32) };
+    if length > 0 {
+        String::from_utf16_lossy(&buffer[..length as usize])
+    } else {
+        format!("#{}", format)
+    }
+}
+
+/// `CF_UNICODETEXT` on the open clipboard
+unsafe fn read_text() -> Option<String> {
+    let handle = GetClipboardData(CF_UNICODETEXT as u32);
+    if handle.is_null() {
+        return None;
+    }
+
+    let data = GlobalLock(handle) as *const u16;
+    if data.is_null() {
+        return None;
+    }
+    let units = std::slice::from_raw_parts(data, GlobalSize(handle) / 2);
+    let end = units.iter().position(|&unit| unit == 0).unwrap_or(units.len());
+    let text = String::from_utf16_lossy(&units[..end]);
+    GlobalUnlock(handle);
+
+    Some(text)
+}
+
+/// Paths in `CF_HDROP` on the open clipboard
+unsafe fn read_files() -> Vec<PathBuf> {
+    let handle = GetClipboardData(CF_HDROP as u32);
+    if handle.is_null() {
+        return Vec::new();
+    }
+
+    let count = DragQueryFileW(handle, u32::MAX, ptr::null_mut(), 0);
+    (0..count)
+        .filter_map(|index| {
+            let length = DragQueryFileW(handle, index, ptr::null_mut(), 0) as usize;
+            if length == 0 {
+                return None;
+            }
+            let mut buffer = vec![0u16; length + 1];
+            DragQueryFileW(handle, index, buffer.as_mut_ptr(), buffer.len() as u32);
+            Some(PathBuf::from(String::from_utf16_lossy(&buffer[..length])))
+        })
+        .collect()
+}
+
+/// Formats, text and files currently on the clipboard
+pub fn read_contents(owner: *mut c_void) -> Result<ClipboardContents, Error> {
+    unsafe {
+        open(owner)?;
+
+        let mut formats = Vec::new();
+        let mut format = EnumClipboardFormats(0);
+        while format != 0 {
+            formats.push(format_name(format));
+            format = EnumClipboardFormats(format);
+        }
+        let text = read_text();
+        let files = read_files();
+
+        CloseClipboard();
+        Ok(ClipboardContents { formats, text, files })
+    }
+}
+
+/// Counter Windows bumps on every clipboard change
+pub fn sequence_number() -> u64 {
+    unsafe { GetClipboardSequenceNumber() as u64 }
+}
+
+/// Empty the clipboard
+pub fn clear(owner: *mut c_void) -> Result<(), Error> {
+    unsafe { replace_contents(owner, || Ok(())) }
+}
+
+/// Called on the listener thread whenever the clipboard changes
+static ON_CHANGE: OnceLock<Box<dyn Fn() + Send + Sync>> = OnceLock::new();
+
+unsafe extern "system" fn listener_proc(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if message == WM_CLIPBOARDUPDATE {
+        if let Some(on_change) = ON_CHANGE.get() {
+            on_change();
+        }
+        return 0;
+    }
+    DefWindowProcW(hwnd, message, wparam, lparam)
+}
+
+/// Call `on_change` whenever the clipboard changes. A hidden message-only window on its own
+/// thread receives `WM_CLIPBOARDUPDATE`.
+pub fn listen(on_change: impl Fn() + Send + Sync + 'static) -> Result<(), Error> {
+    if ON_CHANGE.set(Box::new(on_change)).is_err() {
+        return Err(Error::Clipboard("Clipboard listener is already running".to_string()));
+    }
+
+    std::thread::spawn(|| unsafe {
+        let class_name = wide("WAB2BClipboardListener");
+        let instance = GetModuleHandleW(ptr::null());
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(listener_proc),
+            hInstance: instance,
+            lpszClassName: class_name.as_ptr(),
+            ..std::mem::zeroed()
+        };
+        RegisterClassW(&class);
+
+        let hwnd = CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            ptr::null(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            HWND_MESSAGE,
+            ptr::null_mut(),
+            instance,
+            ptr::null(),
+        );
+        if hwnd.is_null() || AddClipboardFormatListener(hwnd) == 0 {
+            println!("Failed to listen for clipboard changes: {}", std::io::Error::last_os_error());
+            return;
+        }
+
+        let mut message: MSG = std::mem::zeroed();
+        while GetMessageW(&mut message, ptr::null_mut(), 0, 0) > 0 {
+            TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+    });
+    Ok(())
+}
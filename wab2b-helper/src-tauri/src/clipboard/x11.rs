@@ -0,0 +1,334 @@
+/*!
+ * X11 clipboard (also reached by most Wayland apps through XWayland): file lists as
+ * `x-special/gnome-copied-files` and `text/uri-list`, text as `UTF8_STRING` and `text/html`
+ */
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, GetPropertyReply, PropMode,
+    SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass, SELECTION_NOTIFY_EVENT,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE};
+
+use super::{ClipboardContents, ClipboardFileCopy};
+use crate::Error;
+
+/// How long the clipboard owner gets to answer a request
+const CONVERT_TIMEOUT: Duration = Duration::from_millis(500);
+
+fn x11_error(e: impl std::fmt::Display) -> Error {
+    Error::Clipboard(format!("X11: {}", e))
+}
+
+/// File list backend. X11 has no clipboard storage: the owner of the selection answers every
+/// paste, so a thread keeps serving the files until something else is copied.
+pub struct FileClipboard;
+
+impl ClipboardFileCopy for FileClipboard {
+    fn copy_files(&self, paths: &[PathBuf]) -> Result<(), Error> {
+        let uris = paths
+            .iter()
+            .map(|path| {
+                url::Url::from_file_path(path)
+                    .map(|url| url.to_string())
+                    .map_err(|_| Error::Clipboard(format!("Not an absolute path: {}", path.display())))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let contents = Contents {
+            // Nautilus, Nemo and Caja paste files from this one
+            gnome_copied_files: Some(format!("copy\n{}", uris.join("\n")).into_bytes()),
+            // RFC 2483 asks for CRLF line ends
+            uri_list: Some(uris.iter().map(|uri| format!("{}\r\n", uri)).collect::<String>().into_bytes()),
+            html: None,
+            text: paths
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+                .into_bytes(),
+        };
+
+        offer(contents)
+    }
+}
+
+/// Put `text` on the clipboard, with `html` for applications that take formatted text
+pub fn set_text(text: &str, html: Option<&str>) -> Result<(), Error> {
+    offer(Contents {
+        gnome_copied_files: None,
+        uri_list: None,
+        html: html.map(|html| html.as_bytes().to_vec()),
+        text: text.as_bytes().to_vec(),
+    })
+}
+
+/// Take the clipboard and keep serving `contents` from a thread until something else is copied
+fn offer(contents: Contents) -> Result<(), Error> {
+    let session = Session::open()?;
+    session.take_clipboard()?;
+    std::thread::spawn(move || session.serve(&contents));
+    Ok(())
+}
+
+struct Contents {
+    gnome_copied_files: Option<Vec<u8>>,
+    uri_list: Option<Vec<u8>>,
+    html: Option<Vec<u8>>,
+    text: Vec<u8>,
+}
+
+struct Atoms {
+    clipboard: Atom,
+    targets: Atom,
+    gnome_copied_files: Atom,
+    uri_list: Atom,
+    html: Atom,
+    utf8_string: Atom,
+    incr: Atom,
+    /// Property on our window that receives converted selections
+    transfer: Atom,
+}
+
+/// Connection with a hidden window, to own the clipboard or read it
+struct Session {
+    connection: RustConnection,
+    window: Window,
+    atoms: Atoms,
+}
+
+impl Session {
+    fn open() -> Result<Self, Error> {
+        let (connection, screen) = x11rb::connect(None).map_err(x11_error)?;
+        let root = connection.setup().roots[screen].root;
+
+        let window = connection.generate_id().map_err(x11_error)?;
+        connection
+            .create_window(
+                COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_ONLY,
+                COPY_FROM_PARENT,
+                &CreateWindowAux::new(),
+            )
+            .map_err(x11_error)?;
+
+        let intern = |name: &str| -> Result<Atom, Error> {
+            Ok(connection
+                .intern_atom(false, name.as_bytes())
+                .map_err(x11_error)?
+                .reply()
+                .map_err(x11_error)?
+                .atom)
+        };
+        let atoms = Atoms {
+            clipboard: intern("CLIPBOARD")?,
+            targets: intern("TARGETS")?,
+            gnome_copied_files: intern("x-special/gnome-copied-files")?,
+            uri_list: intern("text/uri-list")?,
+            html: intern("text/html")?,
+            utf8_string: intern("UTF8_STRING")?,
+            incr: intern("INCR")?,
+            transfer: intern("WAB2B_CLIPBOARD")?,
+        };
+
+        Ok(Self { connection, window, atoms })
+    }
+
+    /// Make our window the owner of the clipboard
+    fn take_clipboard(&self) -> Result<(), Error> {
+        let clipboard = self.atoms.clipboard;
+        self.connection
+            .set_selection_owner(self.window, clipboard, CURRENT_TIME)
+            .map_err(x11_error)?;
+        let owner = self
+            .connection
+            .get_selection_owner(clipboard)
+            .map_err(x11_error)?
+            .reply()
+            .map_err(x11_error)?
+            .owner;
+        if owner != self.window {
+            return Err(Error::Clipboard("Another application kept the clipboard".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Ask the clipboard owner for `target`. None when it isn't offered, or too large to
+    /// arrive in one piece.
+    fn convert(&self, target: Atom) -> Result<Option<GetPropertyReply>, Error> {
+        let atoms = &self.atoms;
+        self.connection
+            .convert_selection(self.window, atoms.clipboard, target, atoms.transfer, CURRENT_TIME)
+            .map_err(x11_error)?;
+        self.connection.flush().map_err(x11_error)?;
+
+        let deadline = Instant::now() + CONVERT_TIMEOUT;
+        loop {
+            match self.connection.poll_for_event().map_err(x11_error)? {
+                Some(Event::SelectionNotify(notify)) if notify.requestor == self.window => {
+                    if notify.property == NONE {
+                        return Ok(None);
+                    }
+                    let reply = self
+                        .connection
+                        .get_property(true, self.window, atoms.transfer, AtomEnum::ANY, 0, u32::MAX / 4)
+                        .map_err(x11_error)?
+                        .reply()
+                        .map_err(x11_error)?;
+                    return Ok((reply.type_ != atoms.incr).then_some(reply));
+                }
+                Some(_) => {}
+                None if Instant::now() > deadline => {
+                    return Err(Error::Clipboard("The clipboard owner did not answer".to_string()));
+                }
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+    }
+
+    /// Answer paste requests until another application takes the clipboard
+    fn serve(self, contents: &Contents) {
+        while let Ok(event) = self.connection.wait_for_event() {
+            match event {
+                Event::SelectionRequest(request) => self.answer(&request, contents),
+                Event::SelectionClear(clear) if clear.owner == self.window => break,
+                _ => {}
+            }
+        }
+        let _ = self.connection.destroy_window(self.window);
+        let _ = self.connection.flush();
+    }
+
+    fn answer(&self, request: &SelectionRequestEvent, contents: &Contents) {
+        let atoms = &self.atoms;
+        // Obsolete clients leave the property out and expect the target name to be used
+        let property = if request.property == NONE { request.target } else { request.property };
+
+        let offered = [
+            (atoms.gnome_copied_files, contents.gnome_copied_files.as_ref()),
+            (atoms.uri_list, contents.uri_list.as_ref()),
+            (atoms.html, contents.html.as_ref()),
+            (atoms.utf8_string, Some(&contents.text)),
+        ];
+        let data = match request.target {
+            t if t == u32::from(AtomEnum::STRING) => Some(&contents.text),
+            t => offered.iter().find(|(target, _)| *target == t).and_then(|(_, data)| *data),
+        };
+
+        let stored = if request.target == atoms.targets {
+            let targets: Vec<Atom> = std::iter::once(atoms.targets)
+                .chain(offered.iter().filter(|(_, data)| data.is_some()).map(|(target, _)| *target))
+                .collect();
+            self.connection
+                .change_property32(PropMode::REPLACE, request.requestor, property, AtomEnum::ATOM, &targets)
+                .is_ok()
+        } else if let Some(data) = data {
+            self.connection
+                .change_property8(PropMode::REPLACE, request.requestor, property, request.target, data)
+                .is_ok()
+        } else {
+            false
+        };
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if stored { property } else { NONE },
+        };
+        let _ = self
+            .connection
+            .send_event(false, request.requestor, EventMask::NO_EVENT, notify);
+        let _ = self.connection.flush();
+    }
+}
+
+/// Formats, text and files currently on the clipboard
+pub fn read_contents() -> Result<ClipboardContents, Error> {
+    let session = Session::open()?;
+    let atoms = &session.atoms;
+
+    let targets: Vec<Atom> = match session.convert(atoms.targets)? {
+        Some(reply) => reply.value32().map(|atoms| atoms.collect()).unwrap_or_default(),
+        None => return Ok(ClipboardContents::default()),
+    };
+
+    let formats = targets
+        .iter()
+        .filter_map(|&atom| {
+            let reply = session.connection.get_atom_name(atom).ok()?.reply().ok()?;
+            Some(String::from_utf8_lossy(&reply.name).to_string())
+        })
+        .collect();
+
+    let text = if targets.contains(&atoms.utf8_string) {
+        session
+            .convert(atoms.utf8_string)?
+            .map(|reply| String::from_utf8_lossy(&reply.value).to_string())
+    } else {
+        None
+    };
+
+    let mut files = Vec::new();
+    if targets.contains(&atoms.uri_list) {
+        if let Some(reply) = session.convert(atoms.uri_list)? {
+            files = String::from_utf8_lossy(&reply.value)
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .filter_map(|line| url::Url::parse(line.trim()).ok()?.to_file_path().ok())
+                .collect();
+        }
+    }
+
+    Ok(ClipboardContents { formats, text, files })
+}
+
+/// Connection [`owner`] reuses, as the clipboard monitor asks every second
+static OWNER_SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+/// Window owning the clipboard; our serving window for as long as our copy is current
+pub fn owner() -> Result<u64, Error> {
+    let mut cached = OWNER_SESSION.lock().unwrap();
+    let session = match cached.take() {
+        Some(session) => session,
+        None => Session::open()?,
+    };
+
+    let owner = session
+        .connection
+        .get_selection_owner(session.atoms.clipboard)
+        .map_err(x11_error)?
+        .reply()
+        .map_err(x11_error)?
+        .owner;
+    // Only kept once it has worked, a broken connection is opened again next time
+    *cached = Some(session);
+    Ok(owner as u64)
+}
+
+/// Empty the clipboard. The previous owner is told it lost the selection, which stops our
+/// serving thread when the copy was ours.
+pub fn clear() -> Result<(), Error> {
+    let session = Session::open()?;
+    session
+        .connection
+        .set_selection_owner(NONE, session.atoms.clipboard, CURRENT_TIME)
+        .map_err(x11_error)?;
+    session.connection.flush().map_err(x11_error)
+}
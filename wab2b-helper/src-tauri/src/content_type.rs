@@ -0,0 +1,38 @@
+/*!
+ * MIME type detection from the leading bytes of a file, with the extension as fallback
+ */
+
+use std::path::Path;
+
+/// MIME types found for a file
+#[derive(Debug, Clone)]
+pub struct MimeTypes {
+    /// Type to use: the detected one when available, else the extension's, else octet-stream
+    pub mime_type: String,
+    /// Type recognized from the file's magic bytes
+    pub detected: Option<String>,
+    /// Type implied by the file extension
+    pub from_extension: Option<String>,
+}
+
+/// Detect the MIME type of the plaintext file at `path`.
+///
+/// Signed URLs often have no extension at all, and a wrong one is common for attachments,
+/// so the content wins over the name.
+pub fn detect(path: &Path) -> MimeTypes {
+    let detected = match infer::get_from_path(path) {
+        Ok(kind) => kind.map(|kind| kind.mime_type().to_string()),
+        Err(e) => {
+            println!("Failed to sniff content type of {}: {}", path.display(), e);
+            None
+        }
+    };
+    let from_extension = mime_guess::from_path(path).first().map(|mime| mime.to_string());
+
+    let mime_type = detected
+        .clone()
+        .or_else(|| from_extension.clone())
+        .unwrap_or_else(|| mime_guess::mime::APPLICATION_OCTET_STREAM.to_string());
+
+    MimeTypes { mime_type, detected, from_extension }
+}
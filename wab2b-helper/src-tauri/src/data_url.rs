@@ -0,0 +1,183 @@
+/*!
+ * `data:` URLs from deep link payloads, decoded straight into a registered file
+ */
+
+use std::sync::{Arc, Mutex};
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+
+use crate::{ephemeral, AppState, Error, FileInfo};
+
+/// Largest decoded payload accepted; anything bigger belongs behind a real URL
+const MAX_DATA_URL_BYTES: u64 = 20 * 1024 * 1024;
+
+/// Most encoded characters per decoded byte, from `%XX` escapes
+const MAX_ENCODING_OVERHEAD: u64 = 3;
+
+/// Decoded `data:[<mime>][;charset=...][;base64],<data>`
+struct DataUrl {
+    mime_type: Option<String>,
+    data: Vec<u8>,
+}
+
+fn invalid(url: &str) -> Error {
+    // Only the start: the payload itself is of no use in an error message
+    Error::InvalidUrl(url.chars().take(64).collect())
+}
+
+fn too_large(size: u64, max_size: u64) -> Error {
+    Error::FileTooLarge(size, max_size / (1024 * 1024))
+}
+
+/// Decode `url`, refusing payloads that decode to more than `max_size` bytes
+fn parse(url: &str, max_size: u64) -> Result<DataUrl, Error> {
+    let rest = url
+        .get(..5)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+        .map(|_| &url[5..])
+        .ok_or_else(|| invalid(url))?;
+    let (header, payload) = rest.split_once(',').ok_or_else(|| invalid(url))?;
+    // Too long to fit however it is encoded, so not worth decoding
+    if payload.len() as u64 > max_size * MAX_ENCODING_OVERHEAD {
+        return Err(too_large(payload.len() as u64, max_size));
+    }
+
+    let mut params = header.split(';').map(str::trim);
+    let mime_type = params
+        .next()
+        .filter(|mime| mime.contains('/'))
+        .map(|mime| mime.to_ascii_lowercase());
+    let is_base64 = params.any(|param| param.eq_ignore_ascii_case("base64"));
+
+    let payload = percent_decode(payload);
+    let data = if is_base64 {
+        // Line breaks and spaces are common in pasted payloads
+        let compact: Vec<u8> = payload.into_iter().filter(|b| !b.is_ascii_whitespace()).collect();
+        base64::engine::general_purpose::STANDARD
+            .decode(compact.trim_ascii_end())
+            .or_else(|_| base64::engine::general_purpose::STANDARD_NO_PAD.decode(compact.trim_ascii_end()))
+            .map_err(|_| invalid(url))?
+    } else {
+        payload
+    };
+    if data.len() as u64 > max_size {
+        return Err(too_large(data.len() as u64, max_size));
+    }
+
+    Ok(DataUrl { mime_type, data })
+}
+
+/// Undo `%XX` escapes, leaving malformed ones as they are
+pub fn percent_decode(input: &str) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| input.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                output.push(byte);
+                i += 3;
+            }
+            None => {
+                output.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    output
+}
+
+/// Decode a `data:` URL into a file of its own and register it like a download
+pub async fn register(app_handle: &AppHandle, url: &str) -> Result<FileInfo, Error> {
+    // The size limit from the settings applies too, when it is the lower one
+    let settings = crate::settings::load_settings(app_handle).unwrap_or_default();
+    let max_size = match settings.max_file_size_mb.saturating_mul(1024 * 1024) {
+        0 => MAX_DATA_URL_BYTES,
+        limit => limit.min(MAX_DATA_URL_BYTES),
+    };
+    let DataUrl { mime_type, data } = parse(url, max_size)?;
+
+    let id = crate::generate_file_id();
+    let extension = mime_type
+        .as_deref()
+        .and_then(|mime| mime_guess::get_mime_extensions_str(mime))
+        .and_then(|extensions| extensions.first())
+        .map(|extension| format!(".{}", extension))
+        .unwrap_or_default();
+    let mut file_name = format!("attachment{}", extension);
+
+    // Saved where finished downloads go: the download folder, except in an ephemeral session,
+    // whose files stay in the temp dir
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let download_dir = crate::stable_download_dir(&settings)
+        .filter(|_| ephemeral::active_session(app_handle).is_none());
+    let file_path = match download_dir {
+        Some(download_dir) => {
+            tokio::fs::create_dir_all(&download_dir).await?;
+            let file_path = crate::paths::unique_file_path(&download_dir, &file_name)?;
+            if let Some(name) = file_path.file_name() {
+                file_name = name.to_string_lossy().to_string();
+            }
+            file_path
+        }
+        None => {
+            let temp_dir = state.lock().unwrap().temp_dir.path().join(&id);
+            tokio::fs::create_dir_all(&temp_dir).await?;
+            temp_dir.join(&file_name)
+        }
+    };
+    tokio::fs::write(&file_path, &data).await?;
+
+    let sha256 = crate::checksums::to_hex(&Sha256::digest(&data));
+    let mut mime_types = crate::content_type::detect(&file_path);
+    // The declared type beats the made-up extension, but not the content
+    if mime_types.detected.is_none() {
+        if let Some(declared) = &mime_type {
+            mime_types.mime_type = declared.clone();
+        }
+    }
+
+    let encrypted = match ephemeral::active_session(app_handle) {
+        Some(session) => {
+            let path = file_path.clone();
+            tokio::task::spawn_blocking(move || session.encrypt_file(&path))
+                .await
+                .map_err(|e| Error::IoError(std::io::Error::other(e)))??;
+            true
+        }
+        None => false,
+    };
+
+    let file_info = FileInfo {
+        id: id.clone(),
+        original_url: String::new(),
+        file_path: file_path.to_string_lossy().to_string(),
+        file_name,
+        mime_type: mime_types.mime_type,
+        detected_mime_type: mime_types.detected,
+        extension_mime_type: mime_types.from_extension,
+        size: data.len() as u64,
+        encrypted,
+        sha256: Some(sha256),
+        source_url: None,
+        resolved_url: None,
+        redirect_chain: Vec::new(),
+        short_url: None,
+        etag: None,
+        last_modified: None,
+        cached: false,
+        conversation: None,
+        tags: Vec::new(),
+    };
+
+    let mut app_state = state.lock().unwrap();
+    app_state.current_file = Some(file_info.clone());
+    app_state.downloaded_files.insert(id, file_info.clone());
+
+    Ok(file_info)
+}
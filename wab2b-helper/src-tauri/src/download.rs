@@ -0,0 +1,1379 @@
+/*!
+ * Streaming download engine shared by the download commands
+ */
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::io::SeekFrom;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use futures_util::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE};
+use reqwest::{RequestBuilder, StatusCode};
+use tokio_util::sync::CancellationToken;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::settings::{AppSettings, ProgressEvents, RetryPolicy};
+use crate::Error;
+
+/// State of a download that can be started, paused and resumed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadJob {
+    /// File ID the download will be registered under
+    pub id: String,
+    /// URL currently downloaded from
+    pub url: String,
+    /// Primary URL followed by its mirrors, in the order they are tried
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// File name shown to the user
+    pub file_name: String,
+    /// Destination path of the (partial) file
+    pub file_path: PathBuf,
+    /// Number of bytes already written to disk
+    pub downloaded: u64,
+    /// Total size in bytes (0 if unknown)
+    pub total: u64,
+    /// Whether the server accepts `Range` requests
+    pub supports_range: bool,
+    /// ETag of the first response, used to make sure a resume targets the same content
+    pub etag: Option<String>,
+    /// Extra request headers; never serialized so credentials don't end up in events
+    #[serde(skip)]
+    pub headers: HashMap<String, String>,
+    /// Token sent as `Authorization: Bearer`; never serialized
+    #[serde(skip)]
+    pub bearer_token: Option<String>,
+    /// SHA-256 state over the bytes written so far, rebuilt from the partial file when missing
+    #[serde(skip)]
+    pub hasher: Option<Sha256>,
+    /// When the signed URL stops working, in seconds since the Unix epoch
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    /// Whether the partial file is being filled over several connections, possibly with gaps
+    #[serde(default)]
+    pub segmented: bool,
+    /// URLs passed through before reaching the content, starting with `url`; empty without redirects
+    #[serde(default)]
+    pub redirect_chain: Vec<String>,
+    /// Shortened link that expanded to `url`
+    #[serde(default)]
+    pub short_url: Option<String>,
+    /// Place in the queue relative to other waiting downloads
+    #[serde(default)]
+    pub priority: crate::queue::Priority,
+    /// `Last-Modified` of the first response
+    #[serde(default)]
+    pub last_modified: Option<String>,
+    /// Earlier copy to revalidate with a conditional request instead of downloading again
+    #[serde(default)]
+    pub revalidate: Option<Revalidation>,
+}
+
+impl DownloadJob {
+    /// Hex digest of the downloaded content, once the download has completed
+    pub fn take_sha256(&mut self) -> Option<String> {
+        self.hasher
+            .take()
+            .map(|hasher| crate::checksums::to_hex(&hasher.finalize()))
+    }
+}
+
+/// Handle used to signal a running download
+#[derive(Debug, Clone, Default)]
+pub struct DownloadControl {
+    paused: Arc<AtomicBool>,
+    cancel: CancellationToken,
+}
+
+impl DownloadControl {
+    /// Ask the download loop to stop after the current chunk and keep the partial file
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Resolves once the download has been cancelled
+    pub async fn cancelled(&self) {
+        self.cancel.cancelled().await
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Abort the download as soon as possible, including while waiting on the network
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+}
+
+/// How a call to [`run`] ended
+pub enum DownloadOutcome {
+    Completed,
+    Paused,
+    Cancelled,
+    /// The server confirmed the copy named in `job.revalidate` is still current
+    NotModified,
+}
+
+/// A registered copy of the same URL, replaced only when the server reports a change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revalidation {
+    pub file_id: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Payload of the `download-started` event
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadStarted {
+    pub id: String,
+    pub url: String,
+    pub file_name: String,
+    pub resumed_from: u64,
+}
+
+/// Payload of the `link-expiring` event
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkExpiring {
+    pub id: String,
+    pub url: String,
+    /// Seconds since the Unix epoch
+    pub expires_at: u64,
+    pub seconds_left: u64,
+    /// Whether the download was paused when the warning fired
+    pub paused: bool,
+}
+
+/// How long before a link expires the frontend is warned
+const EXPIRY_WARNING: Duration = Duration::from_secs(120);
+
+/// Expiry from an `expires_at` query parameter, in seconds or milliseconds since the Unix epoch
+pub fn expires_at_from_url(url: &url::Url) -> Option<u64> {
+    let (_, value) = url.query_pairs().find(|(key, _)| key == "expires_at")?;
+    let value: u64 = value.trim().parse().ok()?;
+
+    // Anything this large can only be milliseconds
+    Some(if value > 100_000_000_000 { value / 1000 } else { value })
+}
+
+/// Emit `link-expiring` shortly before `expires_at` if download `id` hasn't finished by then
+pub fn watch_expiry(app_handle: &AppHandle, id: &str, url: &str, expires_at: u64) {
+    let app_handle = app_handle.clone();
+    let id = id.to_string();
+    let url = url.to_string();
+
+    tauri::async_runtime::spawn(async move {
+        let warn_at = UNIX_EPOCH + Duration::from_secs(expires_at).saturating_sub(EXPIRY_WARNING);
+        if let Ok(wait) = warn_at.duration_since(SystemTime::now()) {
+            tokio::time::sleep(wait).await;
+        }
+
+        let pending = {
+            let state = app_handle.state::<Arc<std::sync::Mutex<crate::AppState>>>();
+            let app_state = state.lock().unwrap();
+            // A failover may have moved the job to a mirror in the meantime
+            match app_state.paused_downloads.get(&id) {
+                Some(job) => Some((job.url.clone(), true)),
+                None => app_state.active_downloads.contains_key(&id).then_some((url, false)),
+            }
+        };
+        let Some((url, paused)) = pending else {
+            return;
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let _ = app_handle.emit("link-expiring", LinkExpiring {
+            id,
+            url,
+            expires_at,
+            seconds_left: expires_at.saturating_sub(now),
+            paused,
+        });
+    });
+}
+
+/// Validate caller-supplied headers before a download starts
+pub fn parse_headers(headers: &HashMap<String, String>) -> Result<HeaderMap, Error> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+
+    for (name, value) in headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| Error::InvalidHeader(name.clone()))?;
+        let header_value = HeaderValue::from_str(value)
+            .map_err(|_| Error::InvalidHeader(name.clone()))?;
+        map.insert(header_name, header_value);
+    }
+
+    Ok(map)
+}
+
+/// Whether `host` matches an allowlist or pin `pattern`: the exact host, or any subdomain for `*.domain`
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host.ends_with(&format!(".{}", domain)),
+        None => host == pattern,
+    }
+}
+
+/// Refuse URLs whose host isn't in `allowed_hosts` (an empty list allows every host)
+/// or is a private IP address
+pub fn check_host_allowed(settings: &AppSettings, url: &url::Url) -> Result<(), Error> {
+    crate::network::check_url(settings, url)?;
+    if settings.allowed_hosts.is_empty() {
+        return Ok(());
+    }
+
+    let host = url.host_str().unwrap_or_default();
+    if settings.allowed_hosts.iter().any(|pattern| host_matches(pattern, host)) {
+        Ok(())
+    } else {
+        Err(Error::HostNotAllowed(host.to_string()))
+    }
+}
+
+/// Redirects followed by the client's latest request, filled in by its redirect policy
+pub type RedirectChain = Arc<Mutex<Vec<String>>>;
+
+/// Build the HTTP client used for attachment downloads
+pub fn build_client(
+    settings: &AppSettings,
+    chain: RedirectChain,
+    pin_failure: crate::pinning::PinFailure,
+) -> Result<reqwest::Client, Error> {
+    let max_redirects = settings.max_redirects;
+    let redirect_settings = settings.clone();
+
+    // Shortened links tend to bounce through several hops and occasionally loop
+    let policy = reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() > max_redirects as usize {
+            return attempt.error(format!("Too many redirects (limit {})", max_redirects));
+        }
+        if attempt.previous().contains(attempt.url()) {
+            let message = format!("Redirect loop at {}", attempt.url());
+            return attempt.error(message);
+        }
+        // Every hop has to be on the allowlist, or an allowed host could send the download
+        // anywhere; private host names are checked by the resolver, addresses never reach it
+        if let Err(e) = check_host_allowed(&redirect_settings, attempt.url()) {
+            return attempt.error(e);
+        }
+
+        *chain.lock().unwrap() = attempt
+            .previous()
+            .iter()
+            .chain([attempt.url()])
+            .map(|url| url.to_string())
+            .collect();
+        attempt.follow()
+    });
+
+    let timeouts = &settings.timeouts;
+    let mut builder = reqwest::Client::builder()
+        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
+        .connect_timeout(Duration::from_secs(timeouts.connect_secs.max(1)))
+        .read_timeout(Duration::from_secs(timeouts.read_secs.max(1)))
+        .danger_accept_invalid_certs(true) // Accept invalid certs for better compatibility
+        .dns_resolver(Arc::new(crate::network::GuardedResolver::new(settings)))
+        .redirect(policy);
+    if !settings.certificate_pins.is_empty() {
+        let config = crate::pinning::client_config(&settings.certificate_pins, pin_failure)
+            .map_err(|e| Error::IoError(std::io::Error::other(e)))?;
+        builder = builder.use_preconfigured_tls(config);
+    }
+    // Large files may legitimately take long, so an overall limit is opt-in
+    if timeouts.overall_secs > 0 {
+        builder = builder.timeout(Duration::from_secs(timeouts.overall_secs));
+    }
+    let client = builder.build()?;
+
+    Ok(client)
+}
+
+/// Payload of the `download-progress` event
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferProgress {
+    pub id: String,
+    /// Bytes written so far, including any resumed part
+    pub downloaded: u64,
+    /// Total size in bytes (0 if unknown)
+    pub total: u64,
+    /// Completion percentage, when the total size is known
+    pub percent: Option<f64>,
+    /// Smoothed transfer rate in bytes per second
+    pub bytes_per_sec: f64,
+    /// Estimated seconds until completion, when it can be computed
+    pub eta_secs: Option<u64>,
+}
+
+/// Exponentially smoothed transfer rate estimate
+struct SpeedMeter {
+    last_sample: Instant,
+    bytes_since_sample: u64,
+    bytes_per_sec: f64,
+}
+
+impl SpeedMeter {
+    /// Minimum time between two rate samples, so single chunks don't cause spikes
+    const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+    /// Weight of the newest sample in the moving average
+    const SMOOTHING: f64 = 0.3;
+
+    fn new() -> Self {
+        Self {
+            last_sample: Instant::now(),
+            bytes_since_sample: 0,
+            bytes_per_sec: 0.0,
+        }
+    }
+
+    fn record(&mut self, bytes: u64) {
+        self.bytes_since_sample += bytes;
+
+        let elapsed = self.last_sample.elapsed();
+        if elapsed < Self::SAMPLE_INTERVAL {
+            return;
+        }
+
+        let current = self.bytes_since_sample as f64 / elapsed.as_secs_f64();
+        self.bytes_per_sec = if self.bytes_per_sec == 0.0 {
+            current
+        } else {
+            Self::SMOOTHING * current + (1.0 - Self::SMOOTHING) * self.bytes_per_sec
+        };
+        self.last_sample = Instant::now();
+        self.bytes_since_sample = 0;
+    }
+
+    fn progress(&self, job: &DownloadJob) -> TransferProgress {
+        let known_total = job.total > 0;
+        let remaining = job.total.saturating_sub(job.downloaded);
+
+        TransferProgress {
+            id: job.id.clone(),
+            downloaded: job.downloaded,
+            total: job.total,
+            percent: known_total.then(|| (job.downloaded as f64 / job.total as f64) * 100.0),
+            bytes_per_sec: self.bytes_per_sec,
+            eta_secs: (known_total && self.bytes_per_sec > 0.0)
+                .then(|| (remaining as f64 / self.bytes_per_sec).ceil() as u64),
+        }
+    }
+}
+
+/// Limits `download-progress` events to those the webview can usefully render
+struct ProgressThrottle {
+    interval: Duration,
+    percent_step: f64,
+    last_emit: Instant,
+    last_percent: f64,
+}
+
+impl ProgressThrottle {
+    fn new(settings: &ProgressEvents) -> Self {
+        Self {
+            interval: Duration::from_millis(settings.interval_ms),
+            percent_step: settings.percent_step,
+            last_emit: Instant::now(),
+            last_percent: 0.0,
+        }
+    }
+
+    /// Whether an event for `progress` should be sent now; records it as sent if so
+    fn is_due(&mut self, progress: &TransferProgress) -> bool {
+        let stepped = progress
+            .percent
+            .is_some_and(|percent| self.percent_step > 0.0 && percent - self.last_percent >= self.percent_step);
+        if !stepped && self.last_emit.elapsed() < self.interval {
+            return false;
+        }
+
+        self.last_emit = Instant::now();
+        self.last_percent = progress.percent.unwrap_or(0.0);
+        true
+    }
+}
+
+/// Write buffer whose size follows the observed throughput: small on slow links so
+/// progress stays responsive, large on fast links so fewer writes keep up with the network
+struct AdaptiveBuffer {
+    data: Vec<u8>,
+    /// Size at which the buffer is written out
+    target: usize,
+    /// When the oldest buffered byte arrived
+    since: Instant,
+}
+
+impl AdaptiveBuffer {
+    const MIN_SIZE: usize = 16 * 1024;
+    const MAX_SIZE: usize = 4 * 1024 * 1024;
+    /// Transfer time one write should cover at the current rate
+    const WRITE_INTERVAL: Duration = Duration::from_millis(100);
+    /// Longest time data may wait in the buffer, however slow the link
+    const MAX_DELAY: Duration = Duration::from_millis(250);
+
+    fn new() -> Self {
+        Self {
+            data: Vec::with_capacity(Self::MIN_SIZE),
+            target: Self::MIN_SIZE,
+            since: Instant::now(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn push(&mut self, chunk: &[u8]) {
+        if self.data.is_empty() {
+            self.since = Instant::now();
+        }
+        self.data.extend_from_slice(chunk);
+    }
+
+    /// Resize the write target for a transfer rate in bytes per second
+    fn adapt(&mut self, bytes_per_sec: f64) {
+        let target = (bytes_per_sec * Self::WRITE_INTERVAL.as_secs_f64()) as usize;
+        self.target = target.clamp(Self::MIN_SIZE, Self::MAX_SIZE);
+    }
+
+    fn is_due(&self) -> bool {
+        self.data.len() >= self.target || (!self.data.is_empty() && self.since.elapsed() >= Self::MAX_DELAY)
+    }
+
+    /// Write the buffered data to `file` and feed it to `hasher`, returning the number of bytes written
+    async fn write_to(&mut self, file: &mut tokio::fs::File, hasher: &mut Sha256) -> std::io::Result<u64> {
+        file.write_all(&self.data).await?;
+        hasher.update(&self.data);
+        let written = self.data.len() as u64;
+        self.data.clear();
+        Ok(written)
+    }
+}
+
+/// Token bucket limiting the transfer rate of a download
+struct TokenBucket {
+    /// Refill rate in bytes per second
+    rate: f64,
+    /// Maximum burst in bytes
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Bucket for a `kbps` limit, or `None` when the download is unlimited
+    fn from_kbps(kbps: u64) -> Option<Self> {
+        if kbps == 0 {
+            return None;
+        }
+
+        let rate = kbps as f64 * 1000.0 / 8.0;
+        Some(Self {
+            rate,
+            // Allow roughly a quarter second of burst to keep the stream flowing
+            capacity: (rate / 4.0).max(16.0 * 1024.0),
+            tokens: 0.0,
+            last_refill: Instant::now(),
+        })
+    }
+
+    /// Take `bytes` from the bucket, returning how long to wait before continuing
+    fn take(&mut self, bytes: usize) -> Option<Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = now;
+
+        // Chunks larger than the bucket simply leave it in debt
+        self.tokens -= bytes as f64;
+        (self.tokens < 0.0).then(|| Duration::from_secs_f64(-self.tokens / self.rate))
+    }
+}
+
+/// Payload of the `download-retrying` event
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadRetrying {
+    pub id: String,
+    /// Number of the attempt about to start (the first retry is attempt 2)
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub delay_ms: u64,
+    pub reason: String,
+}
+
+/// Run the download, retrying transient failures according to `policy`.
+///
+/// Later attempts continue from the bytes already on disk when the server supports ranges.
+pub async fn run_with_retry(
+    app_handle: &AppHandle,
+    job: &mut DownloadJob,
+    control: &DownloadControl,
+    settings: &AppSettings,
+) -> Result<DownloadOutcome, Error> {
+    let policy = &settings.download_retry;
+    let mut attempt = 1;
+
+    loop {
+        let err = match run(app_handle, job, control, settings).await {
+            Err(err) if attempt < policy.max_attempts && is_retryable(&err, policy) => err,
+            result => return result,
+        };
+
+        let delay = backoff_delay(policy, attempt);
+        attempt += 1;
+        println!("Download {} failed ({}), retrying in {:?}", job.id, err, delay);
+
+        let _ = app_handle.emit("download-retrying", DownloadRetrying {
+            id: job.id.clone(),
+            attempt,
+            max_attempts: policy.max_attempts,
+            delay_ms: delay.as_millis() as u64,
+            reason: err.to_string(),
+        });
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = control.cancel.cancelled() => return Ok(DownloadOutcome::Cancelled),
+        }
+
+        if control.is_paused() {
+            return Ok(DownloadOutcome::Paused);
+        }
+    }
+}
+
+/// Payload of the `download-failover` event
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadFailover {
+    pub id: String,
+    pub failed_url: String,
+    pub next_url: String,
+    pub reason: String,
+}
+
+/// Run the download from `job.url`, moving on to the next source in `job.sources`
+/// when one fails for good. The job keeps the URL that finally worked.
+pub async fn run_with_mirrors(
+    app_handle: &AppHandle,
+    job: &mut DownloadJob,
+    control: &DownloadControl,
+    settings: &AppSettings,
+) -> Result<DownloadOutcome, Error> {
+    loop {
+        let err = match run_with_retry(app_handle, job, control, settings).await {
+            Err(err) if is_source_failure(&err) => err,
+            result => return result,
+        };
+
+        // A resumed download continues with the sources after the one it was using
+        let position = job.sources.iter().position(|url| *url == job.url);
+        let Some(next_url) = position.and_then(|i| job.sources.get(i + 1)).cloned() else {
+            return Err(err);
+        };
+
+        println!("Download {} failed from {} ({}), trying {}", job.id, job.url, err, next_url);
+        let _ = app_handle.emit("download-failover", DownloadFailover {
+            id: job.id.clone(),
+            failed_url: job.url.clone(),
+            next_url: next_url.clone(),
+            reason: err.to_string(),
+        });
+
+        // Bytes from another server can't be trusted to line up, start over
+        job.url = next_url;
+        job.downloaded = 0;
+        job.total = 0;
+        job.supports_range = false;
+        job.etag = None;
+        job.hasher = None;
+    }
+}
+
+/// Whether an error points at the source rather than at this machine
+fn is_source_failure(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::HttpStatus(..)
+            | Error::RequestError(_)
+            | Error::Timeout(_)
+            | Error::ConnectionClosed(_)
+            | Error::Transfer(_)
+            | Error::LinkExpired(_)
+    )
+}
+
+/// Whether a failed attempt should be retried
+fn is_retryable(err: &Error, policy: &RetryPolicy) -> bool {
+    match err {
+        Error::HttpStatus(status, _) => policy.retry_on_status.contains(status),
+        Error::RequestError(e) => e.is_connect() || e.is_body() || e.is_request(),
+        Error::Timeout(_) | Error::ConnectionClosed(_) => true,
+        _ => false,
+    }
+}
+
+/// Exponential backoff for the retry following `attempt`, with random jitter
+fn backoff_delay(policy: &RetryPolicy, attempt: u32) -> Duration {
+    let exponential = policy
+        .initial_backoff_ms
+        .saturating_mul(1u64 << (attempt - 1).min(20));
+    let base = exponential.min(policy.max_backoff_ms) as f64;
+
+    let jitter = policy.jitter.clamp(0.0, 1.0);
+    let factor = if jitter > 0.0 {
+        rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter)
+    } else {
+        1.0
+    };
+
+    Duration::from_millis((base * factor) as u64)
+}
+
+/// Stream `job.url` into `job.file_path`, continuing from `job.downloaded` when possible.
+///
+/// The job is updated in place so a paused download can later be handed back to this function.
+pub async fn run(
+    app_handle: &AppHandle,
+    job: &mut DownloadJob,
+    control: &DownloadControl,
+    settings: &AppSettings,
+) -> Result<DownloadOutcome, Error> {
+    // FTP and SFTP links share everything after the transfer itself
+    if crate::remote::is_remote(&job.url) {
+        return crate::remote::run(app_handle, job, control, settings).await;
+    }
+
+    let redirects = RedirectChain::default();
+    let pin_failure = crate::pinning::PinFailure::default();
+    let client = build_client(settings, redirects.clone(), pin_failure.clone())?;
+
+    println!("Sending request to: {}", job.url);
+
+    let mut request = build_request(&client, job)?;
+
+    simulate_conditions(settings).await?;
+
+    let resuming = job.downloaded > 0 && job.supports_range;
+    if resuming {
+        println!("Resuming {} from byte {}", job.id, job.downloaded);
+        request = request.header(RANGE, format!("bytes={}-", job.downloaded));
+        if let Some(etag) = &job.etag {
+            request = request.header("If-Range", etag.as_str());
+        }
+    } else if let Some(revalidate) = &job.revalidate {
+        if let Some(etag) = &revalidate.etag {
+            request = request.header(IF_NONE_MATCH, etag.as_str());
+        }
+        if let Some(last_modified) = &revalidate.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified.as_str());
+        }
+    }
+
+    // S3 rejects HEAD on presigned URLs and may leave out the length; an open range is
+    // answered with the full size in Content-Range and shows that ranges work
+    let probe = !resuming && is_s3_url(&job.url);
+    if probe {
+        request = request.header(RANGE, "bytes=0-");
+    }
+
+    let res = tokio::select! {
+        res = request.send() => res.map_err(|e| {
+            println!("Request failed: {}", e);
+            match pin_failure.lock().unwrap().take() {
+                Some(host) => Error::PinMismatch(host),
+                None => Error::from(e),
+            }
+        })?,
+        _ = control.cancel.cancelled() => return Ok(DownloadOutcome::Cancelled),
+    };
+
+    println!("Response status: {}", res.status());
+
+    if res.status() == StatusCode::NOT_MODIFIED && job.revalidate.is_some() {
+        return Ok(DownloadOutcome::NotModified);
+    }
+
+    // ...except for an empty object, which has no byte 0
+    if probe && res.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        job.total = 0;
+        let empty = futures_util::stream::empty::<Result<Vec<u8>, Error>>();
+        return write_stream(app_handle, job, control, settings, empty, false).await;
+    }
+
+    if !res.status().is_success() {
+        let status = res.status();
+        let error_text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+        println!("Download failed with status {}: {}", status, error_text);
+        if is_expired_link(job, status, &error_text) {
+            // Without the query: the signature is of no use to anyone once expired, but noisy
+            let link = job.url.split('?').next().unwrap_or(&job.url).to_string();
+            return Err(Error::LinkExpired(link));
+        }
+        return Err(Error::HttpStatus(status.as_u16(), error_text));
+    }
+
+    job.redirect_chain = std::mem::take(&mut *redirects.lock().unwrap());
+
+    // A 200 answer to a ranged request means the server sent the whole file again
+    let append = resuming && res.status() == StatusCode::PARTIAL_CONTENT;
+    if !append {
+        job.downloaded = 0;
+        // Only the probe gets here with a 206, whose length is that of the open range
+        let ranged = res.status() == StatusCode::PARTIAL_CONTENT;
+        let content_range_total = res
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_content_range_total);
+        job.total = content_range_total
+            .filter(|_| ranged)
+            .or(res.content_length())
+            .unwrap_or(0);
+        job.supports_range = ranged || res
+            .headers()
+            .get(ACCEPT_RANGES)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.eq_ignore_ascii_case("bytes"))
+            .unwrap_or(false);
+        job.etag = res
+            .headers()
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        job.last_modified = res
+            .headers()
+            .get(LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+
+        // Signed URLs end in a token, the real name comes with the response
+        if let Some(name) = res
+            .headers()
+            .get(CONTENT_DISPOSITION)
+            .and_then(|v| parse_content_disposition(&String::from_utf8_lossy(v.as_bytes())))
+        {
+            if name != job.file_name {
+                if resuming {
+                    // The partial file under the old name is being replaced
+                    let _ = tokio::fs::remove_file(&job.file_path).await;
+                }
+                println!("Using file name from Content-Disposition: {}", name);
+                // A .part file keeps its suffix until the download completes
+                let on_disk = if crate::pending::is_partial(job) {
+                    crate::pending::part_name(&name)
+                } else {
+                    name.clone()
+                };
+                job.file_path = job.file_path.with_file_name(on_disk);
+                job.file_name = name;
+            }
+        }
+    } else if let Some(total) = res
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_content_range_total)
+    {
+        job.total = total;
+    }
+
+    // Refuse oversized files up front when the server announces the size
+    let max_size = settings.max_file_size_mb.saturating_mul(1024 * 1024);
+    if max_size > 0 && job.total > max_size {
+        return Err(reject_too_large(job, job.total, settings.max_file_size_mb).await);
+    }
+
+    // Fail now rather than halfway through with a bare IO error
+    let required = job.total.saturating_sub(if append { job.downloaded } else { 0 });
+    if required > 0 {
+        let directory = job.file_path.parent().unwrap_or(&job.file_path);
+        match crate::paths::available_space(directory) {
+            Ok(available) if available < required => {
+                return Err(Error::InsufficientDiskSpace(required, available));
+            }
+            Ok(_) => {}
+            Err(e) => println!("Could not determine free space in {}: {}", directory.display(), e),
+        }
+    }
+
+    // Large files go faster over several connections
+    let connections = if append { None } else { segment_count(job, settings) };
+    job.segmented = connections.is_some();
+
+    // Checkpoint so the download can continue after a restart
+    crate::pending::save(app_handle, job, true);
+
+    if let Some(connections) = connections {
+        drop(res);
+        return run_segmented(app_handle, job, control, &client, connections).await;
+    }
+
+    // Stream the response body to the file
+    let stream = res.bytes_stream().map(|chunk| chunk.map_err(Error::from));
+    write_stream(app_handle, job, control, settings, stream, append).await
+}
+
+/// Write `stream` to `job.file_path`, appending to the bytes already there when `append` is set.
+///
+/// Handles hashing, progress events, the speed limit, the size limit, pausing and cancelling,
+/// whatever protocol the bytes come from.
+pub async fn write_stream<S, B>(
+    app_handle: &AppHandle,
+    job: &mut DownloadJob,
+    control: &DownloadControl,
+    settings: &AppSettings,
+    mut stream: S,
+    append: bool,
+) -> Result<DownloadOutcome, Error>
+where
+    S: futures_util::Stream<Item = Result<B, Error>> + Unpin,
+    B: AsRef<[u8]>,
+{
+    let mut file = if append {
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&job.file_path)
+            .await?
+    } else {
+        tokio::fs::File::create(&job.file_path).await?
+    };
+
+    // Hash while streaming so the file never has to be read back
+    let mut hasher = match job.hasher.take() {
+        Some(hasher) if append => hasher,
+        None if append => hash_partial_file(job).await?,
+        _ => Sha256::new(),
+    };
+
+    let max_size = settings.max_file_size_mb.saturating_mul(1024 * 1024);
+    let mut speed = SpeedMeter::new();
+    let mut throttle = TokenBucket::from_kbps(speed_limit_kbps(settings));
+    let mut buffer = AdaptiveBuffer::new();
+    let mut progress_throttle = ProgressThrottle::new(&settings.progress_events);
+
+    loop {
+        let chunk = tokio::select! {
+            chunk = stream.next() => match chunk {
+                Some(chunk) => chunk?,
+                None => break,
+            },
+            _ = control.cancel.cancelled() => {
+                println!("Download {} cancelled at {} bytes", job.id, job.downloaded);
+                return Ok(DownloadOutcome::Cancelled);
+            }
+        };
+        let chunk = chunk.as_ref();
+        // The announced size may be missing or wrong, so keep counting
+        let received = job.downloaded + (buffer.len() + chunk.len()) as u64;
+        if max_size > 0 && received > max_size {
+            drop(file);
+            return Err(reject_too_large(job, received, settings.max_file_size_mb).await);
+        }
+
+        buffer.push(chunk);
+        speed.record(chunk.len() as u64);
+        buffer.adapt(speed.bytes_per_sec);
+
+        if buffer.is_due() {
+            job.downloaded += buffer.write_to(&mut file, &mut hasher).await?;
+            job.hasher = Some(hasher.clone());
+
+            // Emit progress event
+            let progress = speed.progress(job);
+            if progress_throttle.is_due(&progress) {
+                crate::events::emit_coalesced(app_handle, "download-progress", &job.id, &progress);
+            }
+        }
+
+        if let Some(wait) = throttle.as_mut().and_then(|bucket| bucket.take(chunk.len())) {
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = control.cancel.cancelled() => return Ok(DownloadOutcome::Cancelled),
+            }
+        }
+
+        if control.is_paused() {
+            // Only bytes on disk count towards a resume
+            job.downloaded += buffer.write_to(&mut file, &mut hasher).await?;
+            job.hasher = Some(hasher);
+            file.flush().await?;
+            println!("Download {} paused at {} bytes", job.id, job.downloaded);
+            return Ok(DownloadOutcome::Paused);
+        }
+    }
+
+    job.downloaded += buffer.write_to(&mut file, &mut hasher).await?;
+    job.hasher = Some(hasher);
+    file.flush().await?;
+    crate::events::emit_coalesced(app_handle, "download-progress", &job.id, &speed.progress(job));
+
+    Ok(DownloadOutcome::Completed)
+}
+
+/// GET request for `job.url` with the default and caller-supplied headers
+fn build_request(client: &reqwest::Client, job: &DownloadJob) -> Result<RequestBuilder, Error> {
+    let mut request = client
+        .get(&job.url)
+        .header("Accept", "*/*")
+        .header("Accept-Language", "en-US,en;q=0.9")
+        .header("Cache-Control", "no-cache")
+        .header("Pragma", "no-cache")
+        // Caller headers come last so they can override the defaults above
+        .headers(parse_headers(&job.headers)?);
+
+    if let Some(token) = &job.bearer_token {
+        request = request.bearer_auth(token);
+    }
+
+    Ok(request)
+}
+
+/// Effective bandwidth cap in kilobits per second, 0 for unlimited
+fn speed_limit_kbps(settings: &AppSettings) -> u64 {
+    let simulation = &settings.network_simulation;
+    let simulated = if simulation.enabled { simulation.bandwidth_kbps } else { 0 };
+
+    match (settings.max_download_speed_kbps, simulated) {
+        (0, limit) | (limit, 0) => limit,
+        (configured, simulated) => configured.min(simulated),
+    }
+}
+
+/// Delay or fail a request as the developer network simulation asks for
+async fn simulate_conditions(settings: &AppSettings) -> Result<(), Error> {
+    let simulation = &settings.network_simulation;
+    if !simulation.enabled {
+        return Ok(());
+    }
+
+    if simulation.latency_ms > 0 {
+        tokio::time::sleep(Duration::from_millis(simulation.latency_ms)).await;
+    }
+
+    if rand::thread_rng().gen_bool(simulation.failure_rate.clamp(0.0, 1.0)) {
+        println!("Simulated network failure");
+        return Err(Error::HttpStatus(503, "Simulated network failure".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Number of parallel connections for a fresh download, or `None` to use a single one
+fn segment_count(job: &DownloadJob, settings: &AppSettings) -> Option<u64> {
+    let config = &settings.segmented_download;
+    let min_size = config.min_size_mb.saturating_mul(1024 * 1024);
+
+    // A bandwidth cap applies to the whole download, which several connections would bypass.
+    // Simulated conditions are only applied to single connections.
+    let eligible = config.enabled
+        && config.connections > 1
+        && job.supports_range
+        && job.total > 0
+        && job.total >= min_size
+        && speed_limit_kbps(settings) == 0
+        && !settings.network_simulation.enabled;
+
+    eligible.then(|| (config.connections as u64).min(job.total))
+}
+
+/// Byte range fetched by one connection of a segmented download
+struct Segment {
+    start: u64,
+    /// Inclusive, as in the `Range` header
+    end: u64,
+    /// Bytes of this range already on disk
+    written: AtomicU64,
+}
+
+impl Segment {
+    fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    fn is_complete(&self) -> bool {
+        self.written.load(Ordering::SeqCst) == self.len()
+    }
+}
+
+/// Split `total` bytes into `count` ranges of about the same size
+fn split_ranges(total: u64, count: u64) -> Vec<Segment> {
+    let size = total.div_ceil(count);
+    (0..count)
+        .map(|i| i * size)
+        .take_while(|&start| start < total)
+        .map(|start| Segment {
+            start,
+            end: (start + size).min(total) - 1,
+            written: AtomicU64::new(0),
+        })
+        .collect()
+}
+
+/// Download `job.total` bytes as parallel range requests written in place into a preallocated file.
+///
+/// When the download stops early, the file is cut back to the bytes that are contiguous from the
+/// start, so a later resume continues over a single connection like any other download.
+async fn run_segmented(
+    app_handle: &AppHandle,
+    job: &mut DownloadJob,
+    control: &DownloadControl,
+    client: &reqwest::Client,
+    connections: u64,
+) -> Result<DownloadOutcome, Error> {
+    println!("Downloading {} over {} connections", job.id, connections);
+
+    let file = tokio::fs::File::create(&job.file_path).await?;
+    file.set_len(job.total).await?;
+    drop(file);
+
+    let segments = split_ranges(job.total, connections);
+    let requests = segments
+        .iter()
+        .map(|segment| {
+            let mut request = build_request(client, job)?
+                .header(RANGE, format!("bytes={}-{}", segment.start, segment.end));
+            // Ranges of a different version of the file must not be mixed
+            if let Some(etag) = &job.etag {
+                request = request.header("If-Range", etag.as_str());
+            }
+            Ok(request)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    // The first failure stops the other connections too
+    let path = job.file_path.clone();
+    let transfers = futures_util::future::try_join_all(
+        segments
+            .iter()
+            .zip(requests)
+            .map(|(segment, request)| download_segment(request, &path, segment, control)),
+    );
+    tokio::pin!(transfers);
+
+    let mut speed = SpeedMeter::new();
+    let mut ticker = tokio::time::interval(SpeedMeter::SAMPLE_INTERVAL);
+    let result = loop {
+        tokio::select! {
+            result = &mut transfers => break result,
+            _ = ticker.tick() => {
+                let written: u64 = segments.iter().map(|s| s.written.load(Ordering::SeqCst)).sum();
+                speed.record(written - job.downloaded);
+                job.downloaded = written;
+                crate::events::emit_coalesced(app_handle, "download-progress", &job.id, &speed.progress(job));
+            }
+        }
+    };
+
+    if control.cancel.is_cancelled() {
+        println!("Download {} cancelled", job.id);
+        return Ok(DownloadOutcome::Cancelled);
+    }
+
+    if let Err(err) = result {
+        keep_contiguous_prefix(job, &segments).await?;
+        return Err(err);
+    }
+
+    if control.is_paused() && !segments.iter().all(Segment::is_complete) {
+        keep_contiguous_prefix(job, &segments).await?;
+        println!("Download {} paused at {} bytes", job.id, job.downloaded);
+        return Ok(DownloadOutcome::Paused);
+    }
+
+    // Ranges arrive out of order, so the hash is computed once the file is whole
+    job.downloaded = job.total;
+    job.hasher = Some(hash_partial_file(job).await?);
+    crate::events::emit_coalesced(app_handle, "download-progress", &job.id, &speed.progress(job));
+
+    Ok(DownloadOutcome::Completed)
+}
+
+/// Fetch one range into its place in the file, stopping early when paused or cancelled
+async fn download_segment(
+    request: RequestBuilder,
+    path: &std::path::Path,
+    segment: &Segment,
+    control: &DownloadControl,
+) -> Result<(), Error> {
+    let res = tokio::select! {
+        res = request.send() => res?,
+        _ = control.cancel.cancelled() => return Ok(()),
+    };
+
+    // Anything but 206 would be the whole file, or a different version of it
+    if res.status() != StatusCode::PARTIAL_CONTENT {
+        let status = res.status();
+        return Err(Error::HttpStatus(
+            status.as_u16(),
+            format!("Expected a partial response for bytes {}-{}", segment.start, segment.end),
+        ));
+    }
+
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+    file.seek(SeekFrom::Start(segment.start)).await?;
+
+    let mut stream = res.bytes_stream();
+    while !segment.is_complete() && !control.is_paused() {
+        let chunk = tokio::select! {
+            chunk = stream.next() => match chunk {
+                Some(chunk) => chunk?,
+                None => break,
+            },
+            _ = control.cancel.cancelled() => return Ok(()),
+        };
+
+        // Never write past the end of the range, whatever the server sends
+        let remaining = segment.len() - segment.written.load(Ordering::SeqCst);
+        let chunk = &chunk[..chunk.len().min(remaining as usize)];
+        file.write_all(chunk).await?;
+        segment.written.fetch_add(chunk.len() as u64, Ordering::SeqCst);
+    }
+    file.flush().await?;
+
+    if !segment.is_complete() && !control.is_paused() {
+        return Err(Error::ConnectionClosed(format!(
+            "{} of bytes {}-{} received",
+            segment.written.load(Ordering::SeqCst),
+            segment.start,
+            segment.end
+        )));
+    }
+
+    Ok(())
+}
+
+/// Cut the file back to the bytes written without gaps from the start
+async fn keep_contiguous_prefix(job: &mut DownloadJob, segments: &[Segment]) -> std::io::Result<()> {
+    let mut prefix = 0;
+    for segment in segments {
+        prefix += segment.written.load(Ordering::SeqCst);
+        if !segment.is_complete() {
+            break;
+        }
+    }
+
+    let file = tokio::fs::OpenOptions::new().write(true).open(&job.file_path).await?;
+    file.set_len(prefix).await?;
+    job.downloaded = prefix;
+    job.segmented = false;
+    // Rebuilt from the file when the download resumes
+    job.hasher = None;
+
+    Ok(())
+}
+
+/// SHA-256 state over the first `job.downloaded` bytes of the partial file, for resumes
+/// whose hash state was lost
+async fn hash_partial_file(job: &DownloadJob) -> std::io::Result<Sha256> {
+    let mut reader = tokio::fs::File::open(&job.file_path).await?.take(job.downloaded);
+    let mut hasher = Sha256::new();
+    let mut chunk = vec![0u8; 1024 * 1024];
+
+    loop {
+        let read = reader.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&chunk[..read]);
+    }
+
+    Ok(hasher)
+}
+
+/// Remove whatever was written for `job` and build the `FileTooLarge` error
+pub async fn reject_too_large(job: &mut DownloadJob, size: u64, limit_mb: u64) -> Error {
+    println!("Download {} rejected: {} bytes exceeds {} MB", job.id, size, limit_mb);
+
+    if let Err(e) = tokio::fs::remove_file(&job.file_path).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            println!("Failed to remove partial file {}: {}", job.file_path.display(), e);
+        }
+    }
+    job.downloaded = 0;
+
+    Error::FileTooLarge(size, limit_mb)
+}
+
+/// Payload of the `batch-progress` event, sent whenever a file of a batch finishes
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchProgress {
+    pub batch_id: String,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Bytes of the files downloaded so far
+    pub downloaded_bytes: u64,
+}
+
+/// Outcome of one URL of a batch
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchItem<F> {
+    pub url: String,
+    pub file: Option<F>,
+    pub error: Option<String>,
+}
+
+/// Result of `download_files`, with the items in the order the URLs were given
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchReport<F> {
+    pub batch_id: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub items: Vec<BatchItem<F>>,
+}
+
+/// Payload of the `download-cancelled` event
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadCancelled {
+    pub id: String,
+    pub url: String,
+    pub downloaded: u64,
+}
+
+/// Webhook payload for a download that failed for good
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadFailed {
+    pub id: String,
+    pub url: String,
+    pub error: String,
+}
+
+/// Whether `url` is an S3 object URL, presigned or not
+fn is_s3_url(url: &str) -> bool {
+    let Ok(url) = url::Url::parse(url) else {
+        return false;
+    };
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    host.ends_with(".amazonaws.com")
+        || url
+            .query_pairs()
+            .any(|(key, _)| key.eq_ignore_ascii_case("X-Amz-Signature") || key == "AWSAccessKeyId")
+}
+
+/// Whether a failed response means the signed link has run out, rather than any other refusal
+fn is_expired_link(job: &DownloadJob, status: StatusCode, body: &str) -> bool {
+    if !matches!(status, StatusCode::BAD_REQUEST | StatusCode::FORBIDDEN) {
+        return false;
+    }
+
+    // S3 and S3-compatible stores explain the refusal in an XML error document
+    let s3_expired = body.contains("<Code>ExpiredToken</Code>")
+        || body.contains("<Code>TokenRefreshRequired</Code>")
+        || (body.contains("<Code>AccessDenied</Code>") && body.contains("Request has expired"));
+    let past_expiry = job
+        .expires_at
+        .is_some_and(|expires_at| SystemTime::now().duration_since(UNIX_EPOCH).is_ok_and(|now| now.as_secs() >= expires_at));
+
+    s3_expired || past_expiry
+}
+
+/// Extract the complete length from a `Content-Range: bytes start-end/total` header
+fn parse_content_range_total(value: &str) -> Option<u64> {
+    value.rsplit('/').next()?.trim().parse().ok()
+}
+
+/// File name from a `Content-Disposition` header (RFC 6266), preferring the
+/// RFC 5987 `filename*` form over the plain `filename` parameter
+fn parse_content_disposition(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+
+    for param in split_params(value).into_iter().skip(1) {
+        let Some((key, raw)) = param.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let raw = raw.trim();
+
+        match key.as_str() {
+            "filename*" => extended = decode_ext_value(raw),
+            "filename" => plain = Some(unquote(raw)),
+            _ => {}
+        }
+    }
+
+    extended.or(plain).and_then(|name| crate::paths::sanitize_file_name(&name))
+}
+
+/// Split header parameters on `;`, ignoring separators inside quoted strings
+fn split_params(value: &str) -> Vec<String> {
+    let mut params = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for c in value.chars() {
+        match c {
+            _ if escaped => {
+                current.push(c);
+                escaped = false;
+            }
+            '\\' if quoted => {
+                current.push(c);
+                escaped = true;
+            }
+            '"' => {
+                current.push(c);
+                quoted = !quoted;
+            }
+            ';' if !quoted => params.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    params.push(current);
+
+    params
+}
+
+/// Value of a token or quoted-string parameter
+fn unquote(raw: &str) -> String {
+    let Some(inner) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) else {
+        return raw.to_string();
+    };
+
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.extend(chars.next()),
+            _ => value.push(c),
+        }
+    }
+    value
+}
+
+/// Decode an RFC 5987 `charset'language'percent-encoded` value
+fn decode_ext_value(raw: &str) -> Option<String> {
+    let value = unquote(raw);
+    let mut parts = value.splitn(3, '\'');
+    let (charset, _language, encoded) = (parts.next()?, parts.next()?, parts.next()?);
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut input = encoded.bytes();
+    while let Some(b) = input.next() {
+        if b == b'%' {
+            let hex = [input.next()?, input.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    match charset.to_ascii_lowercase().as_str() {
+        "utf-8" => String::from_utf8(bytes).ok(),
+        // Latin-1 bytes map one to one onto the first 256 code points
+        "iso-8859-1" => Some(bytes.into_iter().map(char::from).collect()),
+        _ => None,
+    }
+}
@@ -0,0 +1,291 @@
+/*!
+ * Ephemeral sessions: encrypted-at-rest attachments that are wiped when the session ends
+ */
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use aes_gcm::aead::stream::{DecryptorBE32, EncryptorBE32};
+use aes_gcm::aead::{KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key};
+use rand::RngCore;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use zeroize::Zeroizing;
+
+use crate::{AppState, Error};
+
+/// Plaintext bytes per encrypted chunk
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Authentication tag appended to every chunk by AES-GCM
+const TAG_SIZE: usize = 16;
+/// STREAM nonce prefix: 12-byte GCM nonce minus the 5-byte counter/last-block flag
+const NONCE_PREFIX_SIZE: usize = 7;
+
+/// Key material and bookkeeping for the current ephemeral session
+pub struct EphemeralSession {
+    key: Zeroizing<[u8; 32]>,
+    /// Decrypted copies handed to the OS (open with, clipboard), keyed by encrypted path
+    working_copies: Mutex<HashMap<PathBuf, PathBuf>>,
+}
+
+/// Result of the ephemeral mode commands
+#[derive(Debug, Clone, Serialize)]
+pub struct EphemeralStatus {
+    pub enabled: bool,
+    /// Number of encrypted files currently held by the session
+    pub file_count: usize,
+}
+
+impl EphemeralSession {
+    /// Start a session with a fresh random key that is never written to disk
+    pub fn new() -> Self {
+        let mut key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(key.as_mut());
+
+        Self {
+            key,
+            working_copies: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(self.key.as_ref()))
+    }
+
+    /// Replace the plaintext file at `path` with its encrypted form
+    pub fn encrypt_file(&self, path: &Path) -> Result<(), Error> {
+        let encrypted_path = path.with_extension("enc-tmp");
+
+        let result = (|| {
+            let mut reader = BufReader::new(File::open(path)?);
+            let mut writer = BufWriter::new(File::create(&encrypted_path)?);
+
+            let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+            OsRng.fill_bytes(&mut nonce_prefix);
+            writer.write_all(&nonce_prefix)?;
+
+            let mut encryptor = EncryptorBE32::from_aead(self.cipher(), nonce_prefix.as_ref().into());
+            let mut buffer = vec![0u8; CHUNK_SIZE];
+
+            loop {
+                let read = read_full(&mut reader, &mut buffer)?;
+                if read == CHUNK_SIZE {
+                    let chunk = encryptor.encrypt_next(&buffer[..]).map_err(|_| crypto_error())?;
+                    writer.write_all(&chunk)?;
+                } else {
+                    // A short (possibly empty) chunk closes the stream
+                    let chunk = encryptor.encrypt_last(&buffer[..read]).map_err(|_| crypto_error())?;
+                    writer.write_all(&chunk)?;
+                    break;
+                }
+            }
+
+            writer.flush()?;
+            Ok::<(), Error>(())
+        })();
+
+        if let Err(e) = result {
+            let _ = fs::remove_file(&encrypted_path);
+            return Err(e);
+        }
+
+        fs::rename(&encrypted_path, path)?;
+        Ok(())
+    }
+
+    /// Open an encrypted file for reading its plaintext
+    pub fn open_reader(&self, path: &Path) -> Result<DecryptingReader<BufReader<File>>, Error> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+        reader.read_exact(&mut nonce_prefix)?;
+
+        Ok(DecryptingReader {
+            inner: reader,
+            decryptor: Some(DecryptorBE32::from_aead(self.cipher(), nonce_prefix.as_ref().into())),
+            plaintext: Vec::new(),
+            position: 0,
+        })
+    }
+
+    /// Decrypt the whole file into memory
+    pub fn decrypt_to_vec(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        self.open_reader(path)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Decrypt `path` into `destination`
+    pub fn decrypt_to_file(&self, path: &Path, destination: &Path) -> Result<u64, Error> {
+        let mut reader = self.open_reader(path)?;
+        let mut writer = BufWriter::new(File::create(destination)?);
+        let written = io::copy(&mut reader, &mut writer)?;
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Plaintext copy of an encrypted file for consumers that need a real path.
+    ///
+    /// Copies live next to the session's files and are wiped with them.
+    pub fn working_copy(&self, path: &Path) -> Result<PathBuf, Error> {
+        let mut copies = self.working_copies.lock().unwrap();
+        if let Some(copy) = copies.get(path).filter(|c| c.exists()) {
+            return Ok(copy.clone());
+        }
+
+        let file_name = path.file_name().ok_or_else(|| Error::FileNotFound(path.to_string_lossy().to_string()))?;
+        let directory = path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("ephemeral-open")
+            .join(format!("{:016x}", rand::random::<u64>()));
+        fs::create_dir_all(&directory)?;
+
+        let copy = directory.join(file_name);
+        self.decrypt_to_file(path, &copy)?;
+        copies.insert(path.to_path_buf(), copy.clone());
+
+        Ok(copy)
+    }
+
+    /// Delete the given encrypted files and every working copy
+    pub fn wipe(&self, files: &[PathBuf]) {
+        for file in files {
+            if let Err(e) = fs::remove_file(file) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    println!("Failed to wipe {}: {}", file.display(), e);
+                }
+            }
+        }
+
+        for (_, copy) in self.working_copies.lock().unwrap().drain() {
+            if let Err(e) = fs::remove_file(&copy) {
+                println!("Failed to wipe working copy {}: {}", copy.display(), e);
+            }
+            if let Some(parent) = copy.parent() {
+                let _ = fs::remove_dir(parent);
+            }
+        }
+    }
+}
+
+/// `Read` adapter returning the plaintext of a STREAM-encrypted file
+pub struct DecryptingReader<R: Read> {
+    inner: R,
+    decryptor: Option<DecryptorBE32<Aes256Gcm>>,
+    plaintext: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.plaintext.len() {
+            let Some(mut decryptor) = self.decryptor.take() else {
+                return Ok(0);
+            };
+
+            let mut chunk = vec![0u8; CHUNK_SIZE + TAG_SIZE];
+            let read = read_full(&mut self.inner, &mut chunk)?;
+            let invalid = |_| io::Error::new(io::ErrorKind::InvalidData, "Ephemeral file failed authentication");
+
+            self.plaintext = if read == chunk.len() {
+                let plaintext = decryptor.decrypt_next(&chunk[..]).map_err(invalid)?;
+                self.decryptor = Some(decryptor);
+                plaintext
+            } else {
+                decryptor.decrypt_last(&chunk[..read]).map_err(invalid)?
+            };
+            self.position = 0;
+        }
+
+        let available = &self.plaintext[self.position..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+/// Fill `buffer` as far as possible, returning fewer bytes only at end of input
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn crypto_error() -> Error {
+    Error::IoError(io::Error::other("Failed to encrypt ephemeral file"))
+}
+
+/// Session that should encrypt newly downloaded files, if ephemeral mode is on
+pub fn active_session(app_handle: &AppHandle) -> Option<Arc<EphemeralSession>> {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().unwrap();
+    app_state.ephemeral.clone()
+}
+
+/// End the ephemeral session: delete its files, forget them and drop the key
+pub fn end_session(app_handle: &AppHandle) {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().unwrap();
+
+    let Some(session) = app_state.ephemeral.take() else {
+        return;
+    };
+
+    let ids: Vec<String> = app_state.downloaded_files
+        .values()
+        .filter(|f| f.encrypted)
+        .map(|f| f.id.clone())
+        .collect();
+
+    let mut files = Vec::with_capacity(ids.len());
+    for id in &ids {
+        if let Some(file) = app_state.downloaded_files.remove(id) {
+            files.push(PathBuf::from(file.file_path));
+        }
+    }
+    if app_state.current_file.as_ref().is_some_and(|f| f.encrypted) {
+        app_state.current_file = None;
+    }
+
+    session.wipe(&files);
+    println!("Ephemeral session ended, wiped {} file(s)", files.len());
+}
+
+// Command to turn the ephemeral session on or off. Turning it off wipes the session's files.
+#[tauri::command]
+pub fn set_ephemeral_mode(app_handle: AppHandle, enabled: bool) -> EphemeralStatus {
+    if enabled {
+        let state = app_handle.state::<Arc<Mutex<AppState>>>();
+        let mut app_state = state.lock().unwrap();
+        if app_state.ephemeral.is_none() {
+            app_state.ephemeral = Some(Arc::new(EphemeralSession::new()));
+        }
+    } else {
+        end_session(&app_handle);
+    }
+
+    get_ephemeral_mode(app_handle)
+}
+
+// Command to get the state of the ephemeral session
+#[tauri::command]
+pub fn get_ephemeral_mode(app_handle: AppHandle) -> EphemeralStatus {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().unwrap();
+
+    EphemeralStatus {
+        enabled: app_state.ephemeral.is_some(),
+        file_count: app_state.downloaded_files.values().filter(|f| f.encrypted).count(),
+    }
+}
@@ -0,0 +1,63 @@
+/*!
+ * Coalescing bridge for high-frequency events to the webview
+ */
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// How often held events are delivered while the window is visible
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Events waiting for delivery, managed as app state
+#[derive(Default)]
+pub struct EventBridge {
+    /// Latest payload per event name and key; a newer value replaces the one waiting,
+    /// so memory stays bounded by the number of jobs however long the webview lags
+    pending: Mutex<BTreeMap<(&'static str, String), serde_json::Value>>,
+}
+
+/// Queue `payload` for `event`, replacing any value for the same `key` not yet delivered
+pub fn emit_coalesced<T: Serialize>(app_handle: &AppHandle, event: &'static str, key: &str, payload: &T) {
+    match serde_json::to_value(payload) {
+        Ok(value) => {
+            let bridge = app_handle.state::<EventBridge>();
+            bridge.pending.lock().unwrap().insert((event, key.to_string()), value);
+        }
+        Err(e) => println!("Failed to serialize {} event: {}", event, e),
+    }
+}
+
+/// Deliver everything held, e.g. when the window regains focus or a job finishes
+pub fn flush(app_handle: &AppHandle) {
+    let bridge = app_handle.state::<EventBridge>();
+    let pending = std::mem::take(&mut *bridge.pending.lock().unwrap());
+    for ((event, _), payload) in pending {
+        let _ = app_handle.emit(event, payload);
+    }
+}
+
+/// Whether the main window is currently able to show updates
+fn window_visible(app_handle: &AppHandle) -> bool {
+    app_handle
+        .get_webview_window("main")
+        .map(|window| {
+            window.is_visible().unwrap_or(true) && !window.is_minimized().unwrap_or(false)
+        })
+        .unwrap_or(true)
+}
+
+/// Start delivering held events at a steady pace; nothing is sent while the window is hidden
+pub fn start(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+
+            if window_visible(&app_handle) {
+                flush(&app_handle);
+            }
+        }
+    });
+}
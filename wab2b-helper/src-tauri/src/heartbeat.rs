@@ -0,0 +1,160 @@
+/*!
+ * Periodic presence reporting to the dashboard
+ */
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::settings::{self, HeartbeatSettings};
+use crate::AppState;
+
+/// Lower bound for the reporting interval, whatever the settings say
+const MIN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often disabled heartbeats re-read the settings
+const IDLE_POLL: Duration = Duration::from_secs(30);
+
+/// Last heartbeat result, shared with `get_heartbeat_status`
+static LAST_STATUS: Mutex<Option<HeartbeatStatus>> = Mutex::new(None);
+
+/// Body posted to the dashboard endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct HeartbeatPayload {
+    /// Random ID for this run of the helper
+    pub session_id: String,
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    pub uptime_secs: u64,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub health: HealthReport,
+}
+
+/// Health information included in each heartbeat
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub active_downloads: usize,
+    pub paused_downloads: usize,
+    pub cached_files: usize,
+    pub ephemeral_session: bool,
+    /// Whether the download directory can still be written to
+    pub storage_writable: bool,
+}
+
+/// Outcome of the last heartbeat, also sent as the `heartbeat-status` event
+#[derive(Debug, Clone, Serialize)]
+pub struct HeartbeatStatus {
+    pub connected: bool,
+    /// Seconds since the Unix epoch
+    pub last_attempt: u64,
+    pub http_status: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// Start the background heartbeat task
+pub fn start(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let started = Instant::now();
+        let session_id = format!("{:032x}", rand::random::<u128>());
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+
+        loop {
+            let config = settings::load_settings(&app_handle)
+                .map(|s| s.heartbeat)
+                .unwrap_or_default();
+
+            if !config.enabled || config.endpoint.is_empty() {
+                tokio::time::sleep(IDLE_POLL).await;
+                continue;
+            }
+
+            let token = crate::secrets::get(&app_handle, crate::secrets::HEARTBEAT_TOKEN)
+                .unwrap_or_else(|e| {
+                    println!("Failed to read the heartbeat token: {}", e);
+                    None
+                });
+            let payload = build_payload(&app_handle, &session_id, started);
+            let status = send(&client, &config, token.as_deref(), &payload).await;
+
+            let _ = app_handle.emit("heartbeat-status", &status);
+            *LAST_STATUS.lock().unwrap() = Some(status);
+
+            tokio::time::sleep(Duration::from_secs(config.interval_secs).max(MIN_INTERVAL)).await;
+        }
+    });
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn build_payload(app_handle: &AppHandle, session_id: &str, started: Instant) -> HeartbeatPayload {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let health = {
+        let app_state = state.lock().unwrap();
+        HealthReport {
+            active_downloads: app_state.active_downloads.len(),
+            paused_downloads: app_state.paused_downloads.len(),
+            cached_files: app_state.downloaded_files.len(),
+            ephemeral_session: app_state.ephemeral.is_some(),
+            storage_writable: std::fs::metadata(app_state.temp_dir.path())
+                .map(|m| !m.permissions().readonly())
+                .unwrap_or(false),
+        }
+    };
+
+    HeartbeatPayload {
+        session_id: session_id.to_string(),
+        version: app_handle.package_info().version.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        uptime_secs: started.elapsed().as_secs(),
+        timestamp: now_secs(),
+        health,
+    }
+}
+
+async fn send(
+    client: &reqwest::Client,
+    config: &HeartbeatSettings,
+    token: Option<&str>,
+    payload: &HeartbeatPayload,
+) -> HeartbeatStatus {
+    let mut request = client.post(&config.endpoint).json(payload);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+
+    match request.send().await {
+        Ok(response) => {
+            let status = response.status();
+            HeartbeatStatus {
+                connected: status.is_success(),
+                last_attempt: payload.timestamp,
+                http_status: Some(status.as_u16()),
+                error: (!status.is_success()).then(|| format!("Dashboard answered with status {}", status)),
+            }
+        }
+        Err(e) => HeartbeatStatus {
+            connected: false,
+            last_attempt: payload.timestamp,
+            http_status: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+// Command to get the result of the last heartbeat, if any was sent
+#[tauri::command]
+pub fn get_heartbeat_status() -> Option<HeartbeatStatus> {
+    LAST_STATUS.lock().unwrap().clone()
+}
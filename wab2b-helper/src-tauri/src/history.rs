@@ -0,0 +1,216 @@
+/*!
+ * Persistent download history in an embedded SQLite database
+ */
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::download::DownloadJob;
+use crate::ephemeral;
+
+/// Name of the database in the app data directory
+const DATABASE_FILE: &str = "history.sqlite3";
+
+/// Serializes access to the database
+static DB_LOCK: Mutex<()> = Mutex::new(());
+
+/// How a download ended
+#[derive(Debug, Clone, Copy)]
+pub enum Outcome {
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl Outcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Completed => "completed",
+            Outcome::Failed => "failed",
+            Outcome::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// One download as stored in the history
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub url: String,
+    /// Mirror the file came from, when it wasn't `url`
+    pub source_url: Option<String>,
+    pub file_name: String,
+    pub size: u64,
+    pub sha256: Option<String>,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    /// Seconds since the Unix epoch
+    pub finished_at: u64,
+    pub outcome: String,
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    /// Entry for `job` as it stands, without hash or error
+    pub fn from_job(job: &DownloadJob, started_at: u64) -> Self {
+        let url = job.sources.first().cloned().unwrap_or_else(|| job.url.clone());
+        Self {
+            id: job.id.clone(),
+            source_url: (job.url != url).then(|| job.url.clone()),
+            url,
+            file_name: job.file_name.clone(),
+            size: job.downloaded,
+            sha256: None,
+            started_at,
+            finished_at: now_secs(),
+            outcome: String::new(),
+            error: None,
+        }
+    }
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn database_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    Ok(app_dir.join(DATABASE_FILE))
+}
+
+fn open(app: &AppHandle) -> Result<Connection, String> {
+    let connection = Connection::open(database_path(app)?)
+        .map_err(|e| format!("Failed to open download history: {}", e))?;
+
+    connection
+        .execute_batch(
+            "CREATE TABLE IF NOT EXISTS downloads (
+                id TEXT PRIMARY KEY,
+                url TEXT NOT NULL,
+                source_url TEXT,
+                file_name TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                sha256 TEXT,
+                started_at INTEGER NOT NULL,
+                finished_at INTEGER NOT NULL,
+                outcome TEXT NOT NULL,
+                error TEXT
+            );
+            CREATE INDEX IF NOT EXISTS downloads_finished_at ON downloads (finished_at);",
+        )
+        .map_err(|e| format!("Failed to prepare download history: {}", e))?;
+
+    Ok(connection)
+}
+
+/// Record how a download ended. Nothing is written during an ephemeral session.
+pub fn record(app_handle: &AppHandle, mut entry: HistoryEntry, outcome: Outcome) {
+    if ephemeral::active_session(app_handle).is_some() {
+        return;
+    }
+    entry.outcome = outcome.as_str().to_string();
+
+    let _guard = DB_LOCK.lock().unwrap();
+    let result = open(app_handle).and_then(|connection| {
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO downloads
+                    (id, url, source_url, file_name, size, sha256, started_at, finished_at, outcome, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    entry.id,
+                    entry.url,
+                    entry.source_url,
+                    entry.file_name,
+                    entry.size as i64,
+                    entry.sha256,
+                    entry.started_at as i64,
+                    entry.finished_at as i64,
+                    entry.outcome,
+                    entry.error,
+                ],
+            )
+            .map_err(|e| format!("Failed to write download history: {}", e))
+    });
+
+    if let Err(e) = result {
+        println!("Failed to record download {}: {}", entry.id, e);
+    }
+}
+
+// Command to list past downloads, newest first
+#[tauri::command]
+pub fn get_download_history(
+    app_handle: AppHandle,
+    webview: tauri::Webview,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    token: Option<String>,
+) -> Result<Vec<HistoryEntry>, String> {
+    crate::permissions::authorize_command(&webview, token.as_deref(), crate::permissions::Scope::ReadHistory)?;
+    let _guard = DB_LOCK.lock().unwrap();
+    let connection = open(&app_handle)?;
+
+    let mut statement = connection
+        .prepare(
+            "SELECT id, url, source_url, file_name, size, sha256, started_at, finished_at, outcome, error
+             FROM downloads
+             ORDER BY finished_at DESC, rowid DESC
+             LIMIT ?1 OFFSET ?2",
+        )
+        .map_err(|e| format!("Failed to read download history: {}", e))?;
+
+    // A negative limit means no limit in SQLite
+    let limit = limit.map_or(-1, i64::from);
+    let rows = statement
+        .query_map(params![limit, offset.unwrap_or(0)], |row| {
+            Ok(HistoryEntry {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                source_url: row.get(2)?,
+                file_name: row.get(3)?,
+                size: row.get::<_, i64>(4)? as u64,
+                sha256: row.get(5)?,
+                started_at: row.get::<_, i64>(6)? as u64,
+                finished_at: row.get::<_, i64>(7)? as u64,
+                outcome: row.get(8)?,
+                error: row.get(9)?,
+            })
+        })
+        .map_err(|e| format!("Failed to read download history: {}", e))?;
+
+    rows.collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read download history: {}", e))
+}
+
+// Command to delete the download history, returning the number of removed entries
+#[tauri::command]
+pub fn clear_history(app_handle: AppHandle, webview: tauri::Webview, token: Option<String>) -> Result<usize, String> {
+    crate::permissions::authorize_command(&webview, token.as_deref(), crate::permissions::Scope::Admin)?;
+    let _guard = DB_LOCK.lock().unwrap();
+    let connection = open(&app_handle)?;
+
+    let removed = connection
+        .execute("DELETE FROM downloads", [])
+        .map_err(|e| format!("Failed to clear download history: {}", e))?;
+
+    // Give the space of the deleted rows back to the file system
+    let _ = connection.execute_batch("VACUUM");
+
+    Ok(removed)
+}
@@ -7,28 +7,53 @@ use serde::{Deserialize, Serialize};
 use tempfile::TempDir;
 use url::Url;
 use anyhow::Result;
-use tokio::io::AsyncWriteExt;
-use futures_util::StreamExt;
 use std::fs;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tauri_plugin_single_instance as single_instance;
-use mime_guess::from_path;
 use std::env;
 use tauri_plugin_dialog;
 use tauri_plugin_fs;
+#[cfg(windows)]
 use tauri_plugin_shell::ShellExt;
 use tauri_plugin_dialog::DialogExt;
 
 // Import modules
 pub mod updater;
 pub mod settings;
+mod download;
+mod archive;
+mod ephemeral;
+mod checksums;
+mod heartbeat;
+mod clipboard;
+mod paths;
+mod provisioning;
+mod webhook;
+mod shared_folder;
+mod history;
+mod permissions;
+mod events;
+mod pending;
+mod queue;
+mod schedule;
+mod content_type;
+mod network;
+mod pinning;
+mod secrets;
+mod data_url;
+mod remote;
+mod share_links;
+mod shortener;
 
 // Global state to store downloaded files
 struct AppState {
     temp_dir: TempDir,
     current_file: Option<FileInfo>,
     downloaded_files: HashMap<String, FileInfo>,
+    active_downloads: HashMap<String, download::DownloadControl>,
+    paused_downloads: HashMap<String, download::DownloadJob>,
+    ephemeral: Option<Arc<ephemeral::EphemeralSession>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,15 +63,51 @@ pub struct FileInfo {
     file_path: String,
     file_name: String,
     mime_type: String,
+    /// MIME type recognized from the file content
+    #[serde(default)]
+    detected_mime_type: Option<String>,
+    /// MIME type implied by the file extension
+    #[serde(default)]
+    extension_mime_type: Option<String>,
     size: u64,
+    #[serde(default)]
+    encrypted: bool,
+    /// Lowercase hex SHA-256 of the plaintext, computed while downloading
+    #[serde(default)]
+    sha256: Option<String>,
+    /// URL the content was actually fetched from, when a mirror stood in for `original_url`
+    #[serde(default)]
+    source_url: Option<String>,
+    /// URL the content was finally served from, when the download was redirected
+    #[serde(default)]
+    resolved_url: Option<String>,
+    /// Every URL passed through on the way, starting with the requested one
+    #[serde(default)]
+    redirect_chain: Vec<String>,
+    /// Shortened link the download was requested with, when `original_url` is its expansion
+    #[serde(default)]
+    short_url: Option<String>,
+    /// Validators of the response, for conditional requests when the URL is fetched again
+    #[serde(default)]
+    etag: Option<String>,
+    #[serde(default)]
+    last_modified: Option<String>,
+    /// True when an earlier download was returned instead of fetching the file again
+    #[serde(default)]
+    cached: bool,
+    /// Conversation the attachment came from, set by the dashboard
+    #[serde(default)]
+    conversation: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
+/// Scheme of download deep links, followed by the attachment URL
+const DEEP_LINK_SCHEME: &str = "wab2b-helper:";
+
 // Error types
 #[derive(Debug, thiserror::Error)]
 enum Error {
-    #[error("Failed to download file: {0}")]
-    DownloadError(String),
-    
     #[error("Invalid URL: {0}")]
     InvalidUrl(String),
     
@@ -54,10 +115,86 @@ enum Error {
     IoError(#[from] std::io::Error),
     
     #[error("Request error: {0}")]
-    RequestError(#[from] reqwest::Error),
+    RequestError(reqwest::Error),
+    
+    #[error("Timed out: {0}")]
+    Timeout(String),
+    
+    #[error("Certificate pin mismatch for {0}")]
+    PinMismatch(String),
+    
+    #[error("Host not allowed: {0}")]
+    HostNotAllowed(String),
+    
+    #[error("Private network address not allowed: {0}")]
+    PrivateAddress(String),
+    
+    #[error("Transfer error: {0}")]
+    Transfer(String),
+    
+    // A server hanging up mid-transfer is worth another try, unlike a local I/O error
+    #[error("Connection closed early: {0}")]
+    ConnectionClosed(String),
+    
+    #[error("Link expired: {0}")]
+    LinkExpired(String),
     
     #[error("File not found: {0}")]
     FileNotFound(String),
+    
+    #[error("Failed to download file: HTTP status {0} - {1}")]
+    HttpStatus(u16, String),
+    
+    #[error("Invalid request header: {0}")]
+    InvalidHeader(String),
+    
+    #[error("Download paused: {0}")]
+    DownloadPaused(String),
+    
+    #[error("Download cancelled: {0}")]
+    DownloadCancelled(String),
+    
+    #[error("Invalid archive: {0}")]
+    InvalidArchive(String),
+    
+    #[error("Unsupported archive: {0}")]
+    UnsupportedArchive(String),
+    
+    #[error("Clipboard error: {0}")]
+    Clipboard(String),
+    
+    // Only Windows locks the clipboard while an application reads or writes it
+    #[cfg_attr(not(windows), allow(dead_code))]
+    #[error("Clipboard is in use by another application")]
+    ClipboardBusy,
+    
+    #[error("Path too long: {0}")]
+    PathTooLong(String),
+    
+    #[error("File too large: {0} bytes exceeds the limit of {1} MB")]
+    FileTooLarge(u64, u64),
+    
+    #[error("Insufficient disk space: {0} bytes required, {1} bytes available")]
+    InsufficientDiskSpace(u64, u64),
+    
+    #[error("Failed to move to the recycle bin: {0}")]
+    Trash(String),
+    
+    #[error("Not authorized: {0}")]
+    Unauthorized(String),
+}
+
+// Timeouts get their own variant so the frontend can tell a dead host from other failures
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Error::Timeout(e.to_string())
+        } else if let Some(blocked) = network::blocked_cause(&e) {
+            Error::PrivateAddress(blocked.to_string())
+        } else {
+            Error::RequestError(e)
+        }
+    }
 }
 
 impl serde::Serialize for Error {
@@ -79,122 +216,578 @@ fn init_app_state() -> Result<AppState> {
         temp_dir,
         current_file: None,
         downloaded_files: HashMap::new(),
+        active_downloads: HashMap::new(),
+        paused_downloads: HashMap::new(),
+        ephemeral: None,
     })
 }
 
-// Command to download a file from a URL
+// Generate a unique ID for a file registered in the app state
+fn generate_file_id() -> String {
+    static LAST_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    
+    // Bump the value when two files are registered within the same millisecond
+    let previous = LAST_ID
+        .fetch_update(std::sync::atomic::Ordering::SeqCst, std::sync::atomic::Ordering::SeqCst, |last| {
+            Some(timestamp.max(last + 1))
+        })
+        .unwrap();
+    
+    format!("file_{}", timestamp.max(previous + 1))
+}
+
+// Command to download a file from a URL; callers outside the helper's window need a download token
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn download_file(
     app_handle: AppHandle,
+    webview: tauri::Webview,
     url: String,
+    headers: Option<HashMap<String, String>>,
+    bearer_token: Option<String>,
+    mirrors: Option<Vec<String>>,
+    expires_at: Option<u64>,
+    force: Option<bool>,
+    priority: Option<queue::Priority>,
+    token: Option<String>,
+) -> Result<FileInfo, Error> {
+    permissions::authorize_command(&webview, token.as_deref(), permissions::Scope::Download)
+        .map_err(Error::Unauthorized)?;
+    start_download(app_handle, url, headers, bearer_token, mirrors, expires_at, force, priority).await
+}
+
+/// Download a file from a URL, for the download commands and scheduled downloads
+#[allow(clippy::too_many_arguments)]
+async fn start_download(
+    app_handle: AppHandle,
+    url: String,
+    headers: Option<HashMap<String, String>>,
+    bearer_token: Option<String>,
+    mirrors: Option<Vec<String>>,
+    expires_at: Option<u64>,
+    force: Option<bool>,
+    priority: Option<queue::Priority>,
 ) -> Result<FileInfo, Error> {
     println!("Starting download for URL: {}", url);
     
     // Parse the URL
     let parsed_url = Url::parse(&url).map_err(|_| Error::InvalidUrl(url.clone()))?;
     
-    // Extract the file name from the URL
+    // Small images embedded in the payload need no request at all
+    if parsed_url.scheme() == "data" {
+        return data_url::register(&app_handle, &url).await;
+    }
+    
+    // Mirrors are tried in order after the primary URL
+    let mirrors = mirrors.unwrap_or_default();
+    let settings = settings::load_settings(&app_handle).unwrap_or_default();
+    
+    // The allowlist applies to where a shortened link leads, not to the shortener
+    let (short_url, url, parsed_url) = if shortener::is_shortened(&parsed_url) {
+        let expanded = shortener::expand(&settings, &parsed_url).await?;
+        (Some(url), expanded.to_string(), expanded)
+    } else {
+        (None, url, parsed_url)
+    };
+    download::check_host_allowed(&settings, &parsed_url)?;
+    for mirror in &mirrors {
+        let parsed_mirror = Url::parse(mirror).map_err(|_| Error::InvalidUrl(mirror.clone()))?;
+        download::check_host_allowed(&settings, &parsed_mirror)?;
+    }
+    
+    // Drive and OneDrive share links lead to an HTML page, not the file
+    let (url, parsed_url) = match share_links::direct_url(&parsed_url) {
+        Some(direct) => {
+            println!("Resolved share link to {}", direct);
+            let parsed_direct = Url::parse(&direct).map_err(|_| Error::InvalidUrl(direct.clone()))?;
+            download::check_host_allowed(&settings, &parsed_direct)?;
+            (direct, parsed_direct)
+        }
+        None => (url, parsed_url),
+    };
+    
+    // Re-clicked attachments are served from the registry unless a fresh copy is forced.
+    // With validators from the earlier response the server is asked whether it changed.
+    let force = force.unwrap_or(false);
+    let mut revalidate = None;
+    if !force {
+        if let Some(cached) = find_cached_file(&app_handle, |f| f.original_url == url) {
+            if cached.etag.is_none() && cached.last_modified.is_none() {
+                println!("Reusing {} for URL: {}", cached.id, url);
+                return Ok(cached);
+            }
+            revalidate = Some(download::Revalidation {
+                file_id: cached.id,
+                etag: cached.etag,
+                last_modified: cached.last_modified,
+            });
+        }
+    }
+    let replaces = revalidate.as_ref().map(|r| r.file_id.clone());
+    
+    // Large Drive files need the token from the virus scan warning first
+    let download_url = if share_links::needs_drive_confirmation(&url) {
+        let confirmed = share_links::confirm_drive_download(&settings, &url).await?;
+        let parsed_confirmed = Url::parse(&confirmed).map_err(|_| Error::InvalidUrl(confirmed.clone()))?;
+        download::check_host_allowed(&settings, &parsed_confirmed)?;
+        confirmed
+    } else {
+        url.clone()
+    };
+    let sources = std::iter::once(download_url.clone()).chain(mirrors).collect();
+    
+    // Signed links from deep links carry their expiry in the query
+    let expires_at = expires_at.or_else(|| download::expires_at_from_url(&parsed_url));
+    
+    // Reject malformed headers before anything touches the disk
+    let headers = headers.unwrap_or_default();
+    download::parse_headers(&headers)?;
+    
+    // Extract the file name from the URL; decoded escapes may hide `..` or separators
     let file_name = parsed_url
         .path_segments()
-        .and_then(|segments| segments.last())
-        .unwrap_or("downloaded_file")
-        .to_string();
+        .and_then(|mut segments| segments.next_back())
+        .map(|segment| String::from_utf8_lossy(&data_url::percent_decode(segment)).to_string())
+        .and_then(|segment| paths::sanitize_file_name(&segment))
+        .unwrap_or_else(|| "downloaded_file".to_string());
     
     // Generate a unique ID for this file
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis();
-    let id = format!("file_{}", timestamp);
+    let id = generate_file_id();
     
     // Get the app state
     let state = app_handle.state::<Arc<Mutex<AppState>>>();
     
-    // Create the temp file path - avoid MutexGuard across await points
-    let file_path = {
+    // Create the temp file path - avoid MutexGuard across await points.
+    // Each download gets its own folder so attachments with the same name don't overwrite each other.
+    // Outside an ephemeral session the file is written as a .part in the app data directory
+    // until complete, so an interrupted download can continue after a restart.
+    let (download_dir, part_name) = if ephemeral::active_session(&app_handle).is_some() {
         let app_state = state.lock().unwrap();
-        app_state.temp_dir.path().join(&file_name)
+        (app_state.temp_dir.path().join(&id), file_name.clone())
+    } else {
+        let partial_dir = pending::partial_dir(&app_handle, &id)
+            .map_err(|e| Error::IoError(std::io::Error::other(e)))?;
+        (partial_dir, pending::part_name(&file_name))
     };
+    tokio::fs::create_dir_all(&download_dir).await?;
+    let file_path = download_dir.join(part_name);
     
-    // Create the file
-    let mut file = tokio::fs::File::create(&file_path).await?;
-    
-    // Download the file with proper headers and better error handling
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-        .timeout(std::time::Duration::from_secs(60))
-        .danger_accept_invalid_certs(true) // Accept invalid certs for better compatibility
-        .redirect(reqwest::redirect::Policy::limited(10))
-        .build()
-        .map_err(|e| Error::RequestError(e))?;
-    
-    println!("Sending request to: {}", url);
-    
-    let res = client
-        .get(&url)
-        .header("Accept", "*/*")
-        .header("Accept-Language", "en-US,en;q=0.9")
-        .header("Cache-Control", "no-cache")
-        .header("Pragma", "no-cache")
-        .send()
-        .await
-        .map_err(|e| {
-            println!("Request failed: {}", e);
-            Error::RequestError(e)
-        })?;
+    let job = download::DownloadJob {
+        id,
+        url,
+        sources,
+        file_name,
+        file_path,
+        downloaded: 0,
+        total: 0,
+        supports_range: false,
+        etag: None,
+        headers,
+        bearer_token: bearer_token.filter(|t| !t.is_empty()),
+        hasher: None,
+        expires_at,
+        segmented: false,
+        redirect_chain: Vec::new(),
+        short_url,
+        priority: priority.unwrap_or_default(),
+        last_modified: None,
+        revalidate,
+    };
     
-    println!("Response status: {}", res.status());
+    if let Some(expires_at) = expires_at {
+        download::watch_expiry(&app_handle, &job.id, &job.url, expires_at);
+    }
     
-    if !res.status().is_success() {
-        let status = res.status();
-        let error_text = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        println!("Download failed with status {}: {}", status, error_text);
-        return Err(Error::DownloadError(format!(
-            "Failed to download file: HTTP status {} - {}",
-            status, error_text
-        )));
+    let file_info = run_download_job(&app_handle, job).await?;
+    if file_info.cached {
+        return Ok(file_info);
     }
     
-    // Get the content length if available
-    let content_length = res.content_length().unwrap_or(0);
+    // The server sent a newer version; it takes the place of the outdated copy
+    if let Some(outdated) = replaces.and_then(|id| file_by_id(&app_handle, &id)) {
+        forget_file(&app_handle, &outdated, &settings).await;
+    }
     
-    // Stream the response body to the file
-    let mut stream = res.bytes_stream();
-    let mut downloaded_size = 0;
+    if force || file_info.sha256.is_none() {
+        return Ok(file_info);
+    }
     
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk?;
-        file.write_all(&chunk).await?;
-        downloaded_size += chunk.len() as u64;
-        
-        // Emit progress event
-        if content_length > 0 {
-            let progress = (downloaded_size as f64 / content_length as f64) * 100.0;
-            app_handle.emit("download-progress", progress).unwrap();
+    // Different links can serve the same content; keep only the copy already registered
+    let duplicate_of = find_cached_file(&app_handle, |f| f.id != file_info.id && f.sha256 == file_info.sha256);
+    match duplicate_of {
+        Some(cached) => {
+            println!("Download {} has the same content as {}, dropping it", file_info.id, cached.id);
+            forget_file(&app_handle, &file_info, &settings).await;
+            Ok(cached)
         }
+        None => Ok(file_info),
     }
+}
+
+// Look up a registered file by ID
+fn file_by_id(app_handle: &AppHandle, id: &str) -> Option<FileInfo> {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().unwrap();
+    app_state.downloaded_files.get(id).cloned()
+}
+
+// Look up a registered file whose content is still on disk and make it the current file
+fn find_cached_file(app_handle: &AppHandle, matches: impl Fn(&FileInfo) -> bool) -> Option<FileInfo> {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().unwrap();
     
-    // Get the MIME type
-    let mime_type = from_path(&file_path)
-        .first_or_octet_stream()
-        .to_string();
+    let existing = app_state.downloaded_files
+        .values()
+        .find(|f| matches(f) && std::path::Path::new(&f.file_path).is_file())?
+        .clone();
+    app_state.current_file = Some(existing.clone());
     
-    // Create the file info
-    let file_info = FileInfo {
-        id: id.clone(),
-        original_url: url,
-        file_path: file_path.to_string_lossy().to_string(),
-        file_name,
-        mime_type,
-        size: downloaded_size,
+    Some(FileInfo { cached: true, ..existing })
+}
+
+// Remove a file from the registry and the disk
+async fn forget_file(app_handle: &AppHandle, file_info: &FileInfo, settings: &settings::AppSettings) {
+    {
+        let state = app_handle.state::<Arc<Mutex<AppState>>>();
+        let mut app_state = state.lock().unwrap();
+        app_state.downloaded_files.remove(&file_info.id);
+        if app_state.current_file.as_ref().is_some_and(|f| f.id == file_info.id) {
+            app_state.current_file = None;
+        }
+    }
+    
+    let path = PathBuf::from(&file_info.file_path);
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        println!("Failed to remove {}: {}", path.display(), e);
+    }
+    if let Some(parent) = path.parent().filter(|p| Some(p.to_path_buf()) != stable_download_dir(settings)) {
+        let _ = tokio::fs::remove_dir(parent).await;
+    }
+}
+
+// Command to download several files at once, reporting success or failure per URL
+#[tauri::command]
+async fn download_files(
+    app_handle: AppHandle,
+    webview: tauri::Webview,
+    urls: Vec<String>,
+    headers: Option<HashMap<String, String>>,
+    bearer_token: Option<String>,
+    token: Option<String>,
+) -> Result<download::BatchReport<FileInfo>, Error> {
+    permissions::authorize_command(&webview, token.as_deref(), permissions::Scope::Download)
+        .map_err(Error::Unauthorized)?;
+    use futures_util::StreamExt;
+    
+    // Enough to keep the line busy without starving downloads started elsewhere
+    const MAX_PARALLEL: usize = 3;
+    
+    let batch_id = format!("batch_{}", generate_file_id().trim_start_matches("file_"));
+    let total = urls.len();
+    let mut progress = download::BatchProgress {
+        batch_id: batch_id.clone(),
+        total,
+        succeeded: 0,
+        failed: 0,
+        downloaded_bytes: 0,
+    };
+    events::emit_coalesced(&app_handle, "batch-progress", &batch_id, &progress);
+    
+    let mut downloads = futures_util::stream::iter(urls.into_iter().enumerate())
+        .map(|(index, url)| {
+            let download = start_download(
+                app_handle.clone(),
+                url.clone(),
+                headers.clone(),
+                bearer_token.clone(),
+                None,
+                None,
+                None,
+                None,
+            );
+            async move { (index, url, download.await) }
+        })
+        .buffer_unordered(MAX_PARALLEL);
+    
+    let mut items: Vec<Option<download::BatchItem<FileInfo>>> = vec![None; total];
+    while let Some((index, url, result)) = downloads.next().await {
+        items[index] = Some(match result {
+            Ok(file) => {
+                progress.succeeded += 1;
+                progress.downloaded_bytes += file.size;
+                download::BatchItem { url, file: Some(file), error: None }
+            }
+            Err(e) => {
+                progress.failed += 1;
+                download::BatchItem { url, file: None, error: Some(e.to_string()) }
+            }
+        });
+        events::emit_coalesced(&app_handle, "batch-progress", &batch_id, &progress);
+    }
+    
+    events::flush(&app_handle);
+    
+    Ok(download::BatchReport {
+        batch_id,
+        succeeded: progress.succeeded,
+        failed: progress.failed,
+        items: items.into_iter().flatten().collect(),
+    })
+}
+
+// Run a download job to completion or until it is paused
+async fn run_download_job(
+    app_handle: &AppHandle,
+    mut job: download::DownloadJob,
+) -> Result<FileInfo, Error> {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    
+    let control = download::DownloadControl::default();
+    {
+        let mut app_state = state.lock().unwrap();
+        app_state.active_downloads.insert(job.id.clone(), control.clone());
+    }
+    
+    // Wait for a free slot; higher priority downloads get one first, then expiring links
+    let queue = app_handle.state::<queue::DownloadQueue>();
+    let job_id = job.id.clone();
+    let slot = tokio::select! {
+        slot = queue.acquire(&job_id, job.priority, job.expires_at) => Some(slot),
+        _ = control.cancelled() => None,
+    };
+    
+    let settings = settings::load_settings(app_handle).unwrap_or_default();
+    let started_at = history::now_secs();
+    
+    let outcome = match slot {
+        Some(_slot) => {
+            let _ = app_handle.emit("download-started", download::DownloadStarted {
+                id: job.id.clone(),
+                url: job.url.clone(),
+                file_name: job.file_name.clone(),
+                resumed_from: job.downloaded,
+            });
+            download::run_with_mirrors(app_handle, &mut job, &control, &settings).await
+        }
+        // Cancelled while still queued
+        None => Ok(download::DownloadOutcome::Cancelled),
+    };
+    
+    state.lock().unwrap().active_downloads.remove(&job.id);
+    // The last progress update must reach the webview before the outcome does
+    events::flush(app_handle);
+    
+    let outcome = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            webhook::notify(app_handle, "download.failed", &download::DownloadFailed {
+                id: job.id.clone(),
+                url: job.url.clone(),
+                error: e.to_string(),
+            });
+            history::record(app_handle, history::HistoryEntry {
+                error: Some(e.to_string()),
+                ..history::HistoryEntry::from_job(&job, started_at)
+            }, history::Outcome::Failed);
+            pending::discard(app_handle, &job).await;
+            return Err(e);
+        }
+    };
+    
+    match outcome {
+        download::DownloadOutcome::Paused => {
+            pending::save(app_handle, &job, false);
+            let id = job.id.clone();
+            let _ = app_handle.emit("download-paused", &job);
+            state.lock().unwrap().paused_downloads.insert(id.clone(), job);
+            Err(Error::DownloadPaused(id))
+        }
+        download::DownloadOutcome::NotModified => {
+            pending::discard(app_handle, &job).await;
+            let file_id = job.revalidate.map(|r| r.file_id).unwrap_or_default();
+            println!("{} has not changed, reusing {}", job.url, file_id);
+            find_cached_file(app_handle, |f| f.id == file_id).ok_or(Error::FileNotFound(file_id))
+        }
+        download::DownloadOutcome::Cancelled => {
+            history::record(app_handle, history::HistoryEntry::from_job(&job, started_at), history::Outcome::Cancelled);
+            discard_partial_download(app_handle, &job).await;
+            Err(Error::DownloadCancelled(job.id))
+        }
+        download::DownloadOutcome::Completed => {
+            // Move the finished .part into the download folder, or next to the other
+            // downloads in the temp dir when none is configured
+            if pending::is_partial(&job) {
+                let destination = match stable_download_dir(&settings) {
+                    Some(download_dir) => {
+                        tokio::fs::create_dir_all(&download_dir).await?;
+                        paths::unique_file_path(&download_dir, &job.file_name)?
+                    }
+                    None => {
+                        let app_state = state.lock().unwrap();
+                        app_state.temp_dir.path().join(&job.id).join(&job.file_name)
+                    }
+                };
+                pending::finalize(&job.file_path, &destination).await?;
+                if let Some(name) = destination.file_name() {
+                    job.file_name = name.to_string_lossy().to_string();
+                }
+                job.file_path = destination;
+                pending::remove(app_handle, &job.id);
+            }
+            
+            // Sniff the content while it is still plaintext
+            let mime_types = content_type::detect(&job.file_path);
+            
+            // In an ephemeral session the file only stays on disk in encrypted form
+            let encrypted = match ephemeral::active_session(app_handle) {
+                Some(session) => {
+                    let path = job.file_path.clone();
+                    tokio::task::spawn_blocking(move || session.encrypt_file(&path))
+                        .await
+                        .map_err(|e| Error::IoError(std::io::Error::other(e)))??;
+                    true
+                }
+                None => false,
+            };
+            
+            let sha256 = job.take_sha256();
+            history::record(app_handle, history::HistoryEntry {
+                sha256: sha256.clone(),
+                ..history::HistoryEntry::from_job(&job, started_at)
+            }, history::Outcome::Completed);
+            let original_url = job.sources.first().cloned().unwrap_or_else(|| job.url.clone());
+            let source_url = (job.url != original_url).then_some(job.url);
+            
+            // Create the file info
+            let file_info = FileInfo {
+                id: job.id.clone(),
+                original_url,
+                file_path: job.file_path.to_string_lossy().to_string(),
+                file_name: job.file_name,
+                mime_type: mime_types.mime_type,
+                detected_mime_type: mime_types.detected,
+                extension_mime_type: mime_types.from_extension,
+                size: job.downloaded,
+                encrypted,
+                sha256,
+                source_url,
+                resolved_url: job.redirect_chain.last().cloned(),
+                redirect_chain: job.redirect_chain,
+                short_url: job.short_url,
+                etag: job.etag,
+                last_modified: job.last_modified,
+                cached: false,
+                conversation: None,
+                tags: Vec::new(),
+            };
+            
+            // Update the app state
+            let mut app_state = state.lock().unwrap();
+            app_state.current_file = Some(file_info.clone());
+            app_state.downloaded_files.insert(job.id, file_info.clone());
+            drop(app_state);
+            
+            webhook::notify(app_handle, "download.completed", &file_info);
+            
+            Ok(file_info)
+        }
+    }
+}
+
+// Folder set in the settings for completed downloads, if any
+fn stable_download_dir(settings: &settings::AppSettings) -> Option<PathBuf> {
+    let download_dir = settings.download_dir.trim();
+    (!download_dir.is_empty()).then(|| PathBuf::from(download_dir))
+}
+
+// Command to pause a running download, keeping the partial file
+#[tauri::command]
+fn pause_download(app_handle: AppHandle, id: String) -> Result<(), Error> {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().unwrap();
+    
+    app_state.active_downloads
+        .get(&id)
+        .ok_or_else(|| Error::FileNotFound(id.clone()))?
+        .pause();
+    
+    Ok(())
+}
+
+// Command to cancel a running or paused download and remove its partial file
+#[tauri::command]
+async fn cancel_download(app_handle: AppHandle, id: String) -> Result<(), Error> {
+    let paused_job = {
+        let state = app_handle.state::<Arc<Mutex<AppState>>>();
+        let mut app_state = state.lock().unwrap();
+        
+        if let Some(control) = app_state.active_downloads.get(&id) {
+            // The download loop cleans up after itself once it notices the token
+            control.cancel();
+            return Ok(());
+        }
+        
+        app_state.paused_downloads
+            .remove(&id)
+            .ok_or_else(|| Error::FileNotFound(id.clone()))?
+    };
+    
+    discard_partial_download(&app_handle, &paused_job).await;
+    Ok(())
+}
+
+// Delete the partial file of an aborted download and notify the frontend
+async fn discard_partial_download(app_handle: &AppHandle, job: &download::DownloadJob) {
+    pending::discard(app_handle, job).await;
+    
+    let cancelled = download::DownloadCancelled {
+        id: job.id.clone(),
+        url: job.url.clone(),
+        downloaded: job.downloaded,
+    };
+    webhook::notify(app_handle, "download.cancelled", &cancelled);
+    let _ = app_handle.emit("download-cancelled", cancelled);
+}
+
+// Command to resume a paused download, using a Range request when the server supports it
+#[tauri::command]
+async fn resume_download(app_handle: AppHandle, id: String) -> Result<FileInfo, Error> {
+    let job = {
+        let state = app_handle.state::<Arc<Mutex<AppState>>>();
+        let mut app_state = state.lock().unwrap();
+        app_state.paused_downloads
+            .remove(&id)
+            .ok_or_else(|| Error::FileNotFound(id.clone()))?
     };
     
-    // Update the app state
+    run_download_job(&app_handle, job).await
+}
+
+// Command to change the priority of a queued or paused download
+#[tauri::command]
+fn reprioritize_download(app_handle: AppHandle, id: String, priority: queue::Priority) -> Result<(), Error> {
+    if app_handle.state::<queue::DownloadQueue>().reprioritize(&id, priority) {
+        return Ok(());
+    }
+    
+    // A paused download queues with its new priority once resumed
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
     let mut app_state = state.lock().unwrap();
-    app_state.current_file = Some(file_info.clone());
-    app_state.downloaded_files.insert(id, file_info.clone());
+    if let Some(job) = app_state.paused_downloads.get_mut(&id) {
+        job.priority = priority;
+        return Ok(());
+    }
     
-    Ok(file_info)
+    // Already transferring, nothing left to reorder
+    if app_state.active_downloads.contains_key(&id) {
+        return Ok(());
+    }
+    Err(Error::FileNotFound(id))
 }
 
 // Command to get the current file info
@@ -217,6 +810,78 @@ fn get_file_by_id(app_handle: AppHandle, id: String) -> Result<FileInfo, Error>
         .ok_or_else(|| Error::FileNotFound(id))
 }
 
+// Command to set the conversation and tags of a downloaded file
+#[tauri::command]
+fn label_file(
+    app_handle: AppHandle,
+    id: String,
+    conversation: Option<String>,
+    tags: Option<Vec<String>>,
+) -> Result<FileInfo, Error> {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().unwrap();
+    
+    let file_info = app_state.downloaded_files
+        .get_mut(&id)
+        .ok_or_else(|| Error::FileNotFound(id.clone()))?;
+    file_info.conversation = conversation.filter(|c| !c.is_empty());
+    file_info.tags = tags.unwrap_or_default();
+    let file_info = file_info.clone();
+    
+    if app_state.current_file.as_ref().is_some_and(|f| f.id == id) {
+        app_state.current_file = Some(file_info.clone());
+    }
+    
+    Ok(file_info)
+}
+
+// Command to delete a downloaded file, to the recycle bin unless `to_trash` or the settings say otherwise
+#[tauri::command]
+async fn delete_file(app_handle: AppHandle, id: String, to_trash: Option<bool>) -> Result<(), Error> {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    
+    let file_info = {
+        let app_state = state.lock().unwrap();
+        app_state.downloaded_files
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Error::FileNotFound(id.clone()))?
+    };
+    
+    // Encrypted files are useless in the recycle bin and must not outlive their session
+    let settings = settings::load_settings(&app_handle).unwrap_or_default();
+    let to_trash = to_trash.unwrap_or(settings.delete_to_trash) && !file_info.encrypted;
+    let download_dir = stable_download_dir(&settings);
+    
+    let path = PathBuf::from(&file_info.file_path);
+    tokio::task::spawn_blocking(move || {
+        if to_trash {
+            trash::delete(&path).map_err(|e| Error::Trash(e.to_string()))?;
+        } else if let Err(e) = fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(Error::IoError(e));
+            }
+        }
+        
+        // Drop the download's folder too, unless something else is in it or it is the
+        // configured download folder
+        if let Some(parent) = path.parent().filter(|p| Some(p.to_path_buf()) != download_dir) {
+            let _ = fs::remove_dir(parent);
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| Error::IoError(std::io::Error::other(e)))??;
+    
+    let mut app_state = state.lock().unwrap();
+    app_state.downloaded_files.remove(&id);
+    if app_state.current_file.as_ref().is_some_and(|f| f.id == id) {
+        app_state.current_file = None;
+    }
+    
+    Ok(())
+}
+
 // Command to save file to a specific location
 #[tauri::command]
 async fn save_file(
@@ -257,7 +922,7 @@ async fn save_file(
     };
     
     // Copy the file to the destination
-    fs::copy(&file_info.file_path, &destination)?;
+    copy_to_destination(&app_handle, &file_info, &destination)?;
     
     Ok(destination.to_string_lossy().to_string())
 }
@@ -278,30 +943,128 @@ async fn handle_save_dialog_result(
     };
     
     // Copy the file to the destination
-    fs::copy(&file_info.file_path, &save_path)?;
+    copy_to_destination(&app_handle, &file_info, std::path::Path::new(&save_path))?;
     
     Ok(save_path)
 }
 
-#[tauri::command(async)]
-async fn copy_file_to_clipboard(app: AppHandle, path: String) -> Result<(), String> {
-    let shell = app.shell();
-    let output = shell
-        .sidecar("fct")
-        .map_err(|e| e.to_string())?
-        .args(["--file", &path, "--copy"])
-        .output()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        return Err(format!("fct.exe failed: {}", stderr));
+// Copy a registered file to a user chosen destination, which may be nested deeper than MAX_PATH
+fn copy_to_destination(app_handle: &AppHandle, file_info: &FileInfo, destination: &std::path::Path) -> Result<(), Error> {
+    let check = paths::preflight(destination)?;
+    if check.exceeds_limit {
+        println!(
+            "Destination is {} characters long, applications without long path support may not open it: {}",
+            check.length, check.path
+        );
     }
+    
+    copy_plaintext(app_handle, file_info, &paths::extended_length(destination))
+}
 
+// Copy a registered file to `destination`, decrypting ephemeral files on the way
+fn copy_plaintext(app_handle: &AppHandle, file_info: &FileInfo, destination: &std::path::Path) -> Result<(), Error> {
+    match encrypted_file_session(app_handle, &file_info.file_path) {
+        Some(session) => {
+            session.decrypt_to_file(std::path::Path::new(&file_info.file_path), destination)?;
+        }
+        None => {
+            fs::copy(&file_info.file_path, destination)?;
+        }
+    }
     Ok(())
 }
 
+// The ephemeral session holding the key for the encrypted file at `path`, if any
+fn encrypted_file_session(app_handle: &AppHandle, path: &str) -> Option<Arc<ephemeral::EphemeralSession>> {
+    let state = app_handle.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().unwrap();
+    
+    let encrypted = app_state.downloaded_files
+        .values()
+        .any(|f| f.encrypted && f.file_path == path);
+    
+    if encrypted {
+        app_state.ephemeral.clone()
+    } else {
+        None
+    }
+}
+
+// Path that can be handed to other programs, using a plaintext working copy for ephemeral files
+fn shareable_path(app_handle: &AppHandle, path: &str) -> Result<String, Error> {
+    match encrypted_file_session(app_handle, path) {
+        Some(session) => Ok(session
+            .working_copy(std::path::Path::new(path))?
+            .to_string_lossy()
+            .to_string()),
+        None => Ok(path.to_string()),
+    }
+}
+
+#[tauri::command(async)]
+async fn copy_file_to_clipboard(app: AppHandle, path: String, mode: Option<clipboard::CopyMode>) -> Result<(), String> {
+    let mode = mode.unwrap_or_default();
+    let file = {
+        let state = app.state::<Arc<Mutex<AppState>>>();
+        let app_state = state.lock().unwrap();
+        app_state.downloaded_files
+            .values()
+            .find(|f| f.file_path == path)
+            .cloned()
+    };
+    clipboard::run_blocking(&app, |app| {
+        clipboard::preserve_previous(app);
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    
+    // Images also go on as a bitmap, for applications that don't take files
+    #[cfg(windows)]
+    if let Some(file) = file.as_ref().filter(|f| f.mime_type.starts_with("image/") && mode != clipboard::CopyMode::PathText) {
+        match clipboard::copy_image(&app, file).await {
+            Ok(()) => return Ok(()),
+            Err(e) => println!("Copying {} as a file only: {}", file.file_name, e),
+        }
+    }
+    
+    let ids: Vec<String> = file.map(|f| vec![f.id]).unwrap_or_default();
+    let path = shareable_path(&app, &path).map_err(|e| e.to_string())?;
+    
+    // fct.exe puts the file on the clipboard the way Windows apps expect; when it can't be
+    // started, and on other platforms, the native file list is used
+    #[cfg(windows)]
+    if mode != clipboard::CopyMode::PathText {
+        let output = match app.shell().sidecar("fct") {
+            Ok(command) => command.args(["--file", &path, "--copy"]).output().await,
+            Err(e) => Err(e),
+        };
+        match output {
+            Ok(output) if output.status.success() => {
+                clipboard::record_copy(&app, ids, vec![path.into()]);
+                return Ok(());
+            }
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+                if mode != clipboard::CopyMode::Auto {
+                    return Err(format!("fct.exe failed: {}", stderr));
+                }
+                println!("fct.exe failed, using the native clipboard: {}", stderr);
+            }
+            Err(e) => println!("Could not run fct.exe, using the native clipboard: {}", e),
+        }
+    }
+    
+    let paths = vec![std::path::PathBuf::from(path)];
+    clipboard::run_blocking(&app, move |app| {
+        let used = clipboard::copy_paths(app, &paths, mode)?;
+        clipboard::record_paths_copy(app, ids, paths, used);
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 fn set_theme(app_handle: AppHandle, theme: String) -> Result<(), Error> {
   let window = app_handle.get_webview_window("main").unwrap();
@@ -311,7 +1074,7 @@ fn set_theme(app_handle: AppHandle, theme: String) -> Result<(), Error> {
 
 // Command to read file as bytes for frontend consumption
 #[tauri::command]
-async fn read_file_bytes(path: String) -> Result<Vec<u8>, Error> {
+async fn read_file_bytes(app_handle: AppHandle, path: String) -> Result<Vec<u8>, Error> {
     println!("Reading file bytes for: {}", path);
     
     // Check if the file exists
@@ -319,6 +1082,12 @@ async fn read_file_bytes(path: String) -> Result<Vec<u8>, Error> {
         return Err(Error::FileNotFound(path));
     }
     
+    if let Some(session) = encrypted_file_session(&app_handle, &path) {
+        return tokio::task::spawn_blocking(move || session.decrypt_to_vec(std::path::Path::new(&path)))
+            .await
+            .map_err(|e| Error::IoError(std::io::Error::other(e)))?;
+    }
+    
     // Read the file
     let bytes = tokio::fs::read(&path).await?;
     Ok(bytes)
@@ -326,7 +1095,7 @@ async fn read_file_bytes(path: String) -> Result<Vec<u8>, Error> {
 
 // Command to open a file using the system's default application
 #[tauri::command]
-async fn open_file(path: String) -> Result<(), Error> {
+async fn open_file(app_handle: AppHandle, path: String) -> Result<(), Error> {
     use std::process::{Command, Stdio};
     
     println!("Attempting to open file: {}", path);
@@ -336,6 +1105,8 @@ async fn open_file(path: String) -> Result<(), Error> {
         return Err(Error::FileNotFound(path));
     }
     
+    let path = shareable_path(&app_handle, &path)?;
+    
     #[cfg(target_os = "windows")]
     {
         let result = Command::new("cmd")
@@ -394,16 +1165,42 @@ async fn open_file(path: String) -> Result<(), Error> {
     Ok(())
 }
 
+// Hand a download link to the frontend, unless it points at a host outside the allowlist
+fn forward_deep_link(app_handle: &AppHandle, link: &str) {
+    // Same prefix handling as the frontend: wab2b-helper:https://... or wab2b-helper://https://...
+    let target = link
+        .get(..DEEP_LINK_SCHEME.len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case(DEEP_LINK_SCHEME))
+        .map(|_| link[DEEP_LINK_SCHEME.len()..].trim_start_matches('/'))
+        .unwrap_or(link);
+    
+    if let Ok(url) = Url::parse(target) {
+        let settings = settings::load_settings(app_handle).unwrap_or_default();
+        if let Err(e) = download::check_host_allowed(&settings, &url) {
+            eprintln!("Ignoring deep link {}: {}", link, e);
+            let _ = app_handle.emit("deep-link-rejected", e.to_string());
+            return;
+        }
+    }
+    
+    let _ = app_handle.emit("deep-link-received", link);
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let state = init_app_state().expect("Failed to initialize app state");
 
     tauri::Builder::default()
         .manage(Arc::new(Mutex::new(state)))
+        .manage(events::EventBridge::default())
+        .manage(queue::DownloadQueue::default())
         // ensure only one app instance; forward protocol URL to existing window
         .plugin(single_instance::init(|app, argv, _| {
+            if provisioning::handle_args(app, &argv) {
+                return;
+            }
             if let Some(link) = argv.get(1) {
-                app.emit("deep-link-received", link).unwrap();
+                forward_deep_link(app, link);
             }
         }))
         .plugin(tauri_plugin_deep_link::init())
@@ -416,8 +1213,8 @@ pub fn run() {
             #[cfg(desktop)]
             {
                 let args: Vec<String> = env::args().collect();
-                if args.len() > 1 {
-                    app.emit("deep-link-received", &args[1]).unwrap();
+                if !provisioning::handle_args(app.handle(), &args) && args.len() > 1 {
+                    forward_deep_link(app.handle(), &args[1]);
                 }
             }
             
@@ -426,27 +1223,96 @@ pub fn run() {
                 eprintln!("Failed to initialize updater: {}", err);
             }
             
+            heartbeat::start(app.handle().clone());
+            schedule::start(app.handle().clone());
+            clipboard::start_monitor(app.handle().clone());
+            shared_folder::start(app.handle().clone());
+            events::start(app.handle().clone());
+            
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             download_file,
+            download_files,
+            pause_download,
+            resume_download,
+            reprioritize_download,
+            pending::resume_pending_downloads,
+            schedule::schedule_download,
+            schedule::get_scheduled_downloads,
+            schedule::cancel_scheduled_download,
+            cancel_download,
             get_current_file,
             get_file_by_id,
+            label_file,
+            delete_file,
             copy_file_to_clipboard,
             save_file,
             handle_save_dialog_result,
             set_theme,
             read_file_bytes,
             open_file,
+            archive::list_archive_contents,
+            archive::create_protected_bundle,
+            archive::zip_files,
+            archive::extract_archive,
+            ephemeral::set_ephemeral_mode,
+            ephemeral::get_ephemeral_mode,
+            checksums::generate_checksums,
+            heartbeat::get_heartbeat_status,
+            clipboard::copy_file_with_metadata,
+            clipboard::copy_attachments,
+            clipboard::read_clipboard_summary,
+            clipboard::copy_image_to_clipboard,
+            clipboard::copy_text,
+            clipboard::copy_html,
+            clipboard::restore_clipboard,
+            clipboard::copy_as_data_uri,
+            paths::check_save_path,
+            webhook::list_webhook_deliveries,
+            webhook::redeliver,
+            shared_folder::sync_shared_folder,
+            history::get_download_history,
+            history::clear_history,
+            permissions::create_api_token,
+            permissions::list_api_tokens,
+            permissions::revoke_api_token,
             // Settings commands
             settings::get_settings,
             settings::update_settings,
             // GitHub update system commands
             updater::check_for_updates,
+            updater::compare_versions,
+            updater::skip_version,
+            updater::get_available_downloads,
             updater::download_asset,
+            updater::download_delta_update,
             updater::verify_file_hash,
-            updater::install_update
+            updater::install_update,
+            updater::get_required_update,
+            updater::get_staged_update,
+            updater::discard_staged_update,
+            updater::list_backups,
+            updater::purge_backups,
+            updater::rollback_update,
+            updater::cleanup_update_artifacts
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            match event {
+                // Nothing from an ephemeral session may survive the process
+                tauri::RunEvent::Exit => {
+                    ephemeral::end_session(app_handle);
+                    // Replace the executable once we're gone
+                    updater::install_staged_update(app_handle);
+                }
+                // Catch the webview up on progress held back while it was in the background
+                tauri::RunEvent::WindowEvent {
+                    event: tauri::WindowEvent::Focused(true),
+                    ..
+                } => events::flush(app_handle),
+                _ => {}
+            }
+        });
 }
\ No newline at end of file
@@ -0,0 +1,143 @@
+/*!
+ * Guard against downloads aimed at the local network: private, loopback and link-local targets
+ */
+
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use url::{Host, Url};
+
+use crate::download::host_matches;
+use crate::settings::AppSettings;
+use crate::Error;
+
+/// Whether `ip` belongs to a range that isn't reachable from the internet
+pub fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64)
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_private(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            v6.is_loopback()
+                || v6.is_unspecified()
+                // Unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link-local, fe80::/10
+                || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/// A host that resolved only to addresses the helper may not connect to
+#[derive(Debug)]
+pub struct BlockedAddress {
+    pub host: String,
+    pub address: IpAddr,
+}
+
+impl fmt::Display for BlockedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} resolves to {}", self.host, self.address)
+    }
+}
+
+impl std::error::Error for BlockedAddress {}
+
+impl From<BlockedAddress> for Error {
+    fn from(blocked: BlockedAddress) -> Self {
+        Error::PrivateAddress(blocked.to_string())
+    }
+}
+
+/// Whether `host` may connect to private addresses; `*` in the list exempts every host
+fn is_exempt(allowed: &[String], host: &str) -> bool {
+    allowed.iter().any(|pattern| pattern.trim() == "*" || host_matches(pattern, host))
+}
+
+/// Refuse URLs whose host is a private IP address. Host names are checked when they
+/// are resolved, see [`GuardedResolver`].
+pub fn check_url(settings: &AppSettings, url: &Url) -> Result<(), Error> {
+    let address = match url.host() {
+        Some(Host::Ipv4(v4)) => IpAddr::V4(v4),
+        Some(Host::Ipv6(v6)) => IpAddr::V6(v6),
+        _ => return Ok(()),
+    };
+
+    let host = url.host_str().unwrap_or_default();
+    if is_private(address) && !is_exempt(&settings.allowed_private_hosts, host) {
+        return Err(BlockedAddress { host: host.to_string(), address }.into());
+    }
+    Ok(())
+}
+
+/// Addresses the helper may connect to out of those `host` resolved to. Private ones are
+/// dropped unless the host is exempt, and resolving only to private ones is an error.
+pub fn filter_addresses(allowed: &[String], host: &str, addresses: Vec<SocketAddr>) -> Result<Vec<SocketAddr>, BlockedAddress> {
+    if is_exempt(allowed, host) {
+        return Ok(addresses);
+    }
+
+    let blocked = addresses.first().map(|address| address.ip());
+    let public: Vec<SocketAddr> = addresses
+        .into_iter()
+        .filter(|address| !is_private(address.ip()))
+        .collect();
+    match blocked {
+        Some(address) if public.is_empty() => Err(BlockedAddress { host: host.to_string(), address }),
+        _ => Ok(public),
+    }
+}
+
+/// Resolve `host` for a connection made outside of reqwest, e.g. FTP and SFTP
+pub async fn resolve(settings: &AppSettings, host: &str, port: u16) -> Result<Vec<SocketAddr>, Error> {
+    let addresses = tokio::net::lookup_host((host, port)).await?.collect();
+    Ok(filter_addresses(&settings.allowed_private_hosts, host, addresses)?)
+}
+
+/// DNS resolver for download clients that never hands out private addresses, so redirects
+/// and DNS rebinding can't reach the local network either
+#[derive(Debug, Clone)]
+pub struct GuardedResolver {
+    allowed: Vec<String>,
+}
+
+impl GuardedResolver {
+    pub fn new(settings: &AppSettings) -> Self {
+        Self { allowed: settings.allowed_private_hosts.clone() }
+    }
+}
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allowed = self.allowed.clone();
+        Box::pin(async move {
+            let host = name.as_str();
+            let addresses = tokio::net::lookup_host((host, 0)).await?.collect();
+            let addresses = filter_addresses(&allowed, host, addresses)?;
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// The address check that made a request fail, if any
+pub fn blocked_cause(e: &reqwest::Error) -> Option<&BlockedAddress> {
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        if let Some(blocked) = cause.downcast_ref::<BlockedAddress>() {
+            return Some(blocked);
+        }
+        source = cause.source();
+    }
+    None
+}
@@ -0,0 +1,260 @@
+/*!
+ * Destination path checks: long Windows paths, free disk space and safe file names
+ */
+
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+
+use crate::Error;
+
+/// Classic Windows path limit, including the terminating NUL
+pub const MAX_PATH: usize = 260;
+
+/// Longest single file or folder name NTFS accepts
+pub const MAX_COMPONENT: usize = 255;
+
+/// Longest name `sanitize_file_name` produces, leaving room for `.part` and ` (n)`
+const MAX_FILE_NAME: usize = MAX_COMPONENT - 20;
+
+/// Device names Windows reserves, with or without an extension (`con.txt`)
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Result of checking a destination before writing to it
+#[derive(Debug, Clone, Serialize)]
+pub struct PathCheck {
+    pub path: String,
+    /// Length in UTF-16 code units, as Windows counts it
+    pub length: usize,
+    pub max_length: usize,
+    /// Whether the system has `LongPathsEnabled` set
+    pub long_paths_enabled: bool,
+    /// True when other applications may fail to open the file at this path
+    pub exceeds_limit: bool,
+}
+
+/// Check a destination path. Names longer than any file system allows are an error;
+/// a path only too long for applications without long path support is reported.
+pub fn preflight(path: &Path) -> Result<PathCheck, Error> {
+    if let Some(component) = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .find(|c| c.encode_utf16().count() > MAX_COMPONENT)
+    {
+        return Err(Error::PathTooLong(format!(
+            "\"{}\" is longer than {} characters",
+            component, MAX_COMPONENT
+        )));
+    }
+
+    let length = path.as_os_str().to_string_lossy().encode_utf16().count();
+    let long_paths_enabled = long_paths_enabled();
+
+    Ok(PathCheck {
+        path: path.to_string_lossy().to_string(),
+        length,
+        max_length: MAX_PATH - 1,
+        long_paths_enabled,
+        exceeds_limit: !long_paths_enabled && length >= MAX_PATH,
+    })
+}
+
+/// Extended-length (`\\?\`) form of an absolute path, so writes are not capped at `MAX_PATH`
+#[cfg(windows)]
+pub fn extended_length(path: &Path) -> PathBuf {
+    use std::ffi::OsString;
+    use std::path::{Component, Prefix};
+
+    let mut components = path.components();
+    let mut verbatim = match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::Disk(letter) => OsString::from(format!(r"\\?\{}:", letter as char)),
+            Prefix::UNC(server, share) => {
+                let mut unc = OsString::from(r"\\?\UNC\");
+                unc.push(server);
+                unc.push(r"\");
+                unc.push(share);
+                unc
+            }
+            // Already verbatim or a device path
+            _ => return path.to_path_buf(),
+        },
+        _ => return path.to_path_buf(),
+    };
+
+    // Drive-relative paths such as `C:foo` cannot be made verbatim
+    if !matches!(components.next(), Some(Component::RootDir)) {
+        return path.to_path_buf();
+    }
+
+    // Verbatim paths skip normalization, so resolve `.` and `..` here
+    let mut parts = Vec::new();
+    for component in components {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+
+    for part in parts {
+        verbatim.push(r"\");
+        verbatim.push(part);
+    }
+
+    PathBuf::from(verbatim)
+}
+
+#[cfg(not(windows))]
+pub fn extended_length(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+/// Whether `HKLM\SYSTEM\CurrentControlSet\Control\FileSystem\LongPathsEnabled` is set
+#[cfg(windows)]
+fn long_paths_enabled() -> bool {
+    use windows_sys::Win32::Foundation::ERROR_SUCCESS;
+    use windows_sys::Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_DWORD};
+
+    let wide = |s: &str| s.encode_utf16().chain(std::iter::once(0)).collect::<Vec<u16>>();
+    let subkey = wide(r"SYSTEM\CurrentControlSet\Control\FileSystem");
+    let value = wide("LongPathsEnabled");
+
+    let mut data: u32 = 0;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            subkey.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_DWORD,
+            std::ptr::null_mut(),
+            &mut data as *mut u32 as *mut std::ffi::c_void,
+            &mut size,
+        )
+    };
+
+    status == ERROR_SUCCESS && data == 1
+}
+
+#[cfg(not(windows))]
+fn long_paths_enabled() -> bool {
+    true
+}
+
+/// Bytes the current user may still write to the volume holding `directory`
+#[cfg(windows)]
+pub fn available_space(directory: &Path) -> std::io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = directory.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+    let mut available: u64 = 0;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, std::ptr::null_mut(), std::ptr::null_mut())
+    };
+
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(available)
+}
+
+/// Bytes the current user may still write to the volume holding `directory`
+#[cfg(unix)]
+pub fn available_space(directory: &Path) -> std::io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(directory.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // Field widths differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Path for `name` in `directory` that doesn't replace an existing file, numbered like `name (1).ext`
+pub fn unique_file_path(directory: &Path, name: &str) -> std::io::Result<PathBuf> {
+    let mut used = std::collections::HashSet::new();
+    for entry in std::fs::read_dir(directory)? {
+        used.insert(entry?.file_name().to_string_lossy().to_lowercase());
+    }
+
+    Ok(directory.join(crate::archive::unique_entry_name(name, &mut used)))
+}
+
+/// File name that is safe to create on every platform, from a name taken from a URL or a
+/// server header. Only the last path component is kept, characters Windows rejects are
+/// replaced, reserved device names are prefixed and overlong names are shortened.
+/// Returns None when nothing usable is left.
+pub fn sanitize_file_name(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next()?;
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c => c,
+        })
+        .collect();
+
+    // Windows drops trailing dots and spaces, which also takes care of `.` and `..`
+    let name = name
+        .trim_start()
+        .trim_end_matches(|c: char| c == '.' || c.is_whitespace());
+    if name.is_empty() {
+        return None;
+    }
+
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    let name = if RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved)) {
+        format!("_{}", name)
+    } else {
+        name.to_string()
+    };
+
+    Some(truncate_file_name(&name))
+}
+
+/// Shorten `name` to `MAX_FILE_NAME` UTF-16 units, keeping a short extension
+fn truncate_file_name(name: &str) -> String {
+    if name.encode_utf16().count() <= MAX_FILE_NAME {
+        return name.to_string();
+    }
+
+    let extension = Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .filter(|e| e.len() <= 16)
+        .map(|e| format!(".{}", e))
+        .unwrap_or_default();
+    let budget = MAX_FILE_NAME - extension.encode_utf16().count();
+
+    let mut stem = String::new();
+    let mut length = 0;
+    for c in name[..name.len() - extension.len()].chars() {
+        length += c.len_utf16();
+        if length > budget {
+            break;
+        }
+        stem.push(c);
+    }
+
+    format!("{}{}", stem.trim_end_matches(|c: char| c == '.' || c.is_whitespace()), extension)
+}
+
+// Command to check a destination path before saving to it
+#[tauri::command]
+pub fn check_save_path(path: String) -> Result<PathCheck, Error> {
+    preflight(Path::new(&path))
+}
@@ -0,0 +1,246 @@
+/*!
+ * Downloads that survive a restart: partial files outside the temp dir and their job state
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::download::{BatchItem, DownloadJob};
+use crate::{ephemeral, FileInfo};
+
+/// Name of the job list in the app data directory
+const PENDING_FILE: &str = "pending-downloads.json";
+
+/// Folder in the app data directory holding partial downloads
+const PARTIAL_FOLDER: &str = "partial";
+
+/// Extension of a file that is still being downloaded
+const PART_EXTENSION: &str = "part";
+
+/// Serializes access to the job list
+static PENDING_LOCK: Mutex<()> = Mutex::new(());
+
+/// A download that has not finished yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingDownload {
+    pub job: DownloadJob,
+    /// True while the download is running; the partial file may then be longer than
+    /// `job.downloaded` or, for segmented downloads, contain gaps
+    pub in_flight: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PendingList {
+    downloads: Vec<PendingDownload>,
+}
+
+fn pending_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    Ok(app_dir.join(PENDING_FILE))
+}
+
+fn load(app: &AppHandle) -> Result<PendingList, String> {
+    let path = pending_path(app)?;
+    if !path.exists() {
+        return Ok(PendingList::default());
+    }
+
+    let json = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read pending downloads: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse pending downloads: {}", e))
+}
+
+fn store(app: &AppHandle, list: &PendingList) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(list)
+        .map_err(|e| format!("Failed to serialize pending downloads: {}", e))?;
+
+    fs::write(pending_path(app)?, json)
+        .map_err(|e| format!("Failed to write pending downloads: {}", e))
+}
+
+/// Folder for the partial file of download `id`, kept across restarts
+pub fn partial_dir(app: &AppHandle, id: &str) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    Ok(app_dir.join(PARTIAL_FOLDER).join(id))
+}
+
+/// Name of the partial file for `file_name`
+pub fn part_name(file_name: &str) -> String {
+    format!("{}.{}", file_name, PART_EXTENSION)
+}
+
+/// Whether `job` writes to a partial file that is moved into place once complete
+pub fn is_partial(job: &DownloadJob) -> bool {
+    job.file_path.file_name() == Some(std::ffi::OsStr::new(&part_name(&job.file_name)))
+}
+
+/// Record the current state of `job`. Ephemeral downloads are never recorded.
+pub fn save(app_handle: &AppHandle, job: &DownloadJob, in_flight: bool) {
+    if ephemeral::active_session(app_handle).is_some() {
+        return;
+    }
+
+    let _guard = PENDING_LOCK.lock().unwrap();
+    let result = load(app_handle).and_then(|mut list| {
+        let entry = PendingDownload { job: job.clone(), in_flight };
+        match list.downloads.iter_mut().find(|p| p.job.id == job.id) {
+            Some(existing) => *existing = entry,
+            None => list.downloads.push(entry),
+        }
+        store(app_handle, &list)
+    });
+
+    if let Err(e) = result {
+        println!("Failed to record pending download {}: {}", job.id, e);
+    }
+}
+
+/// Forget download `id`, once it completed or was given up
+pub fn remove(app_handle: &AppHandle, id: &str) {
+    let _guard = PENDING_LOCK.lock().unwrap();
+    let result = load(app_handle).and_then(|mut list| {
+        let count = list.downloads.len();
+        list.downloads.retain(|p| p.job.id != id);
+        if list.downloads.len() == count {
+            return Ok(());
+        }
+        store(app_handle, &list)
+    });
+
+    if let Err(e) = result {
+        println!("Failed to forget pending download {}: {}", id, e);
+    }
+}
+
+/// Move a finished download from its partial file to `destination`
+pub async fn finalize(partial: &Path, destination: &Path) -> std::io::Result<()> {
+    if let Some(parent) = destination.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    // The temp dir may be on another volume, where a rename is not possible
+    if tokio::fs::rename(partial, destination).await.is_err() {
+        tokio::fs::copy(partial, destination).await?;
+        tokio::fs::remove_file(partial).await?;
+    }
+
+    if let Some(parent) = partial.parent() {
+        let _ = tokio::fs::remove_dir(parent).await;
+    }
+    Ok(())
+}
+
+/// Forget `job` and delete its partial file and folder
+pub async fn discard(app_handle: &AppHandle, job: &DownloadJob) {
+    remove(app_handle, &job.id);
+
+    if let Err(e) = tokio::fs::remove_file(&job.file_path).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            println!("Failed to remove partial file {}: {}", job.file_path.display(), e);
+        }
+    }
+
+    // Drop the download's folder too, unless something else ended up in it
+    if let Some(download_dir) = job.file_path.parent() {
+        let _ = tokio::fs::remove_dir(download_dir).await;
+    }
+}
+
+/// Job state to continue `pending` with, based on what is actually on disk
+fn restore(pending: PendingDownload) -> Option<DownloadJob> {
+    let mut job = pending.job;
+    let on_disk = fs::metadata(&job.file_path).ok()?.len();
+
+    if pending.in_flight {
+        // Writes after the last checkpoint still count, unless ranges were being filled in
+        // out of order; then nothing before the first gap is known for sure
+        job.downloaded = if job.segmented { 0 } else { on_disk };
+    } else {
+        job.downloaded = job.downloaded.min(on_disk);
+    }
+
+    // A file that already has every byte may not have been finished properly, fetch it again
+    if job.total > 0 && job.downloaded >= job.total {
+        job.downloaded = 0;
+    }
+
+    // Hash state isn't stored, the download rebuilds it from the file
+    job.hasher = None;
+    Some(job)
+}
+
+// Command to continue the downloads that were interrupted by closing the app
+#[tauri::command]
+pub async fn resume_pending_downloads(app_handle: AppHandle) -> Result<Vec<BatchItem<FileInfo>>, String> {
+    use futures_util::StreamExt;
+
+    // Same limit as batch downloads
+    const MAX_PARALLEL: usize = 3;
+
+    let pending = {
+        let _guard = PENDING_LOCK.lock().unwrap();
+        load(&app_handle)?.downloads
+    };
+
+    let mut items = Vec::with_capacity(pending.len());
+    let mut jobs = Vec::new();
+    for entry in pending {
+        // Downloads running in this session are not pending from a previous one
+        let running = {
+            let state = app_handle.state::<std::sync::Arc<Mutex<crate::AppState>>>();
+            let app_state = state.lock().unwrap();
+            app_state.active_downloads.contains_key(&entry.job.id)
+                || app_state.paused_downloads.contains_key(&entry.job.id)
+        };
+        if running {
+            continue;
+        }
+
+        let url = entry.job.url.clone();
+        let id = entry.job.id.clone();
+        match restore(entry) {
+            Some(job) => jobs.push(job),
+            None => {
+                remove(&app_handle, &id);
+                items.push(BatchItem {
+                    url,
+                    file: None,
+                    error: Some("Partial file is gone".to_string()),
+                });
+            }
+        }
+    }
+
+    let mut downloads = futures_util::stream::iter(jobs)
+        .map(|job| {
+            let app_handle = app_handle.clone();
+            async move {
+                let url = job.url.clone();
+                (url, crate::run_download_job(&app_handle, job).await)
+            }
+        })
+        .buffer_unordered(MAX_PARALLEL);
+
+    while let Some((url, result)) = downloads.next().await {
+        items.push(match result {
+            Ok(file) => BatchItem { url, file: Some(file), error: None },
+            Err(e) => BatchItem { url, file: None, error: Some(e.to_string()) },
+        });
+    }
+
+    Ok(items)
+}
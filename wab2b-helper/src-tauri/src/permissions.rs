@@ -0,0 +1,221 @@
+/*!
+ * Scoped access tokens for callers outside the helper's own window
+ */
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, Webview};
+
+/// Name of the token store in the app data directory
+const TOKENS_FILE: &str = "api-tokens.json";
+
+/// Serializes access to the token store
+static TOKENS_LOCK: Mutex<()> = Mutex::new(());
+
+/// What a token allows its holder to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Start downloads
+    Download,
+    /// Read the download history
+    ReadHistory,
+    /// Everything, including changing settings
+    Admin,
+}
+
+/// An issued token, as shown to the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+}
+
+impl ApiToken {
+    fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+/// A token as stored; only a hash of the secret is kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredToken {
+    #[serde(flatten)]
+    token: ApiToken,
+    token_sha256: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TokenStore {
+    tokens: Vec<StoredToken>,
+}
+
+/// Returned once when a token is created; the secret can't be read back later
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedToken {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub token: String,
+}
+
+fn tokens_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    Ok(app_dir.join(TOKENS_FILE))
+}
+
+fn load_tokens(app: &AppHandle) -> Result<Vec<StoredToken>, String> {
+    let path = tokens_path(app)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let json = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read API tokens: {}", e))?;
+    serde_json::from_str::<TokenStore>(&json)
+        .map(|store| store.tokens)
+        .map_err(|e| format!("Failed to parse API tokens: {}", e))
+}
+
+fn save_tokens(app: &AppHandle, tokens: Vec<StoredToken>) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&TokenStore { tokens })
+        .map_err(|e| format!("Failed to serialize API tokens: {}", e))?;
+
+    fs::write(tokens_path(app)?, json)
+        .map_err(|e| format!("Failed to write API tokens: {}", e))
+}
+
+fn hash_token(token: &str) -> String {
+    crate::checksums::to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+/// Whether any token has been issued; until then the helper hasn't been paired with anything
+pub fn is_paired(app_handle: &AppHandle) -> bool {
+    let _guard = TOKENS_LOCK.lock().unwrap();
+    load_tokens(app_handle).is_ok_and(|tokens| !tokens.is_empty())
+}
+
+/// Check that `token` grants `scope`
+pub fn authorize(app_handle: &AppHandle, token: Option<&str>, scope: Scope) -> Result<(), String> {
+    let token = token
+        .filter(|t| !t.is_empty())
+        .ok_or_else(|| "An access token is required".to_string())?;
+
+    let _guard = TOKENS_LOCK.lock().unwrap();
+    let hash = hash_token(token);
+    let granted = load_tokens(app_handle)?
+        .iter()
+        .any(|t| t.token_sha256 == hash && t.token.allows(scope));
+
+    if granted {
+        Ok(())
+    } else {
+        Err(format!("Access token does not grant {:?}", scope))
+    }
+}
+
+/// Whether `webview` shows the helper's own frontend rather than a page loaded from elsewhere
+fn is_own_frontend(webview: &Webview) -> bool {
+    let Ok(url) = webview.url() else {
+        return false;
+    };
+    match url.scheme() {
+        "tauri" => true,
+        "http" | "https" if url.host_str() == Some("tauri.localhost") => true,
+        // `tauri dev` serves the frontend from the dev server
+        "http" | "https" if tauri::is_dev() => webview
+            .config()
+            .build
+            .dev_url
+            .as_ref()
+            .is_some_and(|dev_url| dev_url.origin() == url.origin()),
+        _ => false,
+    }
+}
+
+/// Check a sensitive command: the helper's own window may always run it, any other caller
+/// needs `token` granting `scope`
+pub fn authorize_command(webview: &Webview, token: Option<&str>, scope: Scope) -> Result<(), String> {
+    if is_own_frontend(webview) {
+        return Ok(());
+    }
+    authorize(webview.app_handle(), token, scope)
+}
+
+// Command to issue a new token with the given scopes
+#[tauri::command]
+pub fn create_api_token(
+    app_handle: AppHandle,
+    webview: Webview,
+    name: String,
+    scopes: Vec<Scope>,
+    token: Option<String>,
+) -> Result<CreatedToken, String> {
+    // Checked before taking the lock, which authorizing takes as well
+    authorize_command(&webview, token.as_deref(), Scope::Admin)?;
+    if name.trim().is_empty() || scopes.is_empty() {
+        return Err("A token needs a name and at least one scope".to_string());
+    }
+
+    let _guard = TOKENS_LOCK.lock().unwrap();
+    let mut tokens = load_tokens(&app_handle)?;
+    if tokens.iter().any(|t| t.token.name == name) {
+        return Err(format!("A token named {} already exists", name));
+    }
+
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    let token = crate::checksums::to_hex(&secret);
+
+    tokens.push(StoredToken {
+        token: ApiToken {
+            name: name.clone(),
+            scopes: scopes.clone(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        },
+        token_sha256: hash_token(&token),
+    });
+    save_tokens(&app_handle, tokens)?;
+
+    Ok(CreatedToken { name, scopes, token })
+}
+
+// Command to list issued tokens, without their secrets
+#[tauri::command]
+pub fn list_api_tokens(app_handle: AppHandle, webview: Webview, token: Option<String>) -> Result<Vec<ApiToken>, String> {
+    authorize_command(&webview, token.as_deref(), Scope::Admin)?;
+    let _guard = TOKENS_LOCK.lock().unwrap();
+    Ok(load_tokens(&app_handle)?.into_iter().map(|t| t.token).collect())
+}
+
+// Command to revoke a token by name
+#[tauri::command]
+pub fn revoke_api_token(app_handle: AppHandle, webview: Webview, name: String, token: Option<String>) -> Result<(), String> {
+    authorize_command(&webview, token.as_deref(), Scope::Admin)?;
+    let _guard = TOKENS_LOCK.lock().unwrap();
+    let mut tokens = load_tokens(&app_handle)?;
+
+    let count = tokens.len();
+    tokens.retain(|t| t.token.name != name);
+    if tokens.len() == count {
+        return Err(format!("Unknown API token: {}", name));
+    }
+
+    save_tokens(&app_handle, tokens)
+}
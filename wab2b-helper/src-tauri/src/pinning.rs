@@ -0,0 +1,199 @@
+/*!
+ * TLS certificate pinning by SHA-256 hash of the server's public key (SPKI)
+ */
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use base64::Engine;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::{verify_server_name, WebPkiServerVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::server::ParsedCertificate;
+use rustls::{DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+
+/// Host whose certificate failed its pin, set by the verifier so the error can name it
+pub type PinFailure = Arc<Mutex<Option<String>>>;
+
+/// Certificate verifier enforcing the configured pins.
+///
+/// Hosts without pins are accepted as before, matching the download client's lenient
+/// certificate handling; for pinned hosts the pin is what establishes trust, so a pinned
+/// issuer must have signed the presented chain and the leaf must be valid for the host.
+#[derive(Debug)]
+struct PinnedVerifier {
+    /// Pins per host pattern, as base64 SHA-256 hashes
+    pins: HashMap<String, Vec<String>>,
+    provider: Arc<CryptoProvider>,
+    failure: PinFailure,
+}
+
+impl PinnedVerifier {
+    fn pins_for(&self, host: &str) -> Option<&Vec<String>> {
+        let host = host.to_ascii_lowercase();
+        self.pins.get(&host).or_else(|| {
+            self.pins
+                .iter()
+                .find(|(pattern, _)| crate::download::host_matches(pattern, &host))
+                .map(|(_, pins)| pins)
+        })
+    }
+
+    /// Verify that `anchor` issued `end_entity` for `server_name`, through `intermediates`
+    fn verify_chain(
+        &self,
+        anchor: &CertificateDer<'_>,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        now: UnixTime,
+    ) -> Result<(), rustls::Error> {
+        let mut roots = RootCertStore::empty();
+        roots.add(anchor.clone().into_owned())?;
+        let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), self.provider.clone())
+            .build()
+            .map_err(|e| rustls::Error::General(format!("Failed to set up certificate verification: {}", e)))?;
+
+        verifier
+            .verify_server_cert(end_entity, intermediates, server_name, &[], now)
+            .map(|_| ())
+    }
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let host = server_name.to_str();
+        let Some(pins) = self.pins_for(&host) else {
+            return Ok(ServerCertVerified::assertion());
+        };
+
+        // Like HPKP, a pin may name the leaf or any certificate up the chain
+        let pinned = |cert: &CertificateDer<'_>| {
+            spki_sha256(cert).is_some_and(|hash| pins.iter().any(|pin| normalize_pin(pin) == hash))
+        };
+
+        let verified = if pinned(end_entity) {
+            // The handshake signature proves the server holds the pinned key; the name still has to fit
+            ParsedCertificate::try_from(end_entity).and_then(|cert| verify_server_name(&cert, server_name))
+        } else if let Some(anchor) = intermediates.iter().find(|cert| pinned(cert)) {
+            // A pinned issuer is only worth something if it actually issued the leaf, so the chain
+            // and name are verified with it as the sole trust anchor
+            self.verify_chain(anchor, end_entity, intermediates, server_name, now)
+        } else {
+            Err(rustls::Error::General(format!("Certificate pin mismatch for {}", host)))
+        };
+
+        match verified {
+            Ok(()) => Ok(ServerCertVerified::assertion()),
+            Err(e) => {
+                *self.failure.lock().unwrap() = Some(host.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// TLS configuration for a download client enforcing `pins`
+pub fn client_config(pins: &HashMap<String, Vec<String>>, failure: PinFailure) -> Result<rustls::ClientConfig, String> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let verifier = PinnedVerifier {
+        pins: pins
+            .iter()
+            .map(|(host, pins)| (host.trim().to_ascii_lowercase(), pins.clone()))
+            .collect(),
+        provider: provider.clone(),
+        failure,
+    };
+
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Failed to set up TLS: {}", e))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(config)
+}
+
+/// Pins may be written as in HPKP, `sha256/<base64>`
+fn normalize_pin(pin: &str) -> &str {
+    let pin = pin.trim();
+    pin.strip_prefix("sha256/").unwrap_or(pin)
+}
+
+/// Base64 SHA-256 of the certificate's SubjectPublicKeyInfo
+fn spki_sha256(cert: &[u8]) -> Option<String> {
+    let spki = subject_public_key_info(cert)?;
+    Some(base64::engine::general_purpose::STANDARD.encode(Sha256::digest(spki)))
+}
+
+/// The DER SubjectPublicKeyInfo of an X.509 certificate
+fn subject_public_key_info(cert: &[u8]) -> Option<&[u8]> {
+    let (_, certificate, _) = der_element(cert)?;
+    let (_, tbs, _) = der_element(certificate)?;
+
+    // Skip the optional version, then serial, signature, issuer, validity and subject
+    let mut rest = tbs;
+    if rest.first() == Some(&0xa0) {
+        rest = der_element(rest)?.2;
+    }
+    for _ in 0..5 {
+        rest = der_element(rest)?.2;
+    }
+
+    Some(der_element(rest)?.0)
+}
+
+/// Split the DER element at the start of `data` into the whole element, its contents and the rest
+fn der_element(data: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let (_tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (length, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let length = rest[..count].iter().fold(0usize, |acc, &b| (acc << 8) | b as usize);
+        (length, &rest[count..])
+    };
+
+    if rest.len() < length {
+        return None;
+    }
+    let header = data.len() - rest.len();
+    Some((&data[..header + length], &rest[..length], &rest[length..]))
+}
@@ -0,0 +1,162 @@
+/*!
+ * One-step setup for a dashboard instance, from `--dashboard-url` or a `wab2b-helper://configure` link
+ */
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use url::Url;
+
+use crate::permissions::{self, Scope};
+use crate::settings;
+
+/// Command line flag carrying the dashboard URL
+const DASHBOARD_URL_FLAG: &str = "--dashboard-url";
+
+/// Command line flag adding a further host to the download allowlist
+const ALLOW_HOST_FLAG: &str = "--allow-host";
+
+/// Host of the deep link that provisions instead of downloading
+const CONFIGURE_HOST: &str = "configure";
+
+/// Heartbeat endpoint relative to the dashboard URL
+const HEARTBEAT_PATH: &str = "api/helper/heartbeat";
+
+/// Dashboard configuration taken from the launch arguments
+#[derive(Debug, Clone)]
+pub struct Provisioning {
+    pub dashboard_url: Url,
+    /// Hosts allowed in addition to the dashboard itself
+    pub extra_hosts: Vec<String>,
+}
+
+/// Payload of the `dashboard-provisioned` event
+#[derive(Debug, Clone, Serialize)]
+pub struct DashboardProvisioned {
+    pub dashboard_url: String,
+    pub heartbeat_endpoint: String,
+    pub allowed_hosts: Vec<String>,
+}
+
+fn parse_dashboard_url(value: &str) -> Result<Url, String> {
+    let url = Url::parse(value).map_err(|e| format!("Invalid dashboard URL {}: {}", value, e))?;
+    match url.scheme() {
+        "https" | "http" if url.host_str().is_some() => Ok(url),
+        _ => Err(format!("Dashboard URL must be an http(s) URL: {}", value)),
+    }
+}
+
+/// Read `--dashboard-url <url>` / `--dashboard-url=<url>` and `--allow-host <host>` from `args`
+fn from_args(args: &[String]) -> Option<Result<Provisioning, String>> {
+    let mut dashboard_url = None;
+    let mut extra_hosts = Vec::new();
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+
+        match flag {
+            DASHBOARD_URL_FLAG => dashboard_url = inline.or_else(|| iter.next().cloned()),
+            ALLOW_HOST_FLAG => extra_hosts.extend(inline.or_else(|| iter.next().cloned())),
+            _ => {}
+        }
+    }
+
+    let dashboard_url = dashboard_url?;
+    Some(parse_dashboard_url(&dashboard_url).map(|dashboard_url| Provisioning { dashboard_url, extra_hosts }))
+}
+
+/// Read `wab2b-helper://configure?dashboard_url=<url>&allow_host=<host>&token=<token>`.
+///
+/// Any web page can open such a link, so once the helper has been paired the link
+/// has to carry an admin token.
+fn from_deep_link(app_handle: &AppHandle, link: &str) -> Option<Result<Provisioning, String>> {
+    let url = Url::parse(link).ok()?;
+    if url.scheme() != "wab2b-helper" || url.host_str() != Some(CONFIGURE_HOST) {
+        return None;
+    }
+
+    let mut dashboard_url = None;
+    let mut extra_hosts = Vec::new();
+    let mut token = None;
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "dashboard_url" => dashboard_url = Some(value.to_string()),
+            "allow_host" => extra_hosts.push(value.to_string()),
+            "token" => token = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    if permissions::is_paired(app_handle) {
+        if let Err(e) = permissions::authorize(app_handle, token.as_deref(), Scope::Admin) {
+            return Some(Err(e));
+        }
+    }
+
+    let Some(dashboard_url) = dashboard_url else {
+        return Some(Err("Configure link is missing dashboard_url".to_string()));
+    };
+    Some(parse_dashboard_url(&dashboard_url).map(|dashboard_url| Provisioning { dashboard_url, extra_hosts }))
+}
+
+/// Store the dashboard URL, its heartbeat endpoint and its hosts in the settings
+fn apply(app_handle: &AppHandle, provisioning: &Provisioning) -> Result<DashboardProvisioned, String> {
+    let mut settings = settings::load_settings(app_handle).map_err(|e| e.to_string())?;
+
+    // Join relative to the dashboard path, e.g. https://erp.example.com/wab2b/
+    let mut base = provisioning.dashboard_url.clone();
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+    let heartbeat_endpoint = base
+        .join(HEARTBEAT_PATH)
+        .map_err(|e| format!("Failed to build heartbeat endpoint: {}", e))?;
+
+    settings.dashboard_url = provisioning.dashboard_url.to_string();
+    settings.heartbeat.endpoint = heartbeat_endpoint.to_string();
+
+    let hosts = provisioning
+        .dashboard_url
+        .host_str()
+        .map(str::to_string)
+        .into_iter()
+        .chain(provisioning.extra_hosts.iter().map(|h| h.trim().to_ascii_lowercase()));
+    for host in hosts {
+        if !host.is_empty() && !settings.allowed_hosts.contains(&host) {
+            settings.allowed_hosts.push(host);
+        }
+    }
+
+    settings::save_settings(app_handle, &settings).map_err(|e| e.to_string())?;
+
+    Ok(DashboardProvisioned {
+        dashboard_url: settings.dashboard_url,
+        heartbeat_endpoint: settings.heartbeat.endpoint,
+        allowed_hosts: settings.allowed_hosts,
+    })
+}
+
+/// Apply provisioning found in launch arguments. Returns true when the arguments were a
+/// provisioning request, so they must not be treated as a download link.
+pub fn handle_args(app_handle: &AppHandle, args: &[String]) -> bool {
+    let request = from_args(args).or_else(|| args.get(1).and_then(|link| from_deep_link(app_handle, link)));
+    let Some(request) = request else {
+        return false;
+    };
+
+    match request.and_then(|provisioning| apply(app_handle, &provisioning)) {
+        Ok(provisioned) => {
+            println!("Provisioned for dashboard {}", provisioned.dashboard_url);
+            let _ = app_handle.emit("dashboard-provisioned", provisioned);
+        }
+        Err(e) => {
+            eprintln!("Dashboard provisioning failed: {}", e);
+            let _ = app_handle.emit("dashboard-provisioning-failed", e);
+        }
+    }
+
+    true
+}
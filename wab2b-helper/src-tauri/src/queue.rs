@@ -0,0 +1,130 @@
+/*!
+ * Download queue: limits how many downloads transfer at once and starts waiting ones by priority,
+ * then by how soon their link expires
+ */
+
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+/// Downloads transferring at the same time; the rest wait for a slot
+const MAX_ACTIVE: usize = 3;
+
+/// Order in which waiting downloads get a slot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug)]
+struct Waiting {
+    id: String,
+    priority: Priority,
+    /// When the download's signed link stops working, in seconds since the Unix epoch
+    expires_at: Option<u64>,
+    /// Arrival order, so downloads of equal priority start first come, first served
+    seq: u64,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    active: usize,
+    waiting: Vec<Waiting>,
+    next_seq: u64,
+}
+
+impl QueueState {
+    /// ID of the download that gets the next free slot
+    fn next_up(&self) -> Option<&str> {
+        self.waiting
+            .iter()
+            .max_by(|a, b| {
+                a.priority
+                    .cmp(&b.priority)
+                    // Among equals, a link about to expire goes before one with more time or none
+                    .then_with(|| match (a.expires_at, b.expires_at) {
+                        (Some(a), Some(b)) => b.cmp(&a),
+                        (a, b) => a.is_some().cmp(&b.is_some()),
+                    })
+                    .then(b.seq.cmp(&a.seq))
+            })
+            .map(|w| w.id.as_str())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DownloadQueue {
+    state: Mutex<QueueState>,
+    changed: Notify,
+}
+
+/// A download's place in the queue; leaves the queue when dropped, e.g. once cancelled
+struct Ticket<'a> {
+    queue: &'a DownloadQueue,
+    id: &'a str,
+}
+
+impl Drop for Ticket<'_> {
+    fn drop(&mut self) {
+        let mut state = self.queue.state.lock().unwrap();
+        let count = state.waiting.len();
+        state.waiting.retain(|w| w.id != self.id);
+        if state.waiting.len() != count {
+            self.queue.changed.notify_waiters();
+        }
+    }
+}
+
+/// Permission to transfer, released when dropped
+pub struct Slot<'a> {
+    queue: &'a DownloadQueue,
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.queue.state.lock().unwrap().active -= 1;
+        self.queue.changed.notify_waiters();
+    }
+}
+
+impl DownloadQueue {
+    /// Wait until download `id`, whose link expires at `expires_at`, may transfer
+    pub async fn acquire<'a>(&'a self, id: &'a str, priority: Priority, expires_at: Option<u64>) -> Slot<'a> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiting.push(Waiting { id: id.to_string(), priority, expires_at, seq });
+        }
+        let _ticket = Ticket { queue: self, id };
+
+        loop {
+            // Created before checking, so a slot freed in between still wakes us up
+            let changed = self.changed.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.active < MAX_ACTIVE && state.next_up() == Some(id) {
+                    state.waiting.retain(|w| w.id != id);
+                    state.active += 1;
+                    return Slot { queue: self };
+                }
+            }
+            changed.await;
+        }
+    }
+
+    /// Change the priority of a waiting download. Returns false when `id` isn't waiting.
+    pub fn reprioritize(&self, id: &str, priority: Priority) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(waiting) = state.waiting.iter_mut().find(|w| w.id == id) else {
+            return false;
+        };
+        waiting.priority = priority;
+        self.changed.notify_waiters();
+        true
+    }
+}
@@ -0,0 +1,419 @@
+/*!
+ * FTP and SFTP sources for `download_file`, feeding the same write pipeline as HTTP downloads
+ */
+
+use std::io::{Read, Seek, SeekFrom};
+use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use url::Url;
+
+use crate::download::{self, DownloadControl, DownloadJob, DownloadOutcome};
+use crate::settings::{AppSettings, Timeouts};
+use crate::Error;
+
+/// Bytes read from the data connection at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Host keys of SFTP servers missing from the user's `known_hosts`, pinned on first use,
+/// in the app data directory
+const PINNED_HOSTS_FILE: &str = "sftp_known_hosts";
+
+/// Whether `url` is served by this module rather than over HTTP
+pub fn is_remote(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "ftp" | "sftp"))
+}
+
+fn transfer_error(message: impl Into<String>) -> Error {
+    Error::Transfer(message.into())
+}
+
+/// Percent-decode a user, password or path from the URL. Line breaks and NUL are refused:
+/// they end up in FTP commands, where they would smuggle in commands of their own.
+fn decode(value: &str) -> Result<String, Error> {
+    let decoded = String::from_utf8_lossy(&crate::data_url::percent_decode(value)).to_string();
+    if decoded.contains(['\r', '\n', '\0']) {
+        return Err(Error::InvalidUrl("line breaks or NUL in the user, password or path".to_string()));
+    }
+    Ok(decoded)
+}
+
+/// Download `job.url` over FTP or SFTP, resuming from `job.downloaded` when there is a partial file
+pub async fn run(
+    app_handle: &AppHandle,
+    job: &mut DownloadJob,
+    control: &DownloadControl,
+    settings: &AppSettings,
+) -> Result<DownloadOutcome, Error> {
+    let url = Url::parse(&job.url).map_err(|_| Error::InvalidUrl(job.url.clone()))?;
+    if url.host_str().is_none() {
+        return Err(Error::InvalidUrl(job.url.clone()));
+    }
+
+    match url.scheme() {
+        "ftp" => run_ftp(app_handle, job, control, settings, &url).await,
+        _ => run_sftp(app_handle, job, control, settings, &url).await,
+    }
+}
+
+/// Apply what the server reported and checkpoint, as HTTP downloads do once headers arrive
+async fn prepare(
+    app_handle: &AppHandle,
+    job: &mut DownloadJob,
+    settings: &AppSettings,
+    size: Option<u64>,
+    append: bool,
+) -> Result<(), Error> {
+    if !append {
+        job.downloaded = 0;
+        job.hasher = None;
+    }
+    job.total = size.unwrap_or(0);
+    // Both protocols can continue from an offset
+    job.supports_range = true;
+    job.segmented = false;
+
+    let max_size = settings.max_file_size_mb.saturating_mul(1024 * 1024);
+    if max_size > 0 && job.total > max_size {
+        return Err(download::reject_too_large(job, job.total, settings.max_file_size_mb).await);
+    }
+
+    crate::pending::save(app_handle, job, true);
+    Ok(())
+}
+
+/// Control connection of an FTP session
+struct FtpControl {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    read_timeout: Duration,
+}
+
+impl FtpControl {
+    /// Read one reply, joining the lines of a multi-line reply
+    async fn reply(&mut self) -> Result<(u16, String), Error> {
+        let mut text = String::new();
+        let mut code = None;
+
+        loop {
+            let mut line = String::new();
+            let read = tokio::time::timeout(self.read_timeout, self.reader.read_line(&mut line))
+                .await
+                .map_err(|_| Error::Timeout("FTP server did not reply".to_string()))??;
+            if read == 0 {
+                return Err(transfer_error("FTP server closed the connection"));
+            }
+            text.push_str(&line);
+
+            // A multi-line reply starts with `123-` and ends with `123 `
+            let line_code = line.get(..3).and_then(|c| c.parse::<u16>().ok());
+            let finished = line.as_bytes().get(3) != Some(&b'-');
+            match (code, line_code) {
+                (None, Some(c)) if finished => return Ok((c, text.trim_end().to_string())),
+                (None, Some(c)) => code = Some(c),
+                (None, None) => return Err(transfer_error(format!("Malformed FTP reply: {}", line.trim_end()))),
+                (Some(c), Some(lc)) if c == lc && finished => return Ok((c, text.trim_end().to_string())),
+                _ => {}
+            }
+        }
+    }
+
+    /// Send `command` and return the reply, failing unless its code is one of `expected`
+    async fn expect(&mut self, command: &str, expected: &[u16]) -> Result<String, Error> {
+        self.writer.write_all(format!("{}\r\n", command).as_bytes()).await?;
+        let (code, text) = self.reply().await?;
+        if expected.contains(&code) {
+            return Ok(text);
+        }
+
+        // Only the verb: the argument of PASS is a secret
+        let verb = command.split(' ').next().unwrap_or(command);
+        Err(transfer_error(format!("FTP {} failed: {}", verb, text)))
+    }
+}
+
+/// Port of a passive data connection from an EPSV (`(|||port|)`) or PASV (`(h,h,h,h,p,p)`) reply
+fn passive_port(reply: &str) -> Option<u16> {
+    let (start, end) = (reply.find('(')? + 1, reply.rfind(')')?);
+    // A reply like `227 )(` has them the wrong way round
+    if start > end {
+        return None;
+    }
+    let inner = &reply[start..end];
+    if let Some(port) = inner.strip_prefix("|||").and_then(|rest| rest.strip_suffix('|')) {
+        return port.parse().ok();
+    }
+
+    let numbers: Vec<u16> = inner.split(',').map(|n| n.trim().parse().ok()).collect::<Option<_>>()?;
+    match numbers[..] {
+        [_, _, _, _, high, low] if high < 256 && low < 256 => Some(high * 256 + low),
+        _ => None,
+    }
+}
+
+async fn run_ftp(
+    app_handle: &AppHandle,
+    job: &mut DownloadJob,
+    control: &DownloadControl,
+    settings: &AppSettings,
+    url: &Url,
+) -> Result<DownloadOutcome, Error> {
+    let timeouts = &settings.timeouts;
+    let connect_timeout = Duration::from_secs(timeouts.connect_secs.max(1));
+    let read_timeout = Duration::from_secs(timeouts.read_secs.max(1));
+    let host = url.host_str().unwrap_or_default();
+    let port = url.port().unwrap_or(21);
+
+    println!("Connecting to FTP server {}:{}", host, port);
+    let addresses = crate::network::resolve(settings, host, port).await?;
+    let stream = tokio::time::timeout(connect_timeout, TcpStream::connect(&addresses[..]))
+        .await
+        .map_err(|_| Error::Timeout(format!("Connecting to {}", host)))??;
+    let server = stream.peer_addr()?.ip();
+    let (reader, writer) = stream.into_split();
+    let mut ftp = FtpControl { reader: BufReader::new(reader), writer, read_timeout };
+
+    let (code, text) = ftp.reply().await?;
+    if code != 220 {
+        return Err(transfer_error(format!("FTP server refused the connection: {}", text)));
+    }
+
+    let user = match url.username() {
+        "" => "anonymous".to_string(),
+        user => decode(user)?,
+    };
+    let password = url.password().map(decode).transpose()?.unwrap_or_else(|| "anonymous@".to_string());
+    let (code, text) = {
+        ftp.writer.write_all(format!("USER {}\r\n", user).as_bytes()).await?;
+        ftp.reply().await?
+    };
+    match code {
+        230 => {}
+        331 | 332 => {
+            ftp.expect(&format!("PASS {}", password), &[230, 202]).await?;
+        }
+        _ => return Err(transfer_error(format!("FTP USER failed: {}", text))),
+    }
+
+    ftp.expect("TYPE I", &[200]).await?;
+    let path = decode(url.path())?;
+
+    // SIZE is an extension; without it the total stays unknown
+    let size = ftp
+        .expect(&format!("SIZE {}", path), &[213])
+        .await
+        .ok()
+        .and_then(|text| text.get(4..).and_then(|size| size.trim().parse::<u64>().ok()));
+
+    // The address in a PASV reply is ignored: behind NAT it is often wrong, and it must
+    // not be able to point the helper at another machine
+    let port = match ftp.expect("EPSV", &[229]).await {
+        Ok(text) => passive_port(&text),
+        Err(_) => passive_port(&ftp.expect("PASV", &[227]).await?),
+    }
+    .ok_or_else(|| transfer_error("FTP server sent an unusable passive mode reply"))?;
+    let data = tokio::time::timeout(connect_timeout, TcpStream::connect((server, port)))
+        .await
+        .map_err(|_| Error::Timeout(format!("Opening FTP data connection to {}", host)))??;
+
+    let append = job.downloaded > 0 && ftp.expect(&format!("REST {}", job.downloaded), &[350]).await.is_ok();
+    if append {
+        println!("Resuming {} from byte {}", job.id, job.downloaded);
+    }
+    ftp.expect(&format!("RETR {}", path), &[125, 150]).await?;
+
+    prepare(app_handle, job, settings, size, append).await?;
+
+    let chunks = futures_util::stream::unfold(data, move |mut data| async move {
+        let mut chunk = vec![0u8; CHUNK_SIZE];
+        match tokio::time::timeout(read_timeout, data.read(&mut chunk)).await {
+            Ok(Ok(0)) => None,
+            Ok(Ok(read)) => {
+                chunk.truncate(read);
+                Some((Ok(chunk), data))
+            }
+            Ok(Err(e)) => Some((Err(Error::IoError(e)), data)),
+            Err(_) => Some((Err(Error::Timeout("FTP transfer stalled".to_string())), data)),
+        }
+    });
+    let outcome = download::write_stream(app_handle, job, control, settings, std::pin::pin!(chunks), append).await?;
+
+    // The data connection closing early looks like the end of the file; only 226 confirms it
+    if let DownloadOutcome::Completed = outcome {
+        let (code, text) = ftp.reply().await?;
+        if code != 226 && code != 250 {
+            return Err(transfer_error(format!("FTP transfer did not complete: {}", text)));
+        }
+        let _ = ftp.expect("QUIT", &[221]).await;
+    }
+
+    Ok(outcome)
+}
+
+/// What the SFTP worker thread hands to the async side
+enum SftpMessage {
+    Opened { size: Option<u64> },
+    Data(Vec<u8>),
+}
+
+/// Compare the server's host key with the user's `known_hosts` and the keys pinned in
+/// `pinned_hosts`. A host in neither is pinned on first use, like `ssh -o StrictHostKeyChecking=accept-new`,
+/// so credentials only ever go to the key seen the first time.
+fn check_host_key(session: &ssh2::Session, host: &str, port: u16, pinned_hosts: &Path) -> Result<(), Error> {
+    let (key, key_type) = session
+        .host_key()
+        .ok_or_else(|| transfer_error("SFTP server sent no host key"))?;
+
+    // Either file may not exist yet
+    let mut known_hosts = session.known_hosts().map_err(sftp_error)?;
+    if let Some(home) = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")) {
+        let _ = known_hosts.read_file(&Path::new(&home).join(".ssh").join("known_hosts"), ssh2::KnownHostFileKind::OpenSSH);
+    }
+    let _ = known_hosts.read_file(pinned_hosts, ssh2::KnownHostFileKind::OpenSSH);
+
+    match known_hosts.check_port(host, port, key) {
+        ssh2::CheckResult::Match => Ok(()),
+        ssh2::CheckResult::NotFound => {
+            let mut pinned = session.known_hosts().map_err(sftp_error)?;
+            let _ = pinned.read_file(pinned_hosts, ssh2::KnownHostFileKind::OpenSSH);
+            let name = if port == 22 { host.to_string() } else { format!("[{}]:{}", host, port) };
+            pinned
+                .add(&name, key, "pinned by wab2b-helper", key_type.into())
+                .and_then(|()| pinned.write_file(pinned_hosts, ssh2::KnownHostFileKind::OpenSSH))
+                .map_err(|e| transfer_error(format!("Could not pin the host key of {}: {}", host, e)))?;
+            println!("Pinned the SFTP host key of {}", name);
+            Ok(())
+        }
+        ssh2::CheckResult::Mismatch => Err(transfer_error(format!(
+            "SFTP host key of {} does not match the known one",
+            host
+        ))),
+        ssh2::CheckResult::Failure => Err(transfer_error(format!("Could not verify the host key of {}", host))),
+    }
+}
+
+fn pinned_hosts_path(app_handle: &AppHandle) -> Result<PathBuf, Error> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| transfer_error(format!("Failed to get app data directory: {}", e)))?;
+    std::fs::create_dir_all(&app_dir)?;
+
+    Ok(app_dir.join(PINNED_HOSTS_FILE))
+}
+
+fn sftp_error(e: ssh2::Error) -> Error {
+    transfer_error(format!("SFTP: {}", e))
+}
+
+/// Open `path` on the server, starting at `offset`
+fn open_sftp_file(
+    url: &Url,
+    timeouts: &Timeouts,
+    allowed_private_hosts: &[String],
+    pinned_hosts: &Path,
+    offset: u64,
+) -> Result<(ssh2::Session, ssh2::File, Option<u64>), Error> {
+    let host = url.host_str().unwrap_or_default();
+    let port = url.port().unwrap_or(22);
+    let addresses = (host, port).to_socket_addrs()?.collect();
+    let address = crate::network::filter_addresses(allowed_private_hosts, host, addresses)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| transfer_error(format!("Could not resolve {}", host)))?;
+
+    let tcp = std::net::TcpStream::connect_timeout(&address, Duration::from_secs(timeouts.connect_secs.max(1)))?;
+    let mut session = ssh2::Session::new().map_err(sftp_error)?;
+    session.set_tcp_stream(tcp);
+    session.set_timeout((timeouts.read_secs.max(1) * 1000) as u32);
+    session.handshake().map_err(sftp_error)?;
+    check_host_key(&session, host, port, pinned_hosts)?;
+
+    let user = match url.username() {
+        "" => std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_default(),
+        user => decode(user)?,
+    };
+    match url.password() {
+        Some(password) => session.userauth_password(&user, &decode(password)?),
+        None => session.userauth_agent(&user),
+    }
+    .map_err(sftp_error)?;
+    if !session.authenticated() {
+        return Err(transfer_error(format!("SFTP login to {} failed", host)));
+    }
+
+    let sftp = session.sftp().map_err(sftp_error)?;
+    let mut file = sftp.open(Path::new(&decode(url.path())?)).map_err(sftp_error)?;
+    let size = file.stat().ok().and_then(|stat| stat.size);
+    if offset > 0 {
+        file.seek(SeekFrom::Start(offset))?;
+    }
+
+    Ok((session, file, size))
+}
+
+async fn run_sftp(
+    app_handle: &AppHandle,
+    job: &mut DownloadJob,
+    control: &DownloadControl,
+    settings: &AppSettings,
+    url: &Url,
+) -> Result<DownloadOutcome, Error> {
+    // libssh2 is blocking, so it runs on a worker thread that stops once nobody listens
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<Result<SftpMessage, Error>>(8);
+    let worker_url = url.clone();
+    let timeouts = settings.timeouts.clone();
+    let allowed_private_hosts = settings.allowed_private_hosts.clone();
+    let pinned_hosts = pinned_hosts_path(app_handle)?;
+    let offset = job.downloaded;
+    tokio::task::spawn_blocking(move || {
+        let result = open_sftp_file(&worker_url, &timeouts, &allowed_private_hosts, &pinned_hosts, offset).and_then(|(_session, mut file, size)| {
+            if sender.blocking_send(Ok(SftpMessage::Opened { size })).is_err() {
+                return Ok(());
+            }
+            loop {
+                let mut chunk = vec![0u8; CHUNK_SIZE];
+                let read = file.read(&mut chunk)?;
+                if read == 0 {
+                    return Ok(());
+                }
+                chunk.truncate(read);
+                if sender.blocking_send(Ok(SftpMessage::Data(chunk))).is_err() {
+                    return Ok(());
+                }
+            }
+        });
+        if let Err(e) = result {
+            let _ = sender.blocking_send(Err(e));
+        }
+    });
+
+    println!("Connecting to SFTP server {}", url.host_str().unwrap_or_default());
+    let size = tokio::select! {
+        message = receiver.recv() => match message {
+            Some(Ok(SftpMessage::Opened { size })) => size,
+            Some(Err(e)) => return Err(e),
+            _ => return Err(transfer_error("SFTP worker stopped unexpectedly")),
+        },
+        _ = control.cancelled() => return Ok(DownloadOutcome::Cancelled),
+    };
+
+    let append = offset > 0;
+    if append {
+        println!("Resuming {} from byte {}", job.id, offset);
+    }
+    prepare(app_handle, job, settings, size, append).await?;
+
+    let chunks = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        match receiver.recv().await? {
+            Ok(SftpMessage::Data(chunk)) => Some((Ok(chunk), receiver)),
+            Ok(SftpMessage::Opened { .. }) => Some((Ok(Vec::new()), receiver)),
+            Err(e) => Some((Err(e), receiver)),
+        }
+    });
+    download::write_stream(app_handle, job, control, settings, std::pin::pin!(chunks), append).await
+}
@@ -0,0 +1,178 @@
+/*!
+ * Downloads deferred until a given time, e.g. large media prefetched overnight
+ */
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use url::Url;
+
+use crate::history::now_secs;
+use crate::queue::Priority;
+
+/// Name of the schedule in the app data directory
+const SCHEDULE_FILE: &str = "scheduled-downloads.json";
+
+/// How often the background task looks for downloads that are due
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Serializes access to the schedule
+static SCHEDULE_LOCK: Mutex<()> = Mutex::new(());
+
+/// A download waiting for its time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledDownload {
+    pub id: String,
+    pub url: String,
+    /// Earliest start, in seconds since the Unix epoch
+    pub not_before: u64,
+    #[serde(default)]
+    pub priority: Priority,
+    /// Seconds since the Unix epoch
+    pub scheduled_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Schedule {
+    downloads: Vec<ScheduledDownload>,
+}
+
+fn schedule_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    Ok(app_dir.join(SCHEDULE_FILE))
+}
+
+fn load(app: &AppHandle) -> Result<Schedule, String> {
+    let path = schedule_path(app)?;
+    if !path.exists() {
+        return Ok(Schedule::default());
+    }
+
+    let json = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read scheduled downloads: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse scheduled downloads: {}", e))
+}
+
+fn store(app: &AppHandle, schedule: &Schedule) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(schedule)
+        .map_err(|e| format!("Failed to serialize scheduled downloads: {}", e))?;
+
+    fs::write(schedule_path(app)?, json)
+        .map_err(|e| format!("Failed to write scheduled downloads: {}", e))
+}
+
+/// Remove and return the downloads whose time has come
+fn take_due(app: &AppHandle) -> Result<Vec<ScheduledDownload>, String> {
+    let _guard = SCHEDULE_LOCK.lock().unwrap();
+    let mut schedule = load(app)?;
+
+    let now = now_secs();
+    let (due, waiting): (Vec<_>, Vec<_>) = schedule
+        .downloads
+        .into_iter()
+        .partition(|d| d.not_before <= now);
+    if due.is_empty() {
+        return Ok(due);
+    }
+
+    schedule.downloads = waiting;
+    store(app, &schedule)?;
+    Ok(due)
+}
+
+/// Start the background task that runs scheduled downloads once they are due.
+/// Downloads that came due while the app was closed start right away.
+pub fn start(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match take_due(&app_handle) {
+                Ok(due) => {
+                    for download in due {
+                        println!("Starting scheduled download {} for {}", download.id, download.url);
+                        let app_handle = app_handle.clone();
+                        tauri::async_runtime::spawn(async move {
+                            let result = crate::start_download(
+                                app_handle,
+                                download.url.clone(),
+                                None,
+                                None,
+                                None,
+                                None,
+                                None,
+                                Some(download.priority),
+                            )
+                            .await;
+                            if let Err(e) = result {
+                                println!("Scheduled download {} failed: {}", download.id, e);
+                            }
+                        });
+                    }
+                }
+                Err(e) => println!("Failed to check scheduled downloads: {}", e),
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+// Command to download a file at a later time
+#[tauri::command]
+pub fn schedule_download(
+    app_handle: AppHandle,
+    webview: tauri::Webview,
+    url: String,
+    when: u64,
+    priority: Option<Priority>,
+    token: Option<String>,
+) -> Result<ScheduledDownload, String> {
+    crate::permissions::authorize_command(&webview, token.as_deref(), crate::permissions::Scope::Download)?;
+    Url::parse(&url).map_err(|_| format!("Invalid URL: {}", url))?;
+
+    let now = now_secs();
+    let download = ScheduledDownload {
+        id: format!("scheduled_{}_{:08x}", now, rand::random::<u32>()),
+        url,
+        not_before: when,
+        priority: priority.unwrap_or_default(),
+        scheduled_at: now,
+    };
+
+    let _guard = SCHEDULE_LOCK.lock().unwrap();
+    let mut schedule = load(&app_handle)?;
+    schedule.downloads.push(download.clone());
+    store(&app_handle, &schedule)?;
+
+    Ok(download)
+}
+
+// Command to list the downloads that haven't started yet
+#[tauri::command]
+pub fn get_scheduled_downloads(app_handle: AppHandle) -> Result<Vec<ScheduledDownload>, String> {
+    let _guard = SCHEDULE_LOCK.lock().unwrap();
+    Ok(load(&app_handle)?.downloads)
+}
+
+// Command to drop a scheduled download before it starts
+#[tauri::command]
+pub fn cancel_scheduled_download(app_handle: AppHandle, id: String) -> Result<(), String> {
+    let _guard = SCHEDULE_LOCK.lock().unwrap();
+    let mut schedule = load(&app_handle)?;
+
+    let count = schedule.downloads.len();
+    schedule.downloads.retain(|d| d.id != id);
+    if schedule.downloads.len() == count {
+        return Err(format!("Scheduled download not found: {}", id));
+    }
+    store(&app_handle, &schedule)
+}
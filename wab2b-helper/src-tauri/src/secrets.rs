@@ -0,0 +1,80 @@
+/*!
+ * Secrets kept apart from the settings file, which the webview can read back
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+/// Name of the secrets store in the app data directory
+const SECRETS_FILE: &str = "secrets.json";
+
+/// Shared secret signing outbound webhooks
+pub const WEBHOOK_SECRET: &str = "webhook_secret";
+
+/// GitHub token for update checks and downloads
+pub const GITHUB_TOKEN: &str = "github_token";
+
+/// GitLab or Gitea token of the release source
+pub const RELEASE_SOURCE_TOKEN: &str = "release_source_token";
+
+/// Bearer token for heartbeats
+pub const HEARTBEAT_TOKEN: &str = "heartbeat_token";
+
+/// Serializes access to the secrets store
+static SECRETS_LOCK: Mutex<()> = Mutex::new(());
+
+fn secrets_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    Ok(app_dir.join(SECRETS_FILE))
+}
+
+fn load_all(app: &AppHandle) -> Result<HashMap<String, String>, String> {
+    let path = secrets_path(app)?;
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let json = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read secrets: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse secrets: {}", e))
+}
+
+/// The secret stored as `name`, if there is one
+pub fn get(app: &AppHandle, name: &str) -> Result<Option<String>, String> {
+    let _guard = SECRETS_LOCK.lock().unwrap();
+    Ok(load_all(app)?.remove(name).filter(|secret| !secret.is_empty()))
+}
+
+/// Store `secret` as `name`, replacing the previous one
+pub fn set(app: &AppHandle, name: &str, secret: &str) -> Result<(), String> {
+    let _guard = SECRETS_LOCK.lock().unwrap();
+    let mut secrets = load_all(app)?;
+    secrets.insert(name.to_string(), secret.to_string());
+
+    let json = serde_json::to_string_pretty(&secrets)
+        .map_err(|e| format!("Failed to serialize secrets: {}", e))?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Readable by the user only
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(secrets_path(app)?)
+        .and_then(|mut file| file.write_all(json.as_bytes()))
+        .map_err(|e| format!("Failed to write secrets: {}", e))
+}
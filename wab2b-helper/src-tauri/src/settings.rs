@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
@@ -6,9 +7,61 @@ use tauri::Manager;
 use anyhow::Result;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppSettings {
     pub auto_update: bool,
     pub beta_mode: bool,
+    pub update_backups: BackupRetention,
+    pub update_checks: UpdateCheckSchedule,
+    /// Release the user chose to skip; only newer releases are announced
+    pub skipped_version: Option<String>,
+    /// GitHub token for update checks and downloads, to get past the anonymous rate limit;
+    /// `WAB2B_GITHUB_TOKEN` in the environment is used when none is stored. Kept in the secrets
+    /// store like the webhook secret.
+    #[serde(skip_serializing)]
+    pub github_token: String,
+    /// HTTPS URL of a self-hosted update manifest used instead of GitHub releases; empty for GitHub
+    pub update_manifest_url: String,
+    pub release_source: ReleaseSource,
+    /// Case-insensitive regular expression naming the update asset to install, e.g. `arm64.*\.msi$`,
+    /// for machines the platform detection gets wrong; empty to let the updater choose
+    pub update_asset_pattern: String,
+    /// Random bucket from 0 to 99 this install falls in for staged rollouts, picked by the updater
+    /// on first use
+    pub update_rollout_bucket: Option<u8>,
+    /// How much of the MSI or NSIS installer the user sees during an update
+    pub installer_ui: InstallerUi,
+    /// Download updates in the background and install them when the helper exits, instead of
+    /// announcing them
+    pub install_updates_on_exit: bool,
+    pub download_retry: RetryPolicy,
+    /// Download bandwidth cap in kilobits per second, 0 for unlimited
+    pub max_download_speed_kbps: u64,
+    /// Largest file a download may produce in megabytes, 0 for unlimited
+    pub max_file_size_mb: u64,
+    pub segmented_download: SegmentedDownload,
+    /// Redirects a download may follow before it fails
+    pub max_redirects: u32,
+    pub timeouts: Timeouts,
+    pub progress_events: ProgressEvents,
+    /// Base64 SHA-256 hashes of the public keys each host may present, by host or `*.domain`
+    pub certificate_pins: HashMap<String, Vec<String>>,
+    pub heartbeat: HeartbeatSettings,
+    /// Base URL of the dashboard instance this helper is set up for
+    pub dashboard_url: String,
+    /// Hosts attachments may be downloaded from, `*.domain` for all subdomains; empty allows any host
+    pub allowed_hosts: Vec<String>,
+    /// Hosts that may resolve to private, loopback or link-local addresses, `*` for all of them
+    pub allowed_private_hosts: Vec<String>,
+    pub webhook: WebhookSettings,
+    pub shared_folder_sync: SharedFolderSync,
+    /// Developer option degrading downloads on purpose
+    pub network_simulation: NetworkSimulation,
+    /// Folder completed downloads are kept in; empty for a temporary folder cleared on exit
+    pub download_dir: String,
+    /// Send deleted files to the recycle bin instead of removing them for good
+    pub delete_to_trash: bool,
+    pub clipboard: ClipboardSettings,
 }
 
 impl Default for AppSettings {
@@ -16,6 +69,290 @@ impl Default for AppSettings {
         Self {
             auto_update: true,
             beta_mode: false,
+            update_backups: BackupRetention::default(),
+            update_checks: UpdateCheckSchedule::default(),
+            skipped_version: None,
+            github_token: String::new(),
+            update_manifest_url: String::new(),
+            release_source: ReleaseSource::default(),
+            update_asset_pattern: String::new(),
+            update_rollout_bucket: None,
+            installer_ui: InstallerUi::default(),
+            install_updates_on_exit: false,
+            download_retry: RetryPolicy::default(),
+            max_download_speed_kbps: 0,
+            max_file_size_mb: 0,
+            segmented_download: SegmentedDownload::default(),
+            max_redirects: 10,
+            timeouts: Timeouts::default(),
+            progress_events: ProgressEvents::default(),
+            certificate_pins: HashMap::new(),
+            heartbeat: HeartbeatSettings::default(),
+            dashboard_url: String::new(),
+            allowed_hosts: Vec::new(),
+            allowed_private_hosts: Vec::new(),
+            webhook: WebhookSettings::default(),
+            shared_folder_sync: SharedFolderSync::default(),
+            network_simulation: NetworkSimulation::default(),
+            download_dir: String::new(),
+            delete_to_trash: true,
+            clipboard: ClipboardSettings::default(),
+        }
+    }
+}
+
+/// Artificial network conditions for exercising progress, retry and error handling
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSimulation {
+    pub enabled: bool,
+    /// Delay added before every request in milliseconds
+    pub latency_ms: u64,
+    /// Bandwidth cap in kilobits per second, 0 for none
+    pub bandwidth_kbps: u64,
+    /// Share of requests that fail with a 503 (0.0 - 1.0)
+    pub failure_rate: f64,
+}
+
+/// Parallel range requests for large downloads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SegmentedDownload {
+    pub enabled: bool,
+    /// Files smaller than this many megabytes use a single connection
+    pub min_size_mb: u64,
+    /// Number of ranges fetched in parallel
+    pub connections: u32,
+}
+
+impl Default for SegmentedDownload {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_size_mb: 50,
+            connections: 4,
+        }
+    }
+}
+
+/// How often a running download reports its progress to the webview
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProgressEvents {
+    /// Milliseconds between two progress events
+    pub interval_ms: u64,
+    /// Percentage points of progress that trigger an event before the interval is up
+    pub percent_step: f64,
+}
+
+impl Default for ProgressEvents {
+    fn default() -> Self {
+        Self {
+            interval_ms: 100,
+            percent_step: 1.0,
+        }
+    }
+}
+
+/// Limits after which a download gives up on an unresponsive server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Timeouts {
+    /// Seconds to wait for the connection to be established
+    pub connect_secs: u64,
+    /// Seconds without receiving any data before a transfer is considered stalled
+    pub read_secs: u64,
+    /// Seconds a whole request may take, 0 for no limit
+    pub overall_secs: u64,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect_secs: 15,
+            read_secs: 30,
+            overall_secs: 0,
+        }
+    }
+}
+
+/// Handling of what the helper copies to the clipboard
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardSettings {
+    /// Seconds after which a copy made by the helper is cleared again, 0 to leave it
+    pub auto_clear_secs: u64,
+    /// Keep what was on the clipboard before a copy, to put it back with `restore_clipboard`
+    pub preserve_previous: bool,
+    /// Seconds after which the preserved contents are restored on their own, 0 to wait for `restore_clipboard`
+    pub restore_after_secs: u64,
+}
+
+/// Mirroring of cached files into a shared network folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SharedFolderSync {
+    pub enabled: bool,
+    pub folder: String,
+    /// Conversations to mirror; with no conversations and no tags every file is mirrored
+    pub conversations: Vec<String>,
+    /// Tags to mirror
+    pub tags: Vec<String>,
+    /// Seconds between two background passes
+    pub interval_secs: u64,
+}
+
+impl Default for SharedFolderSync {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            folder: String::new(),
+            conversations: Vec::new(),
+            tags: Vec::new(),
+            interval_secs: 300,
+        }
+    }
+}
+
+/// Outbound webhook for download job events
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookSettings {
+    pub enabled: bool,
+    pub url: String,
+    /// Shared secret for the `X-Wab2b-Signature` HMAC. Accepted when saving, but kept in the
+    /// secrets store and never written to the settings file or sent back to the webview.
+    #[serde(skip_serializing)]
+    pub secret: String,
+}
+
+/// Background update checks while `auto_update` is on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateCheckSchedule {
+    /// Hours between two checks, 0 to only check at startup
+    pub interval_hours: u64,
+    /// Local hour (0-23) from which no checks are made
+    pub quiet_hours_start: u8,
+    /// Local hour (0-23) at which checks resume; the same as the start for no quiet hours
+    pub quiet_hours_end: u8,
+}
+
+impl Default for UpdateCheckSchedule {
+    fn default() -> Self {
+        Self {
+            interval_hours: 6,
+            quiet_hours_start: 0,
+            quiet_hours_end: 0,
+        }
+    }
+}
+
+/// Server the updater looks for releases on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseProviderKind {
+    #[default]
+    Github,
+    Gitlab,
+    Gitea,
+}
+
+/// User interface of the installer run for an update
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallerUi {
+    /// No window at all
+    Silent,
+    /// Progress only, no questions asked
+    #[default]
+    Passive,
+    /// The full installer wizard
+    Interactive,
+}
+
+/// Where releases are published when no update manifest is set
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReleaseSource {
+    pub provider: ReleaseProviderKind,
+    /// Server URL for GitLab or Gitea; gitlab.com when empty for GitLab
+    pub base_url: String,
+    /// `owner/repo` on that server, empty for the repository the update check names
+    pub project: String,
+    /// Access token for GitLab or Gitea; GitHub uses `github_token`. Kept in the secrets store.
+    #[serde(skip_serializing)]
+    pub token: String,
+}
+
+/// How many backups of earlier versions the updater keeps
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupRetention {
+    /// Newest backups kept, 0 for no limit
+    pub keep_count: usize,
+    /// Days a backup is kept, 0 for no limit
+    pub keep_days: u64,
+}
+
+impl Default for BackupRetention {
+    fn default() -> Self {
+        Self {
+            keep_count: 3,
+            keep_days: 0,
+        }
+    }
+}
+
+/// Presence reporting to the dashboard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatSettings {
+    pub enabled: bool,
+    /// URL the heartbeat is POSTed to
+    pub endpoint: String,
+    /// Token sent as `Authorization: Bearer`. Kept in the secrets store.
+    #[serde(skip_serializing)]
+    pub token: String,
+    /// Seconds between two heartbeats
+    pub interval_secs: u64,
+}
+
+impl Default for HeartbeatSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            token: String::new(),
+            interval_secs: 60,
+        }
+    }
+}
+
+/// Retry behaviour for attachment downloads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry in milliseconds, doubled on every further retry
+    pub initial_backoff_ms: u64,
+    /// Upper bound for the delay between two attempts in milliseconds
+    pub max_backoff_ms: u64,
+    /// Random spread applied to each delay, as a fraction of it (0.0 - 1.0)
+    pub jitter: f64,
+    /// HTTP status codes that are worth retrying
+    pub retry_on_status: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 30_000,
+            jitter: 0.2,
+            retry_on_status: vec![408, 429, 500, 502, 503, 504],
         }
     }
 }
@@ -32,6 +369,17 @@ pub fn get_settings_path(app_handle: &AppHandle) -> PathBuf {
     app_dir.join("settings.json")
 }
 
+/// Settings fields holding secrets, with their names in the secrets store. They are accepted
+/// when saving, but never written to the settings file or sent back to the webview.
+fn secret_fields(settings: &mut AppSettings) -> [(&mut String, &'static str); 4] {
+    [
+        (&mut settings.webhook.secret, crate::secrets::WEBHOOK_SECRET),
+        (&mut settings.github_token, crate::secrets::GITHUB_TOKEN),
+        (&mut settings.release_source.token, crate::secrets::RELEASE_SOURCE_TOKEN),
+        (&mut settings.heartbeat.token, crate::secrets::HEARTBEAT_TOKEN),
+    ]
+}
+
 pub fn load_settings(app_handle: &AppHandle) -> Result<AppSettings> {
     let settings_path = get_settings_path(app_handle);
     
@@ -43,8 +391,20 @@ pub fn load_settings(app_handle: &AppHandle) -> Result<AppSettings> {
     }
     
     // Read and parse the settings file
-    let settings_json = fs::read_to_string(settings_path)?;
-    let settings: AppSettings = serde_json::from_str(&settings_json)?;
+    let settings_json = fs::read_to_string(&settings_path)?;
+    let mut settings: AppSettings = serde_json::from_str(&settings_json)?;
+    
+    // Older versions kept the secrets in here
+    let mut migrated = false;
+    for (value, name) in secret_fields(&mut settings) {
+        if !value.is_empty() {
+            crate::secrets::set(app_handle, name, &std::mem::take(value)).map_err(anyhow::Error::msg)?;
+            migrated = true;
+        }
+    }
+    if migrated {
+        save_settings_to_path(&settings, &settings_path)?;
+    }
     println!("Loaded settings: beta_mode = {}", settings.beta_mode);
     
     Ok(settings)
@@ -69,6 +429,27 @@ pub fn get_settings(app_handle: AppHandle) -> Result<AppSettings, String> {
 
 // Tauri command to save settings
 #[tauri::command]
-pub fn update_settings(app_handle: AppHandle, settings: AppSettings) -> Result<(), String> {
+pub fn update_settings(
+    app_handle: AppHandle,
+    webview: tauri::Webview,
+    mut settings: AppSettings,
+    token: Option<String>,
+) -> Result<(), String> {
+    crate::permissions::authorize_command(&webview, token.as_deref(), crate::permissions::Scope::Admin)?;
+    // A broken pattern would quietly stop all updates
+    if !settings.update_asset_pattern.is_empty() {
+        regex::Regex::new(&settings.update_asset_pattern)
+            .map_err(|e| format!("Invalid update asset pattern: {}", e))?;
+    }
+    // The rollout bucket belongs to the updater; a frontend that doesn't know it mustn't reroll it
+    if settings.update_rollout_bucket.is_none() {
+        settings.update_rollout_bucket = load_settings(&app_handle).ok().and_then(|s| s.update_rollout_bucket);
+    }
+    // An empty secret means the webview didn't change it, as it never sees the stored one
+    for (value, name) in secret_fields(&mut settings) {
+        if !value.is_empty() {
+            crate::secrets::set(&app_handle, name, value)?;
+        }
+    }
     save_settings(&app_handle, &settings).map_err(|e| e.to_string())
 }
\ No newline at end of file
@@ -0,0 +1,112 @@
+/*!
+ * Cloud drive share links (Google Drive, OneDrive, SharePoint) turned into direct download URLs
+ */
+
+use base64::Engine;
+use regex::Regex;
+use reqwest::header::CONTENT_TYPE;
+use url::Url;
+
+use crate::settings::AppSettings;
+use crate::{download, Error};
+
+/// Direct download URL for a share link, or None when `url` isn't one.
+///
+/// Only rewrites the URL; large Google Drive files additionally need [`confirm_drive_download`].
+pub fn direct_url(url: &Url) -> Option<String> {
+    let host = url.host_str()?.to_ascii_lowercase();
+
+    match host.as_str() {
+        "drive.google.com" | "docs.google.com" => {
+            let id = drive_file_id(url)?;
+            Some(format!("https://drive.google.com/uc?export=download&id={}", id))
+        }
+        // Personal OneDrive links resolve through the shares API, which serves the content directly
+        "1drv.ms" | "onedrive.live.com" => {
+            let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(url.as_str());
+            Some(format!("https://api.onedrive.com/v1.0/shares/u!{}/root/content", encoded))
+        }
+        // SharePoint and OneDrive for Business sharing links download with `download=1`
+        _ if host.ends_with(".sharepoint.com") && url.path().starts_with("/:") => {
+            if url.query_pairs().any(|(key, value)| key == "download" && value == "1") {
+                return None;
+            }
+            let mut direct = url.clone();
+            direct.query_pairs_mut().append_pair("download", "1");
+            Some(direct.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// File ID in `/file/d/<id>/...`, `/open?id=<id>` and `/uc?id=<id>` links
+fn drive_file_id(url: &Url) -> Option<String> {
+    let mut segments = url.path_segments()?;
+    if let Some(position) = segments.clone().position(|s| s == "d") {
+        return segments.nth(position + 1).filter(|id| !id.is_empty()).map(str::to_string);
+    }
+
+    url.query_pairs()
+        .find(|(key, _)| key == "id")
+        .map(|(_, id)| id.to_string())
+}
+
+/// Whether `url` is a Google Drive download that may stop at the virus scan warning
+pub fn needs_drive_confirmation(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| url.host_str() == Some("drive.google.com") && url.path() == "/uc")
+}
+
+/// Google Drive doesn't scan files above ~100 MB and answers with a warning page instead;
+/// its form carries the token that confirms the download. Returns the URL to stream from.
+pub async fn confirm_drive_download(settings: &AppSettings, url: &str) -> Result<String, Error> {
+    let client = download::build_client(settings, Default::default(), Default::default())?;
+    let res = client.get(url).send().await?.error_for_status()?;
+
+    let is_html = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    if !is_html {
+        // The file itself; the response is dropped and fetched again by the download
+        return Ok(url.to_string());
+    }
+
+    let page = res.text().await?;
+    confirmed_url(&page).ok_or_else(|| {
+        Error::Transfer("Google Drive did not offer the file for download; is it shared with anyone with the link?".to_string())
+    })
+}
+
+/// Target of the download form on Drive's warning page, with its hidden fields as query
+fn confirmed_url(page: &str) -> Option<String> {
+    let form = Regex::new(r#"(?s)<form[^>]*id="download-form"[^>]*>(.*?)</form>"#).ok()?;
+    let action = Regex::new(r#"action="([^"]+)""#).ok()?;
+    let input = Regex::new(r#"<input[^>]*type="hidden"[^>]*>"#).ok()?;
+    let name = Regex::new(r#"name="([^"]+)""#).ok()?;
+    let value = Regex::new(r#"value="([^"]*)""#).ok()?;
+
+    let form_match = form.find(page)?;
+    let mut target = Url::parse(&html_unescape(action.captures(form_match.as_str())?.get(1)?.as_str())).ok()?;
+    {
+        let mut query = target.query_pairs_mut();
+        for field in input.find_iter(form_match.as_str()) {
+            let field = field.as_str();
+            if let Some(name) = name.captures(field).and_then(|c| c.get(1)) {
+                let value = value.captures(field).and_then(|c| c.get(1)).map_or("", |v| v.as_str());
+                query.append_pair(&html_unescape(name.as_str()), &html_unescape(value));
+            }
+        }
+    }
+
+    Some(target.to_string())
+}
+
+fn html_unescape(value: &str) -> String {
+    value
+        .replace("&amp;", "&")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+}
@@ -0,0 +1,272 @@
+/*!
+ * Differential mirroring of cached files into a team shared folder
+ */
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::settings::{self, SharedFolderSync};
+use crate::{AppState, Error, FileInfo};
+
+/// Name of the manifest written to the root of the shared folder
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Folder for files that don't belong to a conversation
+const UNSORTED_FOLDER: &str = "unsorted";
+
+/// Lower bound for the background sync interval
+const MIN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often a disabled sync re-reads the settings
+const IDLE_POLL: Duration = Duration::from_secs(60);
+
+/// Only one sync may touch the shared folder at a time
+static SYNC_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Description of the shared folder's content, for people without the helper
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SyncManifest {
+    /// Seconds since the Unix epoch
+    updated_at: u64,
+    files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ManifestEntry {
+    id: String,
+    /// Location relative to the shared folder, `/`-separated
+    path: String,
+    file_name: String,
+    original_url: String,
+    conversation: Option<String>,
+    tags: Vec<String>,
+    size: u64,
+    sha256: String,
+    /// Seconds since the Unix epoch
+    synced_at: u64,
+}
+
+/// Result of a sync pass, also sent as the `shared-folder-synced` event
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    pub folder: String,
+    /// Relative paths written in this pass
+    pub copied: Vec<String>,
+    /// Files already up to date
+    pub unchanged: usize,
+    /// Files left out on purpose, with the reason
+    pub skipped: Vec<(String, String)>,
+    /// Files that could not be copied, with the error
+    pub failed: Vec<(String, String)>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Whether `file` is selected by the conversation and tag filters; no filters selects everything
+fn is_selected(file: &FileInfo, config: &SharedFolderSync) -> bool {
+    if config.conversations.is_empty() && config.tags.is_empty() {
+        return true;
+    }
+
+    file.conversation.as_ref().is_some_and(|c| config.conversations.contains(c))
+        || file.tags.iter().any(|t| config.tags.contains(t))
+}
+
+/// Folder name for a conversation, without characters Windows rejects
+fn folder_name(conversation: Option<&str>) -> String {
+    let name: String = conversation
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_control() || r#"<>:"/\|?*"#.contains(c) { '_' } else { c })
+        .collect();
+
+    match name.trim().trim_end_matches('.') {
+        "" => UNSORTED_FOLDER.to_string(),
+        name => name.to_string(),
+    }
+}
+
+fn load_manifest(folder: &Path) -> SyncManifest {
+    fs::read_to_string(folder.join(MANIFEST_FILE))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// Write `target` through a temporary file next to it, so readers never see half a file
+fn replace_file(target: &Path, write: impl FnOnce(&Path) -> std::io::Result<()>) -> std::io::Result<()> {
+    let mut partial = target.as_os_str().to_owned();
+    partial.push(".sync-tmp");
+    let partial = PathBuf::from(partial);
+
+    if let Err(e) = write(&partial).and_then(|_| fs::rename(&partial, target)) {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Mirror `files` into `folder`, copying only what changed since the last pass
+fn sync_files(folder: &Path, files: &[FileInfo]) -> Result<SyncReport, Error> {
+    fs::create_dir_all(folder)?;
+
+    let mut manifest = load_manifest(folder);
+    let mut report = SyncReport {
+        folder: folder.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+
+    for file in files {
+        let hashed = match &file.sha256 {
+            Some(sha256) => Ok(sha256.clone()),
+            None => crate::checksums::sha256_file(Path::new(&file.file_path), None),
+        };
+        let sha256 = match hashed {
+            Ok(sha256) => sha256,
+            Err(e) => {
+                report.failed.push((file.file_name.clone(), e.to_string()));
+                continue;
+            }
+        };
+
+        let existing = manifest.files.iter().position(|e| e.id == file.id);
+        if let Some(entry) = existing.map(|i| &manifest.files[i]) {
+            let current = fs::metadata(folder.join(&entry.path)).is_ok_and(|m| m.len() == file.size);
+            if current && entry.sha256 == sha256 {
+                report.unchanged += 1;
+                continue;
+            }
+        }
+
+        // Files keep their place once synced; new ones get a name nobody else uses
+        let relative = match existing {
+            Some(i) => manifest.files[i].path.clone(),
+            None => {
+                let directory = folder_name(file.conversation.as_deref());
+                let mut used: HashSet<String> = fs::read_dir(folder.join(&directory))
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|e| e.file_name().to_string_lossy().to_lowercase())
+                    .chain(
+                        manifest.files
+                            .iter()
+                            .filter_map(|e| e.path.strip_prefix(&format!("{}/", directory)))
+                            .map(str::to_lowercase),
+                    )
+                    .collect();
+                format!("{}/{}", directory, crate::archive::unique_entry_name(&file.file_name, &mut used))
+            }
+        };
+
+        let target = folder.join(&relative);
+        let copied = target
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| replace_file(&target, |partial| fs::copy(&file.file_path, partial).map(|_| ())));
+        if let Err(e) = copied {
+            report.failed.push((relative, e.to_string()));
+            continue;
+        }
+
+        let entry = ManifestEntry {
+            id: file.id.clone(),
+            path: relative.clone(),
+            file_name: file.file_name.clone(),
+            original_url: file.original_url.clone(),
+            conversation: file.conversation.clone(),
+            tags: file.tags.clone(),
+            size: file.size,
+            sha256,
+            synced_at: now_secs(),
+        };
+        match existing {
+            Some(i) => manifest.files[i] = entry,
+            None => manifest.files.push(entry),
+        }
+        report.copied.push(relative);
+    }
+
+    manifest.updated_at = now_secs();
+    let json = serde_json::to_string_pretty(&manifest).map_err(|e| Error::IoError(std::io::Error::other(e)))?;
+    replace_file(&folder.join(MANIFEST_FILE), |partial| fs::write(partial, &json))?;
+
+    Ok(report)
+}
+
+/// Run one sync pass with the current settings
+async fn run_sync(app_handle: &AppHandle) -> Result<SyncReport, Error> {
+    let config = settings::load_settings(app_handle)
+        .unwrap_or_default()
+        .shared_folder_sync;
+    if config.folder.is_empty() {
+        return Err(Error::FileNotFound("No shared folder configured".to_string()));
+    }
+
+    let _guard = SYNC_LOCK.lock().await;
+
+    let mut skipped = Vec::new();
+    let files: Vec<FileInfo> = {
+        let state = app_handle.state::<Arc<Mutex<AppState>>>();
+        let app_state = state.lock().unwrap();
+        app_state.downloaded_files
+            .values()
+            .filter(|file| is_selected(file, &config))
+            .filter(|file| {
+                // Ephemeral files must never leave the session
+                if file.encrypted {
+                    skipped.push((file.file_name.clone(), "Ephemeral session file".to_string()));
+                }
+                !file.encrypted
+            })
+            .cloned()
+            .collect()
+    };
+
+    let folder = PathBuf::from(&config.folder);
+    let mut report = tokio::task::spawn_blocking(move || sync_files(&folder, &files))
+        .await
+        .map_err(|e| Error::IoError(std::io::Error::other(e)))??;
+    report.skipped = skipped;
+
+    let _ = app_handle.emit("shared-folder-synced", &report);
+    Ok(report)
+}
+
+/// Start the background sync job
+pub fn start(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let config = settings::load_settings(&app_handle)
+                .unwrap_or_default()
+                .shared_folder_sync;
+
+            if !config.enabled || config.folder.is_empty() {
+                tokio::time::sleep(IDLE_POLL).await;
+                continue;
+            }
+
+            if let Err(e) = run_sync(&app_handle).await {
+                println!("Shared folder sync failed: {}", e);
+            }
+
+            tokio::time::sleep(Duration::from_secs(config.interval_secs).max(MIN_INTERVAL)).await;
+        }
+    });
+}
+
+// Command to sync the shared folder now instead of waiting for the next background pass
+#[tauri::command]
+pub async fn sync_shared_folder(app_handle: AppHandle) -> Result<SyncReport, Error> {
+    run_sync(&app_handle).await
+}
@@ -0,0 +1,103 @@
+/*!
+ * Expansion of shortened links (bit.ly, tinyurl, ...) so the real target can be checked
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+use reqwest::header::LOCATION;
+use reqwest::StatusCode;
+use url::Url;
+
+use crate::settings::AppSettings;
+use crate::Error;
+
+/// Hosts that only redirect to the link they stand for
+const SHORTENER_HOSTS: &[&str] = &[
+    "bit.ly",
+    "bitly.com",
+    "buff.ly",
+    "cutt.ly",
+    "goo.gl",
+    "is.gd",
+    "ow.ly",
+    "rb.gy",
+    "rebrand.ly",
+    "shorturl.at",
+    "t.co",
+    "t.ly",
+    "tiny.cc",
+    "tinyurl.com",
+    "v.gd",
+];
+
+/// Upper bound for each request made while expanding
+const HOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether `url` points at a known link shortener
+pub fn is_shortened(url: &Url) -> bool {
+    let Some(host) = url.host_str().map(|host| host.to_ascii_lowercase()) else {
+        return false;
+    };
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    SHORTENER_HOSTS.contains(&host)
+}
+
+/// Follow the shortener's redirects until they leave the shortener hosts, without fetching the target.
+/// The number of hops is bounded by `settings.max_redirects`.
+pub async fn expand(settings: &AppSettings, url: &Url) -> Result<Url, Error> {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .connect_timeout(Duration::from_secs(settings.timeouts.connect_secs.max(1)))
+        .timeout(HOP_TIMEOUT)
+        .dns_resolver(Arc::new(crate::network::GuardedResolver::new(settings)))
+        .build()?;
+
+    let mut current = url.clone();
+    let mut visited = vec![current.clone()];
+    while is_shortened(&current) {
+        if visited.len() > settings.max_redirects as usize {
+            return Err(Error::Transfer(format!(
+                "Too many redirects expanding {} (limit {})",
+                url, settings.max_redirects
+            )));
+        }
+
+        let next = next_hop(&client, &current).await?.ok_or_else(|| {
+            Error::Transfer(format!("Shortened link {} does not lead anywhere", url))
+        })?;
+        // Shorteners themselves needn't be allowed, every other hop must be; redirects after
+        // the last one are checked by the download client's redirect policy
+        if is_shortened(&next) {
+            crate::network::check_url(settings, &next)?;
+        } else {
+            crate::download::check_host_allowed(settings, &next)?;
+        }
+        if visited.contains(&next) {
+            return Err(Error::Transfer(format!("Redirect loop at {}", next)));
+        }
+
+        println!("Shortened link {} leads to {}", current, next);
+        visited.push(next.clone());
+        current = next;
+    }
+
+    Ok(current)
+}
+
+/// Where `url` redirects to. Tries HEAD first and falls back to GET for shorteners that
+/// don't answer it; the body of the GET is never read.
+async fn next_hop(client: &reqwest::Client, url: &Url) -> Result<Option<Url>, Error> {
+    let mut res = client.head(url.clone()).send().await?;
+    if matches!(res.status(), StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED) {
+        res = client.get(url.clone()).send().await?;
+    }
+
+    if !res.status().is_redirection() {
+        return Ok(None);
+    }
+    Ok(res
+        .headers()
+        .get(LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|location| url.join(location).ok()))
+}
@@ -0,0 +1,249 @@
+/*!
+ * Tracking and cleanup of files the update system leaves on disk
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+/// Name of the registry file in the app data directory
+const REGISTRY_FILE: &str = "update-artifacts.json";
+
+/// Directory in app data that update downloads go to
+const UPDATES_DIR: &str = "updates";
+
+/// Age after which a partial download is no longer resumed, and an unregistered file in the
+/// updates directory counts as abandoned
+const ABANDONED_AFTER: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Sidecar extensions that may sit next to a downloaded package
+const SIDECAR_EXTENSIONS: [&str; 3] = ["sha256", "sha", "sig"];
+
+/// Kind of file created by the update system
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactKind {
+    /// Downloaded installer or executable
+    Package,
+    /// Helper script such as `update.bat`
+    Script,
+}
+
+/// A file registered for later cleanup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artifact {
+    pub path: String,
+    pub kind: ArtifactKind,
+    /// Registration time in seconds since the Unix epoch
+    pub registered_at: u64,
+}
+
+/// Summary of a cleanup pass
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CleanupReport {
+    /// Files that were deleted
+    pub removed: Vec<String>,
+    /// Files that could not be deleted, with the reason; they stay registered
+    pub failed: Vec<(String, String)>,
+    /// Total size of the deleted files in bytes
+    pub freed_bytes: u64,
+}
+
+fn app_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    Ok(app_dir)
+}
+
+fn registry_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app_dir(app)?.join(REGISTRY_FILE))
+}
+
+/// The managed directory update packages are downloaded and staged in
+pub fn updates_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app_dir(app)?.join(UPDATES_DIR);
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create updates directory: {}", e))?;
+
+    Ok(dir)
+}
+
+/// Where to download to: `destination` is a file in the updates directory. Absolute paths and
+/// `..` are refused, so callers can't write anywhere else.
+pub fn resolve_destination(app: &AppHandle, destination: &str) -> Result<String, String> {
+    let path = Path::new(destination);
+    let contained = path
+        .components()
+        .all(|component| matches!(component, std::path::Component::Normal(_)));
+    if !contained || path.as_os_str().is_empty() {
+        return Err(format!("Update downloads must stay in the updates directory: {}", destination));
+    }
+
+    Ok(updates_dir(app)?.join(path).to_string_lossy().to_string())
+}
+
+fn load_registry(app: &AppHandle) -> Result<Vec<Artifact>, String> {
+    let path = registry_path(app)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let json = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read artifact registry: {}", e))?;
+
+    // A corrupt registry must not block updates, start over instead
+    Ok(serde_json::from_str(&json).unwrap_or_default())
+}
+
+fn save_registry(app: &AppHandle, artifacts: &[Artifact]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(artifacts)
+        .map_err(|e| format!("Failed to serialize artifact registry: {}", e))?;
+
+    fs::write(registry_path(app)?, json)
+        .map_err(|e| format!("Failed to write artifact registry: {}", e))
+}
+
+/// Record a file so a later cleanup pass can remove it
+pub fn register(app: &AppHandle, path: &Path, kind: ArtifactKind) -> Result<(), String> {
+    let mut artifacts = load_registry(app)?;
+    let path = path.to_string_lossy().to_string();
+
+    if artifacts.iter().any(|a| a.path == path) {
+        return Ok(());
+    }
+
+    let registered_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    artifacts.push(Artifact { path, kind, registered_at });
+    save_registry(app, &artifacts)
+}
+
+/// Delete registered artifacts (optionally only those of `kind`) and stray update scripts
+pub fn cleanup(app: &AppHandle, kind: Option<ArtifactKind>) -> Result<CleanupReport, String> {
+    let mut report = CleanupReport::default();
+    remove_registered(app, &mut report, |artifact| kind.is_none_or(|k| k == artifact.kind))?;
+
+    // Scripts written before artifacts were tracked
+    if kind.is_none_or(|k| k == ArtifactKind::Script) {
+        remove_legacy_script(&mut report);
+    }
+
+    Ok(report)
+}
+
+/// Startup pass over what earlier updates left behind. Everything registered goes, except partial
+/// downloads recent enough to be resumed and the files in `keep`, i.e. a staged update. Files
+/// in the updates directory that were never registered, such as installer logs, go once abandoned.
+pub fn purge_stale(app: &AppHandle, keep: &[PathBuf]) -> Result<CleanupReport, String> {
+    let mut report = CleanupReport::default();
+    let kept = |path: &Path| keep.iter().any(|k| k == path);
+    remove_registered(app, &mut report, |artifact| {
+        let path = Path::new(&artifact.path);
+        !kept(path) && !is_resumable(path)
+    })?;
+    remove_legacy_script(&mut report);
+
+    let registered: Vec<PathBuf> = load_registry(app)?
+        .into_iter()
+        .map(|artifact| PathBuf::from(artifact.path))
+        .collect();
+    let entries = fs::read_dir(updates_dir(app)?)
+        .map_err(|e| format!("Failed to read updates directory: {}", e))?;
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        let sidecar_of = |owner: &PathBuf| {
+            path.to_string_lossy()
+                .strip_prefix(owner.to_string_lossy().as_ref())
+                .and_then(|rest| rest.strip_prefix('.'))
+                .is_some_and(|extension| SIDECAR_EXTENSIONS.contains(&extension))
+        };
+        let owned = keep.iter().chain(&registered).any(|owner| *owner == path || sidecar_of(owner));
+        if !owned && path.is_file() && age(&path).is_some_and(|age| age > ABANDONED_AFTER) {
+            remove_file(&path, &mut report);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Delete the registered artifacts `selected` picks, together with their sidecars
+fn remove_registered(
+    app: &AppHandle,
+    report: &mut CleanupReport,
+    selected: impl Fn(&Artifact) -> bool,
+) -> Result<(), String> {
+    let artifacts = load_registry(app)?;
+    let mut remaining = Vec::new();
+
+    for artifact in artifacts {
+        if !selected(&artifact) {
+            remaining.push(artifact);
+            continue;
+        }
+
+        let path = PathBuf::from(&artifact.path);
+        let mut ok = remove_file(&path, report);
+        for extension in SIDECAR_EXTENSIONS {
+            let sidecar = PathBuf::from(format!("{}.{}", artifact.path, extension));
+            ok &= remove_file(&sidecar, report);
+        }
+
+        if !ok {
+            remaining.push(artifact);
+        }
+    }
+
+    save_registry(app, &remaining)
+}
+
+/// `update.bat` next to the executable, from before artifacts were tracked
+fn remove_legacy_script(report: &mut CleanupReport) {
+    if let Some(script) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join("update.bat")))
+    {
+        remove_file(&script, report);
+    }
+}
+
+/// Time since `path` was last written
+fn age(path: &Path) -> Option<Duration> {
+    fs::metadata(path).ok()?.modified().ok()?.elapsed().ok()
+}
+
+/// Whether `path` is a partial download that the next attempt may still continue
+fn is_resumable(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "part")
+        && age(path).is_some_and(|age| age <= ABANDONED_AFTER)
+}
+
+/// Remove a single file if it exists, recording the result. Returns false on failure.
+fn remove_file(path: &Path, report: &mut CleanupReport) -> bool {
+    let size = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => return true, // Already gone
+    };
+
+    match fs::remove_file(path) {
+        Ok(()) => {
+            report.removed.push(path.to_string_lossy().to_string());
+            report.freed_bytes += size;
+            true
+        }
+        Err(e) => {
+            report.failed.push((path.to_string_lossy().to_string(), e.to_string()));
+            false
+        }
+    }
+}
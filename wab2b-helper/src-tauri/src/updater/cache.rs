@@ -0,0 +1,171 @@
+/*!
+ * On-disk snapshot of the latest release, used to list downloads without API calls
+ */
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::updater::{github, ReleaseInfo};
+
+/// Name of the snapshot file in the app data directory
+const CACHE_FILE: &str = "release-cache.json";
+
+/// Age after which a snapshot is refreshed when the caller allows it
+const MAX_CACHE_AGE: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Minimum time between two GitHub requests made on behalf of the cache
+const MIN_FETCH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Time of the last fetch attempt, successful or not
+static LAST_FETCH: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Release information as stored on disk
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReleaseSnapshot {
+    pub owner: String,
+    pub repo: String,
+    pub include_beta: bool,
+    /// Time of the fetch in seconds since the Unix epoch
+    pub fetched_at: u64,
+    pub release: ReleaseInfo,
+}
+
+/// A release asset as shown on the manual download screen
+#[derive(Debug, Serialize)]
+pub struct AvailableAsset {
+    pub name: String,
+    pub download_url: String,
+    pub size: u64,
+    pub sha256: String,
+    pub sha512: String,
+    /// Whether this is the asset the updater would pick for this machine
+    pub platform_match: bool,
+}
+
+/// Result of `get_available_downloads`
+#[derive(Debug, Serialize)]
+pub struct AvailableDownloads {
+    pub version: String,
+    pub published_at: String,
+    pub fetched_at: u64,
+    /// True when the snapshot is older than the refresh age and could not be refreshed
+    pub stale: bool,
+    pub assets: Vec<AvailableAsset>,
+}
+
+fn cache_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    Ok(app_dir.join(CACHE_FILE))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Load the stored snapshot, if there is a readable one
+pub fn load_snapshot(app: &AppHandle) -> Option<ReleaseSnapshot> {
+    let json = fs::read_to_string(cache_path(app).ok()?).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Store a freshly fetched release
+pub fn store_snapshot(app: &AppHandle, owner: &str, repo: &str, include_beta: bool, release: &ReleaseInfo) -> Result<(), String> {
+    let snapshot = ReleaseSnapshot {
+        owner: owner.to_string(),
+        repo: repo.to_string(),
+        include_beta,
+        fetched_at: now_secs(),
+        release: release.clone(),
+    };
+
+    let json = serde_json::to_string_pretty(&snapshot)
+        .map_err(|e| format!("Failed to serialize release cache: {}", e))?;
+
+    fs::write(cache_path(app)?, json)
+        .map_err(|e| format!("Failed to write release cache: {}", e))
+}
+
+/// Whether a GitHub request may be made now, recording the attempt if so
+fn acquire_fetch_slot() -> bool {
+    let mut last_fetch = LAST_FETCH.lock().unwrap();
+    if last_fetch.is_some_and(|t| t.elapsed() < MIN_FETCH_INTERVAL) {
+        return false;
+    }
+    *last_fetch = Some(Instant::now());
+    true
+}
+
+/// Return the cached asset list, refreshing it from GitHub only when it is missing or old
+pub async fn available_downloads(
+    app: &AppHandle,
+    owner: Option<&str>,
+    repo: Option<&str>,
+) -> Result<AvailableDownloads, String> {
+    let snapshot = load_snapshot(app);
+    let expired = snapshot
+        .as_ref()
+        .map(|s| now_secs().saturating_sub(s.fetched_at) > MAX_CACHE_AGE.as_secs())
+        .unwrap_or(true);
+
+    // Fall back to the repository of the stored snapshot
+    let owner = owner.map(str::to_string).or_else(|| snapshot.as_ref().map(|s| s.owner.clone()));
+    let repo = repo.map(str::to_string).or_else(|| snapshot.as_ref().map(|s| s.repo.clone()));
+    let settings = crate::settings::load_settings(app).ok();
+    let include_beta = settings.as_ref().is_some_and(|s| s.beta_mode);
+    let asset_pattern = settings.map(|s| s.update_asset_pattern).unwrap_or_default();
+
+    if let (true, Some(owner), Some(repo)) = (expired, owner, repo) {
+        if acquire_fetch_slot() {
+            match super::fetch_release(app, &owner, &repo, include_beta).await {
+                Ok(release) => {
+                    store_snapshot(app, &owner, &repo, include_beta, &release)?;
+                }
+                Err(e) if snapshot.is_some() => {
+                    println!("Failed to refresh release cache, serving stale copy: {}", e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    let snapshot = load_snapshot(app)
+        .ok_or_else(|| "No release information is cached yet".to_string())?;
+    let stale = now_secs().saturating_sub(snapshot.fetched_at) > MAX_CACHE_AGE.as_secs();
+
+    let selected = github::select_platform_asset(&snapshot.release.assets, &asset_pattern).map(|a| a.name);
+    let assets = snapshot
+        .release
+        .assets
+        .into_iter()
+        .map(|asset| AvailableAsset {
+            platform_match: selected.as_deref() == Some(asset.name.as_str()),
+            name: asset.name,
+            download_url: asset.download_url,
+            size: asset.size,
+            sha256: asset.sha256,
+            sha512: asset.sha512,
+        })
+        .collect();
+
+    Ok(AvailableDownloads {
+        version: snapshot.release.version,
+        published_at: snapshot.release.published_at,
+        fetched_at: snapshot.fetched_at,
+        stale,
+        assets,
+    })
+}
@@ -0,0 +1,75 @@
+/*!
+ * The updater's events to the webview, all sent as `updater-event` tagged by `type`
+ */
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::updater::ReleaseInfo;
+
+/// Name of the one event the updater emits
+pub const UPDATER_EVENT: &str = "updater-event";
+
+/// Where in the update process something went wrong
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCode {
+    Check,
+    Download,
+    Verification,
+    Install,
+}
+
+/// A step of the update process
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case", rename_all_fields = "camelCase")]
+pub enum UpdaterEvent {
+    /// Looking for a newer release
+    Checking,
+    /// A newer release the user hasn't skipped
+    Available { release: Box<ReleaseInfo> },
+    /// The running version is older than the release allows; the frontend has to block until
+    /// the update is installed
+    UpdateRequired { release: Box<ReleaseInfo>, minimum_version: String },
+    /// Progress of the package download
+    Downloading { pct: f64, downloaded: u64, total: u64 },
+    /// Checking the hash of a downloaded package
+    Verifying { path: String },
+    /// A verified package waiting to be installed; `staged` when it installs on exit
+    ReadyToInstall { path: String, version: Option<String>, staged: bool },
+    Failed {
+        code: FailureCode,
+        message: String,
+        /// Exit code of the installer, for install failures
+        exit_code: Option<i32>,
+        /// Installer log, when one was written
+        log_path: Option<String>,
+    },
+}
+
+impl UpdaterEvent {
+    /// A failure with nothing but a message
+    pub fn failed(code: FailureCode, message: impl Into<String>) -> Self {
+        Self::Failed {
+            code,
+            message: message.into(),
+            exit_code: None,
+            log_path: None,
+        }
+    }
+}
+
+/// Send `event` to the webview
+pub fn emit(app: &AppHandle, event: UpdaterEvent) {
+    let _ = app.emit(UPDATER_EVENT, &event);
+}
+
+/// Send download progress for `destination`, coalesced with other progress of the same download
+pub fn emit_progress(app: &AppHandle, destination: &str, downloaded: u64, total: u64) {
+    let event = UpdaterEvent::Downloading {
+        pct: downloaded as f64 * 100.0 / total as f64,
+        downloaded,
+        total,
+    };
+    crate::events::emit_coalesced(app, UPDATER_EVENT, destination, &event);
+}
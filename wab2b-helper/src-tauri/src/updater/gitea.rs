@@ -0,0 +1,73 @@
+/*!
+ * Gitea (and Forgejo) releases, whose API mirrors GitHub's
+ */
+
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+use reqwest::Client;
+use serde_json::Value;
+
+use crate::updater::github;
+use crate::updater::provider::ReleaseProvider;
+use crate::updater::ReleaseInfo;
+
+/// Releases looked at per check
+const RELEASES_PER_PAGE: u32 = 30;
+
+/// Releases of a repository on a Gitea server
+pub struct Gitea {
+    /// Server URL, e.g. `https://git.example.com`
+    pub base_url: String,
+    pub owner: String,
+    pub repo: String,
+    pub token: Option<String>,
+}
+
+impl ReleaseProvider for Gitea {
+    async fn latest_release(&self, include_beta: bool) -> Result<ReleaseInfo, String> {
+        if self.base_url.trim().is_empty() {
+            return Err("A Gitea server URL is required to check for updates".to_string());
+        }
+        
+        let client = Client::builder()
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        // Only requests to the server carry the token; release assets may link elsewhere
+        let server_token = github::ServerToken::new(&self.base_url, self.token.as_deref());
+        
+        let url = format!(
+            "{}/api/v1/repos/{}/{}/releases?limit={}",
+            self.base_url.trim().trim_end_matches('/'),
+            self.owner,
+            self.repo,
+            RELEASES_PER_PAGE
+        );
+        let mut request = client
+            .get(&url)
+            .header("User-Agent", "WAB2B-Helper-Update-System");
+        if let Some(token) = &self.token {
+            let mut value = HeaderValue::from_str(&format!("token {}", token))
+                .map_err(|_| "Gitea token contains invalid characters".to_string())?;
+            value.set_sensitive(true);
+            request = request.header(AUTHORIZATION, value);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send request: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Gitea API request failed with status: {}",
+                response.status()
+            ));
+        }
+        
+        let releases = response
+            .json::<Vec<Value>>()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+        
+        let mut release_info = github::select_release(releases, include_beta)?;
+        github::populate_checksums(&client, &mut release_info, server_token.as_ref()).await?;
+        Ok(release_info)
+    }
+}
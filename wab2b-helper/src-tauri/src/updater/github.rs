@@ -2,130 +2,396 @@
  * GitHub API integration for the update system
  */
 
-use crate::updater::{ReleaseInfo, Asset, DownloadProgress};
+use crate::updater::{ReleaseInfo, ReleaseChannel, Asset};
+use crate::updater::events;
+use crate::updater::provider::{self, ReleaseProvider};
+use crate::updater::artifacts::{self, ArtifactKind};
+use crate::updater::hash;
+use crate::updater::installer::{self, InstallMode, InstallerKind};
+use crate::updater::signature;
+use crate::updater::version;
 use serde_json::Value;
 use reqwest::Client;
-use std::path::Path;
-use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, ETAG, IF_NONE_MATCH, RANGE};
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use regex::{Regex, RegexBuilder};
 use std::env::consts::{OS, ARCH};
 use tokio::io::AsyncWriteExt;
 use futures_util::StreamExt;
-use tauri::Emitter;
+
+/// Releases looked at per check; the newest ones by date are enough to find the newest version
+const RELEASES_PER_PAGE: u32 = 30;
+
+/// Environment variable holding a GitHub token when the settings have none
+const TOKEN_ENV: &str = "WAB2B_GITHUB_TOKEN";
+
+/// GitHub token from the secrets store or the environment, if any
+pub fn api_token(app_handle: Option<&tauri::AppHandle>) -> Option<String> {
+    app_handle
+        .and_then(|app| crate::secrets::get(app, crate::secrets::GITHUB_TOKEN).ok().flatten())
+        .filter(|token| !token.trim().is_empty())
+        .or_else(|| std::env::var(TOKEN_ENV).ok().filter(|token| !token.trim().is_empty()))
+        .map(|token| token.trim().to_string())
+}
+
+/// Host of `url`, lowercase
+fn url_host(url: &str) -> Option<String> {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+}
+
+/// Whether `url` points at GitHub, the only host the token is sent to
+fn is_github_url(url: &str) -> bool {
+    url_host(url).is_some_and(|host| host == "github.com" || host.ends_with(".github.com"))
+}
+
+/// A GitLab or Gitea token, sent only to URLs on the server it belongs to. Release links may
+/// point anywhere, and a token in a default header would go along.
+pub struct ServerToken {
+    host: String,
+    token: String,
+}
+
+impl ServerToken {
+    /// Token for the server at `base_url`, if there is a token
+    pub fn new(base_url: &str, token: Option<&str>) -> Option<Self> {
+        let token = token.map(str::trim).filter(|token| !token.is_empty())?;
+        Some(Self { host: url_host(base_url)?, token: token.to_string() })
+    }
+
+    fn applies_to(&self, url: &str) -> bool {
+        url_host(url).as_deref() == Some(self.host.as_str())
+    }
+}
+
+/// Token to send along when downloading `url`: the GitHub token to GitHub, the release
+/// source's token to its GitLab or Gitea server, nothing anywhere else
+pub fn token_for_url(app_handle: Option<&tauri::AppHandle>, url: &str) -> Option<String> {
+    if is_github_url(url) {
+        return api_token(app_handle);
+    }
+    
+    let app_handle = app_handle?;
+    let source = crate::settings::load_settings(app_handle).ok()?.release_source;
+    let base_url = match (source.provider, source.base_url.trim()) {
+        (crate::settings::ReleaseProviderKind::Github, _) => return None,
+        (crate::settings::ReleaseProviderKind::Gitlab, "") => super::gitlab::DEFAULT_BASE_URL,
+        (_, base_url) => base_url,
+    };
+    let token = crate::secrets::get(app_handle, crate::secrets::RELEASE_SOURCE_TOKEN).ok().flatten();
+    ServerToken::new(base_url, token.as_deref())
+        .filter(|server| server.applies_to(url))
+        .map(|server| server.token)
+}
+
+/// HTTP client sending `token` as `Authorization: Bearer` with every request, which GitHub,
+/// GitLab and Gitea all accept. reqwest drops the header on redirects to other hosts, such as
+/// GitHub's download CDN.
+fn http_client(token: Option<&str>) -> Result<Client, String> {
+    let mut headers = HeaderMap::new();
+    if let Some(token) = token {
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|_| "GitHub token contains invalid characters".to_string())?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+    
+    Client::builder()
+        .default_headers(headers)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// Releases of a GitHub repository
+pub struct GitHub {
+    pub owner: String,
+    pub repo: String,
+    pub token: Option<String>,
+}
+
+impl ReleaseProvider for GitHub {
+    async fn latest_release(&self, include_beta: bool) -> Result<ReleaseInfo, String> {
+        check_latest_release(&self.owner, &self.repo, include_beta, self.token.as_deref()).await
+    }
+}
 
 /// Check for the latest release on GitHub
-pub async fn check_latest_release(owner: &str, repo: &str, include_beta: bool) -> Result<ReleaseInfo, String> {
+pub async fn check_latest_release(owner: &str, repo: &str, include_beta: bool, token: Option<&str>) -> Result<ReleaseInfo, String> {
     // Create a new HTTP client
-    let client = Client::new();
-    
-    // Build the GitHub API URL - use /releases for beta support or /releases/latest for stable only
-    let url = if include_beta {
-        format!(
-            "https://api.github.com/repos/{}/{}/releases",
-            owner, repo
-        )
-    } else {
-        format!(
-            "https://api.github.com/repos/{}/{}/releases/latest",
-            owner, repo
-        )
-    };
+    let client = http_client(token)?;
     
-    // Send the request with appropriate headers
-    let response = client
-        .get(&url)
+    // List the releases rather than asking for /releases/latest, which never returns pre-releases
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/releases?per_page={}",
+        owner, repo, RELEASES_PER_PAGE
+    );
+    
+    let releases = fetch_releases(&client, &url).await?;
+    
+    // Pick the newest release the channel allows; the API orders by creation date, not version
+    let mut release_info = select_release(releases, include_beta)?;
+    
+    // The client only sends its token to GitHub, where release assets live
+    populate_checksums(&client, &mut release_info, None).await?;
+    
+    Ok(release_info)
+}
+
+/// Release listing last received per URL, with its ETag for conditional requests
+struct CachedReleases {
+    etag: String,
+    releases: Vec<Value>,
+}
+
+static RELEASES_CACHE: LazyLock<Mutex<HashMap<String, CachedReleases>>> = LazyLock::new(Default::default);
+
+/// Unix time before which the GitHub API is not asked again, after running out of requests
+static RATE_LIMITED_UNTIL: AtomicU64 = AtomicU64::new(0);
+
+/// Wait after a rate limit response that doesn't say how long to wait
+const DEFAULT_RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(15 * 60);
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The last listing received for `url`, if any
+fn cached_releases(url: &str) -> Option<Vec<Value>> {
+    RELEASES_CACHE.lock().unwrap().get(url).map(|cached| cached.releases.clone())
+}
+
+/// Unix time at which a rate-limited response says requests may resume, from `Retry-After`
+/// or `X-RateLimit-Reset`
+fn rate_limit_end(headers: &HeaderMap) -> u64 {
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+    
+    header("retry-after")
+        .map(|secs| now_secs() + secs)
+        .or_else(|| header("x-ratelimit-reset"))
+        .unwrap_or_else(|| now_secs() + DEFAULT_RATE_LIMIT_BACKOFF.as_secs())
+}
+
+/// Error for checks made while rate limited, or the last listing if there is one to fall back on
+fn rate_limited(url: &str, until: u64) -> Result<Vec<Value>, String> {
+    if let Some(releases) = cached_releases(url) {
+        println!("GitHub rate limit reached, using the last release listing");
+        return Ok(releases);
+    }
+    let minutes = until.saturating_sub(now_secs()).div_ceil(60).max(1);
+    Err(format!("GitHub rate limit reached, update checks resume in {} minute(s)", minutes))
+}
+
+/// List releases with a conditional request, backing off while GitHub's rate limit is exhausted
+async fn fetch_releases(client: &Client, url: &str) -> Result<Vec<Value>, String> {
+    let until = RATE_LIMITED_UNTIL.load(Ordering::Relaxed);
+    if now_secs() < until {
+        return rate_limited(url, until);
+    }
+    
+    let etag = RELEASES_CACHE.lock().unwrap().get(url).map(|cached| cached.etag.clone());
+    let mut request = client
+        .get(url)
         .header("User-Agent", "WAB2B-Helper-Update-System")
-        .header("Accept", "application/vnd.github.v3+json")
+        .header("Accept", "application/vnd.github.v3+json");
+    // A 304 doesn't count against the rate limit
+    if let Some(etag) = &etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    
+    let response = request
         .send()
         .await
         .map_err(|e| format!("Failed to send request: {}", e))?;
+    let status = response.status();
+    let headers = response.headers().clone();
+    let remaining = headers
+        .get("x-ratelimit-remaining")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    
+    if status == StatusCode::NOT_MODIFIED {
+        if let Some(releases) = cached_releases(url) {
+            return Ok(releases);
+        }
+    }
+    
+    // GitHub answers 403 or 429 once the quota is used up
+    if (status == StatusCode::FORBIDDEN && remaining == Some(0)) || status == StatusCode::TOO_MANY_REQUESTS {
+        let until = rate_limit_end(&headers);
+        RATE_LIMITED_UNTIL.store(until, Ordering::Relaxed);
+        return rate_limited(url, until);
+    }
     
     // Check if the request was successful
-    if !response.status().is_success() {
+    if !status.is_success() {
         return Err(format!(
             "GitHub API request failed with status: {}",
-            response.status()
+            status
         ));
     }
     
-    // Parse the response as JSON
-    let release_data = if include_beta {
-        // For beta releases, get the first release from the array
-        let releases = response
-            .json::<Vec<Value>>()
-            .await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
-        
-        if releases.is_empty() {
-            return Err("No releases found".to_string());
-        }
-        
-        releases[0].clone()
-    } else {
-        // For stable releases, get the single latest release
-        response
-            .json::<Value>()
-            .await
-            .map_err(|e| format!("Failed to parse response: {}", e))?
-    };
+    // That was the last request of this window; don't spend the next check on an error
+    if remaining == Some(0) {
+        RATE_LIMITED_UNTIL.store(rate_limit_end(&headers), Ordering::Relaxed);
+    }
+    
+    let releases = response
+        .json::<Vec<Value>>()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
     
-    // Parse the release information
-    let mut release_info = parse_release_info(release_data)?;
+    if let Some(etag) = headers.get(ETAG).and_then(|value| value.to_str().ok()) {
+        RELEASES_CACHE.lock().unwrap().insert(url.to_string(), CachedReleases {
+            etag: etag.to_string(),
+            releases: releases.clone(),
+        });
+    }
+    
+    Ok(releases)
+}
 
-    // Populate SHA-256 hashes from a checksums.txt asset if one exists
-    if let Some(checksum_asset) = release_info
-        .assets
-        .iter()
-        .find(|a| a.name.to_lowercase().ends_with("checksums.txt"))
-        .cloned()
-    {
-        // Download the checksums file
-        let checksum_text = client
-            .get(&checksum_asset.download_url)
-            .header("User-Agent", "WAB2B-Helper-Update-System")
-            .send()
-            .await
-            .map_err(|e| format!("Failed to download checksums file: {}", e))?
-            .text()
-            .await
-            .map_err(|e| format!("Failed to read checksums file: {}", e))?;
-
-        use std::collections::HashMap;
-        use std::path::Path;
-
-        // Build filename -> hash map
-        let mut hash_map: HashMap<String, String> = HashMap::new();
-        for line in checksum_text.lines() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                let hash = parts[0].to_string();
-                let file_path_part = parts[1];
-                let file_name = Path::new(file_path_part)
-                    .file_name()
-                    .map(|s| s.to_string_lossy().to_string())
-                    .unwrap_or_else(|| file_path_part.to_string());
-                hash_map.insert(file_name, hash);
+/// Parse the published releases and return the newest by version, skipping drafts, and
+/// pre-releases unless `include_beta` is set
+pub fn select_release(releases: Vec<Value>, include_beta: bool) -> Result<ReleaseInfo, String> {
+    let releases = releases
+        .into_iter()
+        .filter(|release_data| !release_data["draft"].as_bool().unwrap_or(false))
+        .filter_map(|release_data| match parse_release_info(release_data) {
+            Ok(release_info) => Some(release_info),
+            Err(e) => {
+                println!("Skipping release: {}", e);
+                None
             }
-        }
+        });
+    
+    provider::newest_release(releases, include_beta)
+}
 
-        // Attach hashes to assets
-        for asset in &mut release_info.assets {
-            if let Some(hash) = hash_map.get(&asset.name) {
-                asset.sha256 = hash.clone();
+/// Fill in asset hashes from combined checksum files (`checksums.txt`, `SHA256SUMS`,
+/// `SHA512SUMS`) or `<asset>.sha256`/`<asset>.sha512` sidecars, sending `server_token` to its
+/// server only
+pub async fn populate_checksums(
+    client: &Client,
+    release_info: &mut ReleaseInfo,
+    server_token: Option<&ServerToken>,
+) -> Result<(), String> {
+    let mut checksums_texts = Vec::new();
+    for checksum_asset in release_info.assets.iter().filter(|a| {
+        let name = a.name.to_lowercase();
+        let name = name.strip_suffix(".txt").unwrap_or(&name);
+        name.ends_with("checksums") || name == "sha256sums" || name == "sha512sums"
+    }) {
+        checksums_texts.push(fetch_text(client, &checksum_asset.download_url, server_token).await?);
+    }
+    
+    let asset_names: Vec<String> = release_info.assets.iter().map(|a| a.name.clone()).collect();
+    for name in asset_names {
+        let mut found = Vec::new();
+        for extension in ["sha256", "sha512"] {
+            // A sidecar takes precedence over the combined files
+            let sidecar_url = release_info
+                .assets
+                .iter()
+                .find(|a| a.name.eq_ignore_ascii_case(&format!("{}.{}", name, extension)))
+                .map(|a| a.download_url.clone());
+            if let Some(url) = sidecar_url {
+                // A sidecar naming another file means the wrong checksum was published
+                let hash = hash::parse_checksum_file(&fetch_text(client, &url, server_token).await?, &name)
+                    .ok_or_else(|| format!("Checksum file {}.{} does not cover {}", name, extension, name))?;
+                found.push(hash);
+            }
+        }
+        if found.is_empty() {
+            found.extend(checksums_texts.iter().filter_map(|text| hash::parse_checksum_file(text, &name)));
+        }
+        
+        if let Some(asset) = release_info.assets.iter_mut().find(|a| a.name == name) {
+            for hash in found {
+                // The length tells the algorithm
+                if hash.len() == 128 {
+                    asset.sha512 = hash;
+                } else {
+                    asset.sha256 = hash;
+                }
             }
         }
     }
+    
+    Ok(())
+}
 
-    Ok(release_info)
+/// Download a small text asset such as a checksums file
+async fn fetch_text(client: &Client, url: &str, server_token: Option<&ServerToken>) -> Result<String, String> {
+    let mut request = client
+        .get(url)
+        .header("User-Agent", "WAB2B-Helper-Update-System");
+    if let Some(server_token) = server_token.filter(|server_token| server_token.applies_to(url)) {
+        request = request.bearer_auth(&server_token.token);
+    }
+    request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to download checksums file: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read checksums file: {}", e))
 }
 
-/// Download an asset from GitHub
+/// Download a release asset together with its signature
 pub async fn download_asset(
     url: &str, 
     destination: &str,
     app_handle: Option<&tauri::AppHandle>
 ) -> Result<String, String> {
-    // Create a new HTTP client
-    let client = Client::new();
+    download_file(url, destination, app_handle).await?;
+    
+    // Releases publish a minisign signature per package as `<asset>.sig`; it is checked before installing
+    download_signature(url, Path::new(destination), token_for_url(app_handle, url).as_deref()).await?;
+    
+    Ok(destination.to_string())
+}
+
+/// Where a download is written until it is complete. It is kept when the download fails, so the
+/// next attempt continues from where this one stopped.
+fn partial_path(destination: &str) -> PathBuf {
+    PathBuf::from(format!("{}.part", destination))
+}
+
+/// Request `url`, from byte `offset` on when it isn't 0
+async fn request_from(client: &Client, url: &str, offset: u64) -> Result<reqwest::Response, String> {
+    let mut request = client
+        .get(url)
+        .header("User-Agent", "WAB2B-Helper-Update-System");
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    
+    request
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request: {}", e))
+}
+
+/// Download a release file to `destination`, reporting progress when `app_handle` is given.
+/// An interrupted download is resumed; callers check the hash of the result as before, which
+/// also catches a partial file that belonged to another package.
+pub async fn download_file(
+    url: &str, 
+    destination: &str,
+    app_handle: Option<&tauri::AppHandle>
+) -> Result<(), String> {
+    // Private GitHub, GitLab and Gitea releases need their token
+    let token = token_for_url(app_handle, url);
+    let client = http_client(token.as_deref())?;
     
     // Create the destination directory if it doesn't exist
     if let Some(parent) = Path::new(destination).parent() {
@@ -133,13 +399,19 @@ pub async fn download_asset(
             .map_err(|e| format!("Failed to create directory: {}", e))?;
     }
     
-    // Send the request to download the asset
-    let response = client
-        .get(url)
-        .header("User-Agent", "WAB2B-Helper-Update-System")
-        .send()
+    let partial = partial_path(destination);
+    let mut offset = tokio::fs::metadata(&partial)
         .await
-        .map_err(|e| format!("Failed to send request: {}", e))?;
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    
+    // Send the request to download the asset, continuing a partial download if there is one
+    let mut response = request_from(&client, url, offset).await?;
+    if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial file is no prefix of this package; start over
+        offset = 0;
+        response = request_from(&client, url, offset).await?;
+    }
     
     // Check if the request was successful
     if !response.status().is_success() {
@@ -149,19 +421,37 @@ pub async fn download_asset(
         ));
     }
     
-    // Get the content length if available
+    // A server that ignores the range sends the whole file again
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        offset = 0;
+    }
+    if offset > 0 {
+        println!("Resuming download of {} at {} bytes", url, offset);
+    }
+    
+    // Get the content length if available; for a resumed download it only covers the rest
     let total_size = response
         .content_length()
+        .map(|length| length + offset)
         .unwrap_or(0);
     
-    // Create the destination file
-    let mut file = tokio::fs::File::create(destination)
-        .await
-        .map_err(|e| format!("Failed to create file: {}", e))?;
+    // Track the download so it is purged once the update is done with it
+    if let Some(app) = app_handle {
+        artifacts::register(app, &partial, ArtifactKind::Package)?;
+        artifacts::register(app, Path::new(destination), ArtifactKind::Package)?;
+    }
+    
+    // Append to the partial file when resuming, start it afresh otherwise
+    let mut file = if offset > 0 {
+        tokio::fs::OpenOptions::new().append(true).open(&partial).await
+    } else {
+        tokio::fs::File::create(&partial).await
+    }
+    .map_err(|e| format!("Failed to create file: {}", e))?;
     
     // Stream the response body to the file
     let mut stream = response.bytes_stream();
-    let mut downloaded = 0;
+    let mut downloaded = offset;
     
     while let Some(chunk) = stream.next().await {
         let chunk = chunk.map_err(|e| format!("Error while downloading: {}", e))?;
@@ -174,20 +464,54 @@ pub async fn download_asset(
         // Emit progress event if app_handle is provided
         if let Some(app) = app_handle {
             if total_size > 0 {
-                let progress = DownloadProgress {
-                    downloaded,
-                    total: total_size,
-                    file_path: destination.to_string(),
-                };
-                
-                // Emit the progress event
-                let _ = app.emit("download-progress", progress);
+                events::emit_progress(app, destination, downloaded, total_size);
             }
         }
     }
     
-    // Return the path to the downloaded file
-    Ok(destination.to_string())
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to write to file: {}", e))?;
+    drop(file);
+    
+    // Only a complete download takes the destination name
+    tokio::fs::rename(&partial, destination)
+        .await
+        .map_err(|e| format!("Failed to move download into place: {}", e))?;
+    
+    if let Some(app) = app_handle {
+        crate::events::flush(app);
+    }
+    
+    Ok(())
+}
+
+/// Fetch the `.sig` asset next to the package at `url`. A missing signature is not an error
+/// here: the installer refuses the package later. `token` is the one for `url`'s host, if any.
+pub async fn download_signature(url: &str, destination: &Path, token: Option<&str>) -> Result<(), String> {
+    let client = http_client(token)?;
+    let signature_path = signature::signature_path(destination);
+    // Don't let the signature of an earlier download vouch for this one
+    let _ = tokio::fs::remove_file(&signature_path).await;
+    
+    let response = client
+        .get(format!("{}.sig", url))
+        .header("User-Agent", "WAB2B-Helper-Update-System")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to download signature: {}", e))?;
+    if !response.status().is_success() {
+        println!("No signature published for {}: {}", url, response.status());
+        return Ok(());
+    }
+    
+    let signature = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to download signature: {}", e))?;
+    tokio::fs::write(&signature_path, &signature)
+        .await
+        .map_err(|e| format!("Failed to save signature: {}", e))
 }
 
 /// Parse GitHub release information from API response
@@ -197,8 +521,16 @@ fn parse_release_info(release_data: Value) -> Result<ReleaseInfo, String> {
         .as_str()
         .ok_or_else(|| "Missing tag_name in release data".to_string())?;
     
-    // Remove 'v' prefix if present
-    let version = tag_name.trim_start_matches('v').to_string();
+    // Normalise the tag, e.g. `v1.10` to `1.10.0`
+    let parsed = version::parse(tag_name)?;
+    let version = parsed.to_string();
+    
+    // Either flag makes a pre-release: GitHub's checkbox or a `-beta.1` style version
+    let channel = if release_data["prerelease"].as_bool().unwrap_or(false) || !parsed.pre.is_empty() {
+        ReleaseChannel::Beta
+    } else {
+        ReleaseChannel::Stable
+    };
     
     // Extract the release notes
     let release_notes = release_data["body"]
@@ -242,22 +574,68 @@ fn parse_release_info(release_data: Value) -> Result<ReleaseInfo, String> {
             download_url,
             size,
             sha256,
+            sha512: String::new(),
         });
     }
     
+    // Extract the release page URL
+    let html_url = release_data["html_url"]
+        .as_str()
+        .unwrap_or("")
+        .to_string();
+    
     // Create the release info
     let release_info = ReleaseInfo {
         version,
         release_notes,
         assets,
         published_at,
+        html_url,
+        manual_update: None,
+        update_available: false,
+        skipped: false,
+        // Filled in by the check, from the release notes
+        minimum_version: None,
+        update_required: false,
+        rollout_percentage: None,
+        held_back: false,
+        channel,
+        delta: None,
+        install_mode: Default::default(),
+        installer: None,
+        platform_asset: None,
     };
     
     Ok(release_info)
 }
 
-/// Select the appropriate asset for the current platform
-pub fn select_platform_asset(assets: &[Asset]) -> Option<Asset> {
+/// Extensions of release files that accompany a package but are none themselves
+const NON_PACKAGE_EXTENSIONS: [&str; 7] = [".txt", ".sha256", ".sha512", ".sig", ".asc", ".minisig", ".patch"];
+
+/// Whether the asset `name` is something that can be installed rather than a checksum,
+/// signature or patch
+fn is_package(name: &str) -> bool {
+    let name = name.to_lowercase();
+    !NON_PACKAGE_EXTENSIONS.iter().any(|extension| name.ends_with(extension))
+}
+
+/// Select the appropriate asset for the current platform. A non-empty `pattern` from the settings
+/// replaces the heuristics: the first package whose name matches it, ignoring case, is taken.
+pub fn select_platform_asset(assets: &[Asset], pattern: &str) -> Option<Asset> {
+    if !pattern.is_empty() {
+        let pattern = match RegexBuilder::new(pattern).case_insensitive(true).build() {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                println!("Invalid update asset pattern {}: {}", pattern, e);
+                return None;
+            }
+        };
+        return assets
+            .iter()
+            .find(|asset| is_package(&asset.name) && pattern.is_match(&asset.name))
+            .cloned();
+    }
+    
     // Get the current platform and architecture
     let platform = OS;
     let arch = ARCH;
@@ -265,8 +643,16 @@ pub fn select_platform_asset(assets: &[Asset]) -> Option<Asset> {
     // Define patterns for each platform
     let asset = match platform {
         "windows" => {
+            // A portable copy has no installer to run, it needs the zip with the executable.
+            // Installed copies prefer the installer they were set up with, then the other one
+            let extensions: &[&str] = match (installer::install_mode(), installer::installer_kind()) {
+                (InstallMode::Portable, _) => &[".zip"],
+                (_, Some(InstallerKind::Nsis)) => &[".exe", ".msi", ".zip"],
+                _ => &[".msi", ".exe", ".zip"],
+            };
+            
             // Look for Windows-specific assets
-            assets.iter().find(|a| {
+            extensions.iter().find_map(|extension| assets.iter().find(|a| {
                 let name = a.name.to_lowercase();
                 (name.contains("windows") || name.contains("win")) &&
                 (
@@ -274,11 +660,11 @@ pub fn select_platform_asset(assets: &[Asset]) -> Option<Asset> {
                     (arch == "x86" && (name.contains("x86") || name.contains("i686"))) ||
                     (arch == "aarch64" && (name.contains("arm64") || name.contains("aarch64")))
                 ) &&
-                (name.ends_with(".exe") || name.ends_with(".msi") || name.ends_with(".zip"))
-            })
+                name.ends_with(extension)
+            }))
         },
         "macos" => {
-            // Look for macOS-specific assets
+            // Look for macOS-specific assets; bundles are installed from the disk image
             assets.iter().find(|a| {
                 let name = a.name.to_lowercase();
                 (name.contains("macos") || name.contains("mac") || name.contains("darwin")) &&
@@ -286,20 +672,27 @@ pub fn select_platform_asset(assets: &[Asset]) -> Option<Asset> {
                     (arch == "x86_64" && (name.contains("x64") || name.contains("amd64") || !name.contains("arm"))) ||
                     (arch == "aarch64" && (name.contains("arm64") || name.contains("aarch64") || name.contains("m1")))
                 ) &&
-                (name.ends_with(".dmg") || name.ends_with(".pkg") || name.ends_with(".zip"))
+                name.ends_with(".dmg")
             })
         },
         "linux" => {
-            // Look for Linux-specific assets
+            // An AppImage is replaced by an AppImage and a package by a package of the same kind;
+            // a copy no package owns takes the AppImage, which runs from anywhere
+            let extension = match installer::installer_kind() {
+                Some(InstallerKind::Deb) => ".deb",
+                Some(InstallerKind::Rpm) => ".rpm",
+                _ => ".appimage",
+            };
+            
+            // The extensions are Linux only, so the name needn't say "linux"
             assets.iter().find(|a| {
                 let name = a.name.to_lowercase();
-                name.contains("linux") &&
                 (
                     (arch == "x86_64" && (name.contains("x64") || name.contains("amd64") || name.contains("x86_64"))) ||
                     (arch == "x86" && (name.contains("x86") || name.contains("i686"))) ||
                     (arch == "aarch64" && (name.contains("arm64") || name.contains("aarch64")))
                 ) &&
-                (name.ends_with(".AppImage") || name.ends_with(".deb") || name.ends_with(".rpm") || name.ends_with(".tar.gz"))
+                name.ends_with(extension)
             })
         },
         _ => None,
@@ -309,6 +702,73 @@ pub fn select_platform_asset(assets: &[Asset]) -> Option<Asset> {
     asset.cloned()
 }
 
+/// Patch turning `current_version` into the package `target`, published as
+/// `<target>.from-<version>.patch`
+pub fn select_patch(assets: &[Asset], target: &Asset, current_version: &str) -> Option<Asset> {
+    let prefix = format!("{}.from-", target.name.to_lowercase());
+    
+    assets
+        .iter()
+        .find(|asset| {
+            let name = asset.name.to_lowercase();
+            name.strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(".patch"))
+                .is_some_and(|from| version::compare(from, current_version).is_ok_and(|o| o.is_eq()))
+        })
+        .cloned()
+}
+
+/// Assets that come closest to the current platform, for when none matches exactly
+pub fn suggest_assets(assets: &[Asset]) -> Vec<Asset> {
+    const MAX_SUGGESTIONS: usize = 3;
+    
+    let os_keywords: &[&str] = match OS {
+        "windows" => &["windows", "win"],
+        "macos" => &["macos", "mac", "darwin", "osx"],
+        "linux" => &["linux"],
+        _ => &[],
+    };
+    let arch_keywords: &[&str] = match ARCH {
+        "x86_64" => &["x64", "amd64", "x86_64"],
+        "x86" => &["x86", "i686"],
+        "aarch64" => &["arm64", "aarch64"],
+        _ => &[],
+    };
+    let package_extensions: &[&str] = match OS {
+        "windows" => &[".exe", ".msi", ".zip"],
+        "macos" => &[".dmg", ".pkg", ".zip"],
+        "linux" => &[".appimage", ".deb", ".rpm", ".tar.gz"],
+        _ => &[],
+    };
+    
+    let mut scored: Vec<(u32, &Asset)> = assets
+        .iter()
+        .filter_map(|asset| {
+            let name = asset.name.to_lowercase();
+            
+            // Checksums, signatures and patches are never what the user wants to install
+            if !is_package(&name) {
+                return None;
+            }
+            
+            let score = 2 * os_keywords.iter().any(|k| name.contains(k)) as u32
+                + arch_keywords.iter().any(|k| name.contains(k)) as u32
+                + package_extensions.iter().any(|ext| name.ends_with(ext)) as u32;
+            
+            (score > 0).then_some((score, asset))
+        })
+        .collect();
+    
+    // Stable sort keeps the release's own order among equal scores
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, asset)| asset.clone())
+        .collect()
+}
+
 /// Parse SHA256 hash from release notes or checksums file
 fn parse_sha256_hash(release_notes: &str, asset_name: &str) -> Option<String> {
     // Look for SHA256 hash in the release notes
@@ -0,0 +1,163 @@
+/*!
+ * GitLab releases, with packages attached as release links
+ */
+
+use reqwest::header::HeaderValue;
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::updater::provider::{self, ReleaseProvider};
+use crate::updater::{github, version, Asset, ReleaseChannel, ReleaseInfo};
+
+/// Server used when the settings name none
+pub(super) const DEFAULT_BASE_URL: &str = "https://gitlab.com";
+
+/// Releases looked at per check
+const RELEASES_PER_PAGE: u32 = 30;
+
+/// Releases of a GitLab project
+pub struct GitLab {
+    /// Server URL, gitlab.com when empty
+    pub base_url: String,
+    /// Project path, e.g. `group/helper`
+    pub project: String,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabRelease {
+    tag_name: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    released_at: Option<String>,
+    /// Set for releases dated in the future
+    #[serde(default)]
+    upcoming_release: bool,
+    #[serde(default)]
+    assets: GitLabAssets,
+    #[serde(default, rename = "_links")]
+    links: GitLabReleaseLinks,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct GitLabAssets {
+    #[serde(default)]
+    links: Vec<GitLabLink>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabLink {
+    name: String,
+    url: String,
+    /// Permanent URL through the release, preferred when present
+    #[serde(default)]
+    direct_asset_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct GitLabReleaseLinks {
+    #[serde(default, rename = "self")]
+    page: Option<String>,
+}
+
+impl GitLabRelease {
+    fn into_release_info(self) -> Result<ReleaseInfo, String> {
+        let parsed = version::parse(&self.tag_name)?;
+        // GitLab has no pre-release flag, only the version says so
+        let channel = if parsed.pre.is_empty() { ReleaseChannel::Stable } else { ReleaseChannel::Beta };
+        
+        Ok(ReleaseInfo {
+            version: parsed.to_string(),
+            release_notes: self.description.unwrap_or_default(),
+            assets: self
+                .assets
+                .links
+                .into_iter()
+                .map(|link| Asset {
+                    name: link.name,
+                    download_url: link.direct_asset_url.unwrap_or(link.url),
+                    // Release links carry no size
+                    size: 0,
+                    sha256: String::new(),
+                    sha512: String::new(),
+                })
+                .collect(),
+            published_at: self.released_at.unwrap_or_default(),
+            html_url: self.links.page.unwrap_or_default(),
+            manual_update: None,
+            update_available: false,
+            skipped: false,
+            // Filled in by the check, from the release notes
+            minimum_version: None,
+            update_required: false,
+            rollout_percentage: None,
+            held_back: false,
+            channel,
+            delta: None,
+            install_mode: Default::default(),
+            installer: None,
+            platform_asset: None,
+        })
+    }
+}
+
+impl ReleaseProvider for GitLab {
+    async fn latest_release(&self, include_beta: bool) -> Result<ReleaseInfo, String> {
+        let client = Client::builder()
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        
+        let base_url = match self.base_url.trim() {
+            "" => DEFAULT_BASE_URL,
+            base_url => base_url.trim_end_matches('/'),
+        };
+        // Only the API request carries the token; release links may point at other hosts
+        let server_token = github::ServerToken::new(base_url, self.token.as_deref());
+        // The project path goes into the URL as one segment, slashes included
+        let project: String = url::form_urlencoded::byte_serialize(self.project.as_bytes()).collect();
+        let url = format!(
+            "{}/api/v4/projects/{}/releases?per_page={}",
+            base_url, project, RELEASES_PER_PAGE
+        );
+        
+        let mut request = client
+            .get(&url)
+            .header("User-Agent", "WAB2B-Helper-Update-System");
+        if let Some(token) = &self.token {
+            let mut value = HeaderValue::from_str(token)
+                .map_err(|_| "GitLab token contains invalid characters".to_string())?;
+            value.set_sensitive(true);
+            request = request.header("PRIVATE-TOKEN", value);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to send request: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "GitLab API request failed with status: {}",
+                response.status()
+            ));
+        }
+        
+        let releases = response
+            .json::<Vec<GitLabRelease>>()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+        let releases = releases
+            .into_iter()
+            .filter(|release| !release.upcoming_release)
+            .filter_map(|release| match release.into_release_info() {
+                Ok(release_info) => Some(release_info),
+                Err(e) => {
+                    println!("Skipping release: {}", e);
+                    None
+                }
+            });
+        
+        let mut release_info = provider::newest_release(releases, include_beta)?;
+        github::populate_checksums(&client, &mut release_info, server_token.as_ref()).await?;
+        Ok(release_info)
+    }
+}
@@ -4,16 +4,26 @@
 
 use std::fs::File;
 use std::io::Read;
-use sha2::{Sha256, Digest};
+use sha2::{Sha256, Sha512, Digest};
 
 /// Calculate SHA256 hash of a file
 pub fn calculate_sha256(file_path: &str) -> Result<String, String> {
+    calculate::<Sha256>(file_path)
+}
+
+/// Calculate SHA512 hash of a file
+pub fn calculate_sha512(file_path: &str) -> Result<String, String> {
+    calculate::<Sha512>(file_path)
+}
+
+/// Hash a file with `D`, as lower-case hex
+fn calculate<D: Digest>(file_path: &str) -> Result<String, String> {
     // Open the file
     let mut file = File::open(file_path)
         .map_err(|e| format!("Failed to open file: {}", e))?;
     
-    // Create a SHA256 hasher
-    let mut hasher = Sha256::new();
+    // Create the hasher
+    let mut hasher = D::new();
     
     // Read the file in chunks and update the hasher
     let mut buffer = [0; 1024 * 1024]; // 1MB buffer
@@ -37,13 +47,66 @@ pub fn calculate_sha256(file_path: &str) -> Result<String, String> {
     Ok(hash_hex)
 }
 
-/// Verify that a file matches an expected SHA256 hash
+/// Verify that a file matches an expected SHA256 or SHA512 hash, told apart by their length
 pub fn verify_file_hash(file_path: &str, expected_hash: &str) -> Result<bool, String> {
     // Calculate the hash of the file
-    let actual_hash = calculate_sha256(file_path)?;
+    let actual_hash = match expected_hash.trim().len() {
+        64 => calculate_sha256(file_path)?,
+        128 => calculate_sha512(file_path)?,
+        len => return Err(format!("Unsupported checksum of {} characters", len)),
+    };
     
     // Compare the hashes (case-insensitive)
-    let matches = actual_hash.to_lowercase() == expected_hash.to_lowercase();
+    let matches = actual_hash.to_lowercase() == expected_hash.trim().to_lowercase();
     
     Ok(matches)
-}
\ No newline at end of file
+}
+
+/// Find the SHA-256 or SHA-512 of `file_name` in the text of a checksums file.
+///
+/// Understands a bare hash (a `.sha256` or `.sha512` sidecar), `sha256sum`/`sha512sum` lines
+/// (`<hash>  <file>`, `<hash> *<file>`) and BSD-style lines (`SHA256 (<file>) = <hash>`,
+/// `SHA512 (<file>) = <hash>`). Hashes may be upper or lower case and are returned in lower
+/// case; the algorithm follows from their length.
+pub fn parse_checksum_file(text: &str, file_name: &str) -> Option<String> {
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let (hash, name) = match parse_checksum_line(line) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        
+        // A bare hash names no file, it belongs to whatever file it sits next to
+        if name.is_none_or(|name| base_name(name).eq_ignore_ascii_case(file_name)) {
+            return Some(hash);
+        }
+    }
+    
+    None
+}
+
+/// Split one checksum line into its lower-case hash and the file name, if any
+fn parse_checksum_line(line: &str) -> Option<(String, Option<&str>)> {
+    let is_hash = |s: &str| matches!(s.len(), 64 | 128) && s.chars().all(|c| c.is_ascii_hexdigit());
+    
+    // BSD style: SHA256 (file) = hash, likewise for SHA512
+    let bsd = ["SHA256 (", "SHA2-256 (", "SHA512 (", "SHA2-512 ("]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix));
+    if let Some(rest) = bsd {
+        let (name, hash) = rest.rsplit_once(") = ")?;
+        let hash = hash.trim();
+        return is_hash(hash).then(|| (hash.to_ascii_lowercase(), Some(name)));
+    }
+    
+    // GNU style: hash  file, or hash *file for binary mode
+    let (hash, name) = match line.split_once(char::is_whitespace) {
+        Some((hash, name)) => (hash, Some(name.trim_start().trim_start_matches('*'))),
+        None => (line, None),
+    };
+    is_hash(hash).then(|| (hash.to_ascii_lowercase(), name.filter(|n| !n.is_empty())))
+}
+
+/// File name without any directory a pipeline may have prefixed
+fn base_name(name: &str) -> &str {
+    name.trim().rsplit(['/', '\\']).next().unwrap_or(name)
+}
@@ -3,33 +3,241 @@
  */
 
 use std::fs;
-use std::path::Path;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use tauri::AppHandle;
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager};
 use std::env;
 use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::settings::{BackupRetention, InstallerUi};
+use crate::updater::artifacts::{self, ArtifactKind, CleanupReport};
+use crate::updater::signature;
 
-/// Install an update and restart the application
-pub fn install_update(app: &AppHandle, update_path: &str) -> Result<bool, String> {
-    // Get the path to the current executable
-    let current_exe = env::current_exe()
-        .map_err(|e| format!("Failed to get current executable path: {}", e))?;
+/// Set to anything but `0` to make every install a dry run
+const DRY_RUN_VAR: &str = "WAB2B_UPDATE_DRY_RUN";
+
+/// Subject name of the certificate that Windows packages are signed with, embedded at build time
+#[cfg(target_os = "windows")]
+const PUBLISHER: Option<&str> = option_env!("WAB2B_UPDATE_PUBLISHER");
+
+/// How the running copy of the application was put on this machine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallMode {
+    /// Set up by an installer
+    #[default]
+    Installed,
+    /// Unpacked from a zip, e.g. onto a USB stick or a shared folder; updated from the zip asset
+    Portable,
+    /// Running from an AppImage on Linux, which is replaced by the new AppImage
+    AppImage,
+}
+
+/// Installer the running copy was set up with, and the package kind it prefers for updates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallerKind {
+    /// Windows Installer package, run with msiexec
+    Msi,
+    /// NSIS setup executable
+    Nsis,
+    /// Debian package, installed with dpkg through pkexec
+    Deb,
+    /// RPM package, installed with rpm through pkexec
+    Rpm,
+}
+
+/// What a downloaded update turned out to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PackageKind {
+    /// The new executable itself
+    Executable,
+    /// A zip holding the new executable
+    Zip,
+    /// An installer that replaces the application once it has exited
+    Installer(InstallerKind),
+    /// A macOS disk image holding the new application bundle
+    DiskImage,
+}
+
+/// What to do when the application is also running in another user session
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OtherSessionAction {
+    /// Don't install, report the other sessions so the user can decide
+    #[default]
+    Ask,
+    /// Replace the executable during the next reboot
+    ScheduleOnReboot,
+}
+
+/// How an update was applied
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallStrategy {
+    /// Installed now, the application restarts
+    Restart,
+    /// Installed by Windows during the next reboot
+    ScheduledOnReboot,
+    /// Not installed because other sessions use the application
+    Deferred,
+    /// Handed to the MSI or NSIS installer. The application exits for an NSIS setup right away,
+    /// for an MSI package once it has installed; a failed MSI is reported as an updater event
+    Installer,
+}
+
+/// Outcome of an install request
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallReport {
+    pub strategy: InstallStrategy,
+    /// IDs of other user sessions running the application
+    pub other_sessions: Vec<u32>,
+    /// Whether the update was only checked, with `strategy` and `actions` telling what would
+    /// have happened
+    pub dry_run: bool,
+    /// Steps a dry run skipped
+    pub actions: Vec<String>,
+}
+
+/// Whether the environment asks for dry runs, for testing the update pipeline end to end
+pub fn dry_run_requested() -> bool {
+    env::var_os(DRY_RUN_VAR).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Install an update and restart the application, or with `restart` unset just let the
+/// update land once the application, which is exiting already, is gone. A `dry_run` does all
+/// the checks but stops before anything is replaced or run.
+pub fn install_update(
+    app: &AppHandle,
+    update_path: &str,
+    on_other_sessions: OtherSessionAction,
+    ui: InstallerUi,
+    restart: bool,
+    dry_run: bool,
+) -> Result<InstallReport, String> {
+    // Nothing replaces the executable unless the release key signed the update
+    let package = Path::new(update_path);
+    signature::verify_update(package)?;
+    
+    // Get the path to the current executable, or the AppImage it runs from
+    let current_exe = application_path()?;
+    
+    let kind = package_kind(package)?;
+    if install_mode() == InstallMode::Portable && kind != PackageKind::Zip {
+        return Err("A portable copy can only be updated from the zip package".to_string());
+    }
+    if install_mode() == InstallMode::AppImage && kind != PackageKind::Executable {
+        return Err("An AppImage can only be updated from the AppImage package".to_string());
+    }
+    // Copying a bare file over the executable would break the bundle and its signature
+    if cfg!(target_os = "macos") && kind != PackageKind::DiskImage {
+        return Err("The application can only be updated from the disk image on macOS".to_string());
+    }
+    
+    // A zip carries the new executable, which replaces ours like a bare executable would
+    let update_path = match kind {
+        PackageKind::Zip => extract_executable(app, package, &current_exe)?,
+        _ => package.to_path_buf(),
+    };
+    
+    // Whatever gets executed must also carry our Authenticode signature
+    verify_authenticode(&update_path)?;
+    
+    // Another user's instance keeps the executable locked until they quit or sign out
+    let other_sessions = other_sessions(&current_exe)?;
+    if !other_sessions.is_empty() {
+        println!("Application is running in other sessions: {:?}", other_sessions);
+        
+        let strategy = match on_other_sessions {
+            OtherSessionAction::Ask => InstallStrategy::Deferred,
+            OtherSessionAction::ScheduleOnReboot if matches!(kind, PackageKind::Installer(_)) => {
+                return Err("An installer can't be scheduled for the next reboot; close the application in the other sessions first".to_string());
+            }
+            OtherSessionAction::ScheduleOnReboot if dry_run => {
+                let actions = vec![
+                    format!("Back up {}", current_exe.display()),
+                    format!("Replace {} with {} during the next reboot", current_exe.display(), update_path.display()),
+                ];
+                return Ok(dry_run_report(InstallStrategy::ScheduledOnReboot, other_sessions, actions));
+            }
+            OtherSessionAction::ScheduleOnReboot => {
+                backup_current_version(app, &current_exe)?;
+                schedule_on_reboot(&current_exe, &update_path)?;
+                InstallStrategy::ScheduledOnReboot
+            }
+        };
+        
+        return Ok(InstallReport {
+            strategy,
+            other_sessions,
+            dry_run,
+            actions: Vec::new(),
+        });
+    }
+    
+    // Everything so far only checked the update
+    if dry_run {
+        let strategy = match kind {
+            PackageKind::Installer(_) => InstallStrategy::Installer,
+            _ => InstallStrategy::Restart,
+        };
+        let actions = planned_actions(kind, package, &update_path, &current_exe, ui, restart);
+        return Ok(dry_run_report(strategy, other_sessions, actions));
+    }
+    
+    // A bundle is swapped as a whole, there is no single executable to back up
+    if kind == PackageKind::DiskImage {
+        replace_bundle(package, &current_exe, restart)?;
+        if restart {
+            app.exit(0);
+        }
+        return Ok(InstallReport {
+            strategy: InstallStrategy::Restart,
+            other_sessions,
+            dry_run,
+            actions: Vec::new(),
+        });
+    }
     
     // Create a backup of the current version
-    let backup_path = backup_current_version(&current_exe)?;
+    let backup_path = backup_current_version(app, &current_exe)?;
     
-    // Get the update path as a Path
-    let update_path = Path::new(update_path);
+    // An installer does the replacing itself, it only has to wait for us to exit
+    if let PackageKind::Installer(installer) = kind {
+        run_installer(app, package, installer, ui, restart)?;
+        enforce_retention(app, &current_exe);
+        return Ok(InstallReport {
+            strategy: InstallStrategy::Installer,
+            other_sessions,
+            dry_run,
+            actions: Vec::new(),
+        });
+    }
     
     // Replace the current application with the update
-    match replace_application(&current_exe, update_path) {
+    match replace_application(app, &current_exe, &update_path, restart) {
         Ok(_) => {
+            // The backup just made is the newest, so it always survives
+            enforce_retention(app, &current_exe);
+            
             // Restart the application
-            restart_application(app)?;
-            Ok(true)
+            if restart {
+                restart_application(app)?;
+            }
+            Ok(InstallReport {
+                strategy: InstallStrategy::Restart,
+                other_sessions,
+                dry_run,
+                actions: Vec::new(),
+            })
         },
         Err(e) => {
             // If replacement fails, try to restore from backup
+            // Don't leave a half-written update script behind
+            let _ = artifacts::cleanup(app, Some(ArtifactKind::Script));
+            
             if let Err(restore_err) = fs::copy(&backup_path, &current_exe) {
                 return Err(format!(
                     "Update failed and backup restoration also failed: {} (Backup error: {})",
@@ -42,8 +250,938 @@ pub fn install_update(app: &AppHandle, update_path: &str) -> Result<bool, String
     }
 }
 
+/// Report of a dry run, logging the skipped `actions`
+fn dry_run_report(strategy: InstallStrategy, other_sessions: Vec<u32>, actions: Vec<String>) -> InstallReport {
+    for action in &actions {
+        println!("Dry run, skipped: {}", action);
+    }
+    
+    InstallReport {
+        strategy,
+        other_sessions,
+        dry_run: true,
+        actions,
+    }
+}
+
+/// What installing a package of `kind` would do
+fn planned_actions(
+    kind: PackageKind,
+    package: &Path,
+    update_path: &Path,
+    app_path: &Path,
+    ui: InstallerUi,
+    restart: bool,
+) -> Vec<String> {
+    let mut actions = Vec::new();
+    match kind {
+        PackageKind::DiskImage => {
+            actions.push(format!("Mount {} and copy its app bundle over the one of {}", package.display(), app_path.display()));
+        }
+        PackageKind::Installer(installer) => {
+            actions.push(format!("Back up {}", app_path.display()));
+            actions.push(format!("Run the {:?} installer {} with the {:?} UI", installer, package.display(), ui));
+        }
+        PackageKind::Executable | PackageKind::Zip => {
+            actions.push(format!("Back up {}", app_path.display()));
+            actions.push(format!("Replace {} with {}", app_path.display(), update_path.display()));
+        }
+    }
+    if restart {
+        actions.push("Restart the application".to_string());
+    }
+    
+    actions
+}
+
+/// The file the application runs from: the AppImage rather than the executable mounted from it,
+/// otherwise the running executable
+pub(crate) fn application_path() -> Result<PathBuf, String> {
+    if install_mode() == InstallMode::AppImage {
+        if let Some(app_image) = env::var_os("APPIMAGE") {
+            return Ok(PathBuf::from(app_image));
+        }
+    }
+    
+    env::current_exe()
+        .map_err(|e| format!("Failed to get current executable path: {}", e))
+}
+
+/// Install mode of the running executable, detected on first use
+pub fn install_mode() -> InstallMode {
+    static MODE: OnceLock<InstallMode> = OnceLock::new();
+    
+    *MODE.get_or_init(|| {
+        let mode = env::current_exe()
+            .map(|current_exe| detect_install_mode(&current_exe))
+            .unwrap_or_default();
+        println!("Install mode: {:?}", mode);
+        mode
+    })
+}
+
+/// A copy is installed when it lives under Program Files or in the install location of an
+/// uninstall entry, which covers per-user installs as well; anything else is portable
+#[cfg(target_os = "windows")]
+fn detect_install_mode(app_path: &Path) -> InstallMode {
+    let normalize = |path: &Path| PathBuf::from(path.to_string_lossy().to_lowercase().trim_end_matches('\\'));
+    let app_dir = match app_path.parent() {
+        Some(app_dir) => normalize(app_dir),
+        None => return InstallMode::Installed,
+    };
+    
+    let program_files = ["ProgramFiles", "ProgramFiles(x86)", "ProgramW6432"]
+        .iter()
+        .filter_map(|var| env::var(var).ok());
+    let installed = program_files
+        .chain(uninstall_locations())
+        .any(|dir| !dir.is_empty() && app_dir.starts_with(normalize(Path::new(&dir))));
+    
+    if installed {
+        InstallMode::Installed
+    } else {
+        InstallMode::Portable
+    }
+}
+
+/// The AppImage runtime tells the application where its image is; anything else is updated by
+/// its package manager, or in place when no package owns it
+#[cfg(not(target_os = "windows"))]
+fn detect_install_mode(_app_path: &Path) -> InstallMode {
+    if cfg!(target_os = "linux") && env::var_os("APPIMAGE").is_some() {
+        InstallMode::AppImage
+    } else {
+        InstallMode::Installed
+    }
+}
+
+/// Installer or package manager the running copy was set up with; none for portable copies,
+/// AppImages and copies no package owns
+pub fn installer_kind() -> Option<InstallerKind> {
+    if install_mode() != InstallMode::Installed {
+        return None;
+    }
+    
+    let current_exe = env::current_exe().ok()?;
+    detect_installer_kind(&current_exe)
+}
+
+/// The NSIS installer leaves its uninstaller next to the executable, msiexec keeps it elsewhere
+#[cfg(target_os = "windows")]
+fn detect_installer_kind(app_path: &Path) -> Option<InstallerKind> {
+    let uninstaller = app_path.parent()?.join("uninstall.exe");
+    if uninstaller.is_file() {
+        Some(InstallerKind::Nsis)
+    } else {
+        Some(InstallerKind::Msi)
+    }
+}
+
+/// Ask dpkg, then rpm, whether one of their packages owns the executable
+#[cfg(target_os = "linux")]
+fn detect_installer_kind(app_path: &Path) -> Option<InstallerKind> {
+    let owns = |program: &str, flag: &str| {
+        Command::new(program)
+            .arg(flag)
+            .arg(app_path)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    };
+    
+    if owns("dpkg", "-S") {
+        Some(InstallerKind::Deb)
+    } else if owns("rpm", "-qf") {
+        Some(InstallerKind::Rpm)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn detect_installer_kind(_app_path: &Path) -> Option<InstallerKind> {
+    None
+}
+
+/// `InstallLocation` of every uninstall entry, machine-wide (both registry views) and per user
+#[cfg(target_os = "windows")]
+fn uninstall_locations() -> Vec<String> {
+    use windows_sys::Win32::Foundation::ERROR_SUCCESS;
+    use windows_sys::Win32::System::Registry::{
+        RegCloseKey, RegEnumKeyExW, RegGetValueW, RegOpenKeyExW, HKEY, HKEY_CURRENT_USER,
+        HKEY_LOCAL_MACHINE, KEY_READ, KEY_WOW64_32KEY, KEY_WOW64_64KEY, RRF_RT_REG_SZ,
+    };
+    
+    let wide = |s: &str| s.encode_utf16().chain(std::iter::once(0)).collect::<Vec<u16>>();
+    let uninstall = wide(r"Software\Microsoft\Windows\CurrentVersion\Uninstall");
+    let value = wide("InstallLocation");
+    
+    let mut locations = Vec::new();
+    for (root, view) in [
+        (HKEY_LOCAL_MACHINE, KEY_WOW64_64KEY),
+        (HKEY_LOCAL_MACHINE, KEY_WOW64_32KEY),
+        (HKEY_CURRENT_USER, 0),
+    ] {
+        let mut key: HKEY = std::ptr::null_mut();
+        if unsafe { RegOpenKeyExW(root, uninstall.as_ptr(), 0, KEY_READ | view, &mut key) } != ERROR_SUCCESS {
+            continue;
+        }
+        
+        for index in 0.. {
+            // Key names are at most 255 characters
+            let mut name = [0u16; 256];
+            let mut name_len = name.len() as u32;
+            let status = unsafe {
+                RegEnumKeyExW(
+                    key,
+                    index,
+                    name.as_mut_ptr(),
+                    &mut name_len,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                )
+            };
+            if status != ERROR_SUCCESS {
+                break;
+            }
+            
+            let mut data = [0u16; 1024];
+            let mut size = std::mem::size_of_val(&data) as u32;
+            let status = unsafe {
+                RegGetValueW(
+                    key,
+                    name.as_ptr(),
+                    value.as_ptr(),
+                    RRF_RT_REG_SZ,
+                    std::ptr::null_mut(),
+                    data.as_mut_ptr() as *mut std::ffi::c_void,
+                    &mut size,
+                )
+            };
+            if status == ERROR_SUCCESS {
+                // The size includes the terminating NUL
+                let len = (size as usize / 2).saturating_sub(1);
+                let location = String::from_utf16_lossy(&data[..len]);
+                let location = location.trim().trim_matches('"');
+                if !location.is_empty() {
+                    locations.push(location.to_string());
+                }
+            }
+        }
+        
+        unsafe { RegCloseKey(key) };
+    }
+    
+    locations
+}
+
+/// Tell the package kinds apart by content, downloads don't keep the asset's extension
+fn package_kind(path: &Path) -> Result<PackageKind, String> {
+    let mut magic = [0u8; 8];
+    let mut file = fs::File::open(path)
+        .map_err(|e| format!("Failed to open update file: {}", e))?;
+    match file.read_exact(&mut magic) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(PackageKind::Executable),
+        Err(e) => return Err(format!("Failed to read update file: {}", e)),
+    }
+    
+    if magic.starts_with(b"PK\x03\x04") {
+        return Ok(PackageKind::Zip);
+    }
+    // Debian packages are ar archives
+    if &magic == b"!<arch>\n" {
+        return Ok(PackageKind::Installer(InstallerKind::Deb));
+    }
+    if magic.starts_with(&[0xED, 0xAB, 0xEE, 0xDB]) {
+        return Ok(PackageKind::Installer(InstallerKind::Rpm));
+    }
+    // MSI packages are OLE compound files
+    if magic == [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1] {
+        return Ok(PackageKind::Installer(InstallerKind::Msi));
+    }
+    // An NSIS setup is a small executable stub followed by the installer data and its header
+    if magic.starts_with(b"MZ") {
+        let data = fs::read(path)
+            .map_err(|e| format!("Failed to read update file: {}", e))?;
+        if data.windows(12).any(|window| window == b"NullsoftInst") {
+            return Ok(PackageKind::Installer(InstallerKind::Nsis));
+        }
+    }
+    
+    // A disk image ends with its "koly" trailer
+    let len = file.metadata()
+        .map_err(|e| format!("Failed to read update file: {}", e))?
+        .len();
+    if len >= 512 {
+        let mut trailer = [0u8; 4];
+        file.seek(SeekFrom::End(-512))
+            .and_then(|_| file.read_exact(&mut trailer))
+            .map_err(|e| format!("Failed to read update file: {}", e))?;
+        if &trailer == b"koly" {
+            return Ok(PackageKind::DiskImage);
+        }
+    }
+    
+    Ok(PackageKind::Executable)
+}
+
+/// Command line switch selecting `ui`; the interactive wizard needs none
+#[cfg(target_os = "windows")]
+fn installer_ui_flag(installer: InstallerKind, ui: InstallerUi) -> Option<&'static str> {
+    match (installer, ui) {
+        (InstallerKind::Msi, InstallerUi::Silent) => Some("/quiet"),
+        (InstallerKind::Msi, InstallerUi::Passive) => Some("/passive"),
+        (InstallerKind::Nsis, InstallerUi::Silent) => Some("/S"),
+        // Tauri's NSIS template shows progress without questions with /P
+        (InstallerKind::Nsis, InstallerUi::Passive) => Some("/P"),
+        (_, InstallerUi::Interactive) => None,
+        // Package managers run without a UI of their own
+        (InstallerKind::Deb | InstallerKind::Rpm, _) => None,
+    }
+}
+
+/// Start the installer or package manager for `package`
+fn run_installer(app: &AppHandle, package: &Path, installer: InstallerKind, ui: InstallerUi, restart: bool) -> Result<(), String> {
+    match installer {
+        InstallerKind::Msi | InstallerKind::Nsis => run_windows_installer(app, package, installer, ui, restart),
+        InstallerKind::Deb | InstallerKind::Rpm => run_linux_package(app, package, installer, restart),
+    }
+}
+
+/// Start the MSI or NSIS installer in `package`. With `restart` an NSIS setup gets the application
+/// out of its way at once, while an MSI package is watched and the application restarts once it
+/// succeeds; otherwise the application is exiting already.
+#[cfg(target_os = "windows")]
+fn run_windows_installer(app: &AppHandle, package: &Path, installer: InstallerKind, ui: InstallerUi, restart: bool) -> Result<(), String> {
+    // msiexec and Windows go by the extension, which the download doesn't have
+    let extension = match installer {
+        InstallerKind::Msi => "msi",
+        _ => "exe",
+    };
+    let staged = package.with_extension(extension);
+    if staged != package {
+        artifacts::register(app, &staged, ArtifactKind::Package)?;
+        fs::copy(package, &staged)
+            .map_err(|e| format!("Failed to stage installer: {}", e))?;
+    }
+    
+    // Unregistered, so cleanups keep it until abandoned; it is what support asks for when an install fails
+    let log_path = staged.with_extension("log");
+    let mut command = match installer {
+        InstallerKind::Msi => {
+            let mut command = Command::new("msiexec");
+            command.arg("/i").arg(&staged).arg("/l*v").arg(&log_path);
+            command
+        }
+        _ => Command::new(&staged),
+    };
+    command.args(installer_ui_flag(installer, ui));
+    let child = command
+        .spawn()
+        .map_err(|e| format!("Failed to start installer: {}", e))?;
+    
+    match installer {
+        _ if !restart => {}
+        InstallerKind::Msi => watch_msiexec(app.clone(), child, log_path),
+        _ => app.exit(0),
+    }
+    
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn run_windows_installer(_app: &AppHandle, _package: &Path, _installer: InstallerKind, _ui: InstallerUi, _restart: bool) -> Result<(), String> {
+    Err("MSI and NSIS installers can only be run on Windows".to_string())
+}
+
+/// Install the deb or rpm `package` as root through pkexec, which asks for the password with the
+/// desktop's polkit agent. A package can be upgraded under the running application, so with
+/// `restart` the package manager is watched and the application restarts once it succeeds;
+/// otherwise the application is exiting already.
+#[cfg(target_os = "linux")]
+fn run_linux_package(app: &AppHandle, package: &Path, installer: InstallerKind, restart: bool) -> Result<(), String> {
+    // Unregistered like the msiexec log, for the same reason
+    let log_path = package.with_extension("log");
+    let log = fs::File::create(&log_path)
+        .map_err(|e| format!("Failed to create installation log: {}", e))?;
+    let log_errors = log.try_clone()
+        .map_err(|e| format!("Failed to create installation log: {}", e))?;
+    
+    let mut command = Command::new("pkexec");
+    match installer {
+        InstallerKind::Rpm => command.args(["rpm", "-U"]),
+        _ => command.args(["dpkg", "-i"]),
+    };
+    let child = command
+        .arg(package)
+        .stdout(log)
+        .stderr(log_errors)
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => "Installing the update package needs pkexec (polkit)".to_string(),
+            _ => format!("Failed to start installer: {}", e),
+        })?;
+    
+    if restart {
+        watch_package_manager(app.clone(), child, log_path);
+    }
+    
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run_linux_package(_app: &AppHandle, _package: &Path, _installer: InstallerKind, _restart: bool) -> Result<(), String> {
+    Err("Deb and RPM packages can only be installed on Linux".to_string())
+}
+
+/// Wait for pkexec in the background: restart into the update once the package is installed,
+/// report the failure and keep running when it isn't
+#[cfg(target_os = "linux")]
+fn watch_package_manager(app: AppHandle, mut child: std::process::Child, log_path: PathBuf) {
+    use crate::updater::events::{self, FailureCode, UpdaterEvent};
+    
+    std::thread::spawn(move || {
+        let code = match child.wait() {
+            Ok(status) => status.code().unwrap_or(-1),
+            Err(e) => {
+                println!("Failed to wait for the package manager: {}", e);
+                return;
+            }
+        };
+        
+        if code == 0 {
+            app.restart();
+        }
+        
+        // pkexec itself exits with 126 when the password prompt is dismissed or refused
+        let message = match code {
+            126 | 127 => "Installing the update was not authorized".to_string(),
+            code => format!("The package manager failed with exit code {}; the installation log has the details", code),
+        };
+        let log_path = log_path.to_string_lossy().to_string();
+        println!("Package installation failed with {}: {} (log: {})", code, message, log_path);
+        events::emit(&app, UpdaterEvent::Failed {
+            code: FailureCode::Install,
+            message,
+            exit_code: Some(code),
+            log_path: Some(log_path),
+        });
+    });
+}
+
+/// Wait for msiexec in the background: restart into the update when it succeeds, report the
+/// failure and keep running when it doesn't
+#[cfg(target_os = "windows")]
+fn watch_msiexec(app: AppHandle, mut child: std::process::Child, log_path: PathBuf) {
+    use crate::updater::events::{self, FailureCode, UpdaterEvent};
+    
+    std::thread::spawn(move || {
+        let code = match child.wait() {
+            Ok(status) => status.code().unwrap_or(-1),
+            Err(e) => {
+                println!("Failed to wait for msiexec: {}", e);
+                return;
+            }
+        };
+        
+        match code {
+            0 => app.restart(),
+            1641 | 3010 => {
+                println!("Update installed, Windows has to restart to finish it");
+                app.restart()
+            }
+            code => {
+                let message = msiexec_error(code);
+                let log_path = log_path.to_string_lossy().to_string();
+                println!("msiexec failed with {}: {} (log: {})", code, message, log_path);
+                events::emit(&app, UpdaterEvent::Failed {
+                    code: FailureCode::Install,
+                    message,
+                    exit_code: Some(code),
+                    log_path: Some(log_path),
+                });
+            }
+        }
+    });
+}
+
+/// Replace the application bundle `app_exe` belongs to with the one in the disk image `package`.
+/// The new bundle is copied next to the old one and swapped in; when the folder isn't writable for
+/// the user, macOS asks for an administrator password. With `restart` the new bundle is opened
+/// once this process has exited.
+#[cfg(target_os = "macos")]
+fn replace_bundle(package: &Path, app_exe: &Path, restart: bool) -> Result<(), String> {
+    let bundle = bundle_path(app_exe)?;
+    let bundle_name = bundle.file_name()
+        .ok_or_else(|| "Failed to get the application bundle name".to_string())?
+        .to_string_lossy()
+        .to_string();
+    
+    let mount_point = package.with_extension("mount");
+    fs::create_dir_all(&mount_point)
+        .map_err(|e| format!("Failed to create mount point: {}", e))?;
+    let status = Command::new("hdiutil")
+        .args(["attach", "-nobrowse", "-readonly", "-noautoopen", "-mountpoint"])
+        .arg(&mount_point)
+        .arg(package)
+        .status()
+        .map_err(|e| format!("Failed to mount the update: {}", e))?;
+    if !status.success() {
+        let _ = fs::remove_dir(&mount_point);
+        return Err("The update disk image could not be mounted".to_string());
+    }
+    
+    let result = swap_bundle(&mount_point, &bundle, &bundle_name);
+    
+    let _ = Command::new("hdiutil")
+        .args(["detach", "-quiet"])
+        .arg(&mount_point)
+        .status();
+    let _ = fs::remove_dir(&mount_point);
+    result?;
+    
+    if restart {
+        // Only one instance may run, so the new one has to wait for this one to be gone
+        let script = format!(
+            "while kill -0 {} 2>/dev/null; do sleep 1; done; open {}",
+            std::process::id(),
+            shell_quote(&bundle)
+        );
+        Command::new("sh")
+            .args(["-c", &script])
+            .spawn()
+            .map_err(|e| format!("Failed to restart application: {}", e))?;
+    }
+    
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn replace_bundle(_package: &Path, _app_exe: &Path, _restart: bool) -> Result<(), String> {
+    Err("Disk images can only be installed on macOS".to_string())
+}
+
+/// The `.app` bundle holding `app_exe`. A quarantined download runs translocated from a read-only
+/// copy, so the bundle of the same name in Applications is updated instead.
+#[cfg(target_os = "macos")]
+fn bundle_path(app_exe: &Path) -> Result<PathBuf, String> {
+    let bundle = app_exe
+        .ancestors()
+        .find(|path| path.extension().is_some_and(|extension| extension == "app"))
+        .ok_or_else(|| "The application is not running from an app bundle".to_string())?;
+    
+    if !bundle.to_string_lossy().contains("/AppTranslocation/") {
+        return Ok(bundle.to_path_buf());
+    }
+    
+    let installed = Path::new("/Applications").join(bundle.file_name().unwrap_or_default());
+    if installed.is_dir() {
+        Ok(installed)
+    } else {
+        Err("The application runs from a quarantined location; move it to the Applications folder and try again".to_string())
+    }
+}
+
+/// Copy the bundle named `bundle_name` (or the only bundle) from the mounted image over `bundle`
+#[cfg(target_os = "macos")]
+fn swap_bundle(mount_point: &Path, bundle: &Path, bundle_name: &str) -> Result<(), String> {
+    let bundles: Vec<PathBuf> = fs::read_dir(mount_point)
+        .map_err(|e| format!("Failed to read the update disk image: {}", e))?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "app") && path.is_dir())
+        .collect();
+    let source = bundles
+        .iter()
+        .find(|path| path.file_name().is_some_and(|name| name.to_string_lossy() == bundle_name))
+        .or(match bundles.as_slice() {
+            [only] => Some(only),
+            _ => None,
+        })
+        .ok_or_else(|| format!("The update disk image contains no {}", bundle_name))?;
+    
+    let parent = bundle.parent()
+        .ok_or_else(|| "Failed to get parent directory".to_string())?;
+    let new_bundle = parent.join(format!(".{}.new", bundle_name));
+    let old_bundle = parent.join(format!(".{}.old", bundle_name));
+    
+    // ditto keeps symlinks, permissions and extended attributes, and with them the code signature.
+    // The old bundle is put back when the new one can't take its place.
+    let script = format!(
+        "rm -rf {new} {old} && ditto {source} {new} && mv {bundle} {old} && \
+         {{ mv {new} {bundle} || {{ mv {old} {bundle}; exit 1; }}; }} && rm -rf {old}",
+        new = shell_quote(&new_bundle),
+        old = shell_quote(&old_bundle),
+        source = shell_quote(source),
+        bundle = shell_quote(bundle),
+    );
+    
+    let status = Command::new("sh")
+        .args(["-c", &script])
+        .status()
+        .map_err(|e| format!("Failed to replace application: {}", e))?;
+    if status.success() {
+        return Ok(());
+    }
+    
+    // Most likely a folder the user can't write to, such as Applications for a standard user
+    let apple_script = format!(
+        "do shell script \"{}\" with administrator privileges",
+        script.replace('\\', "\\\\").replace('"', "\\\"")
+    );
+    let status = Command::new("osascript")
+        .args(["-e", &apple_script])
+        .status()
+        .map_err(|e| format!("Failed to replace application: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Failed to replace {}; the update was not installed", bundle.display()))
+    }
+}
+
+/// `path` in single quotes for `sh`
+#[cfg(target_os = "macos")]
+fn shell_quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', "'\\''"))
+}
+
+/// Check the Authenticode signature of the executable or installer at `path`: the chain has to
+/// be trusted by Windows and the signing certificate issued to `PUBLISHER`
+#[cfg(target_os = "windows")]
+fn verify_authenticode(path: &Path) -> Result<(), String> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use windows_sys::Win32::Foundation::{
+        CERT_E_EXPIRED, CERT_E_UNTRUSTEDROOT, TRUST_E_BAD_DIGEST, TRUST_E_EXPLICIT_DISTRUST,
+        TRUST_E_NOSIGNATURE,
+    };
+    use windows_sys::Win32::Security::Cryptography::{CertGetNameStringW, CERT_NAME_SIMPLE_DISPLAY_TYPE};
+    use windows_sys::Win32::Security::WinTrust::{
+        WTHelperGetProvSignerFromChain, WTHelperProvDataFromStateData, WinVerifyTrust,
+        WINTRUST_ACTION_GENERIC_VERIFY_V2, WINTRUST_DATA, WINTRUST_DATA_0, WINTRUST_FILE_INFO,
+        WTD_CHOICE_FILE, WTD_REVOKE_NONE, WTD_STATEACTION_CLOSE, WTD_STATEACTION_VERIFY, WTD_UI_NONE,
+    };
+    
+    let publisher = PUBLISHER
+        .ok_or_else(|| "This build has no expected update publisher; please install the update manually".to_string())?;
+    
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut file_info = WINTRUST_FILE_INFO {
+        cbStruct: std::mem::size_of::<WINTRUST_FILE_INFO>() as u32,
+        pcwszFilePath: wide_path.as_ptr(),
+        hFile: ptr::null_mut(),
+        pgKnownSubject: ptr::null_mut(),
+    };
+    // Revocation isn't checked: the package is already pinned by its hash and minisign signature,
+    // and a staged update may well be installed without a network connection
+    let mut trust_data = WINTRUST_DATA {
+        cbStruct: std::mem::size_of::<WINTRUST_DATA>() as u32,
+        pPolicyCallbackData: ptr::null_mut(),
+        pSIPClientData: ptr::null_mut(),
+        dwUIChoice: WTD_UI_NONE,
+        fdwRevocationChecks: WTD_REVOKE_NONE,
+        dwUnionChoice: WTD_CHOICE_FILE,
+        Anonymous: WINTRUST_DATA_0 { pFile: &mut file_info },
+        dwStateAction: WTD_STATEACTION_VERIFY,
+        hWVTStateData: ptr::null_mut(),
+        pwszURLReference: ptr::null_mut(),
+        dwProvFlags: 0,
+        dwUIContext: 0,
+        pSignatureSettings: ptr::null_mut(),
+    };
+    let mut action = WINTRUST_ACTION_GENERIC_VERIFY_V2;
+    
+    let status = unsafe {
+        WinVerifyTrust(ptr::null_mut(), &mut action, &mut trust_data as *mut _ as *mut _)
+    };
+    
+    // The signer is read from the state data, which has to be released whatever the outcome
+    let signer = if status == 0 {
+        unsafe {
+            let provider_data = WTHelperProvDataFromStateData(trust_data.hWVTStateData);
+            let signer = if provider_data.is_null() {
+                ptr::null_mut()
+            } else {
+                WTHelperGetProvSignerFromChain(provider_data, 0, 0, 0)
+            };
+            if signer.is_null() || (*signer).csCertChain == 0 {
+                None
+            } else {
+                let cert = (*(*signer).pasCertChain).pCert;
+                let mut name = [0u16; 256];
+                let len = CertGetNameStringW(
+                    cert,
+                    CERT_NAME_SIMPLE_DISPLAY_TYPE,
+                    0,
+                    ptr::null(),
+                    name.as_mut_ptr(),
+                    name.len() as u32,
+                );
+                // The length includes the terminating null
+                Some(String::from_utf16_lossy(&name[..(len as usize).saturating_sub(1)]))
+            }
+        }
+    } else {
+        None
+    };
+    
+    trust_data.dwStateAction = WTD_STATEACTION_CLOSE;
+    unsafe {
+        WinVerifyTrust(ptr::null_mut(), &mut action, &mut trust_data as *mut _ as *mut _);
+    }
+    
+    match status {
+        0 => {}
+        TRUST_E_NOSIGNATURE => return Err("The update is not signed; it was not installed".to_string()),
+        TRUST_E_BAD_DIGEST => return Err("The update was modified after signing; it was not installed".to_string()),
+        CERT_E_EXPIRED => return Err("The update's signing certificate has expired; it was not installed".to_string()),
+        CERT_E_UNTRUSTEDROOT | TRUST_E_EXPLICIT_DISTRUST => {
+            return Err("The update's signing certificate is not trusted; it was not installed".to_string());
+        }
+        status => {
+            return Err(format!(
+                "The update's signature could not be verified (0x{:08X}); it was not installed",
+                status as u32
+            ));
+        }
+    }
+    
+    match signer {
+        Some(signer) if signer == publisher => Ok(()),
+        Some(signer) => Err(format!(
+            "The update is signed by \"{}\" instead of \"{}\"; it was not installed",
+            signer, publisher
+        )),
+        None => Err("The update's signer could not be determined; it was not installed".to_string()),
+    }
+}
+
+/// Authenticode is Windows only; elsewhere the minisign signature stands alone
+#[cfg(not(target_os = "windows"))]
+fn verify_authenticode(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+/// Explanation of a failing msiexec exit code
+#[cfg(target_os = "windows")]
+fn msiexec_error(code: i32) -> String {
+    match code {
+        1601 => "The Windows Installer service could not be started".to_string(),
+        1602 => "The update was cancelled".to_string(),
+        1603 => "The installer failed; the installation log has the details".to_string(),
+        1618 => "Another installation is in progress; try again once it has finished".to_string(),
+        1619 | 1620 => "The update package could not be opened".to_string(),
+        1625 => "Installing updates is blocked by system policy".to_string(),
+        1633 => "The update package is not meant for this version of Windows".to_string(),
+        1638 => "Another version of the helper is already installed".to_string(),
+        1925 => "Installing the update requires administrator rights".to_string(),
+        code => format!("The installer failed with exit code {}", code),
+    }
+}
+
+/// Unpack the executable named like the one at `app_path` from the zip at `package`,
+/// next to the package. On Windows a zip holding a single `.exe` under another name will do.
+fn extract_executable(app: &AppHandle, package: &Path, app_path: &Path) -> Result<PathBuf, String> {
+    let exe_name = app_path.file_name()
+        .ok_or_else(|| "Failed to get file name".to_string())?
+        .to_string_lossy()
+        .to_lowercase();
+    
+    let file = fs::File::open(package)
+        .map_err(|e| format!("Failed to open update archive: {}", e))?;
+    let mut archive = zip::ZipArchive::new(io::BufReader::new(file))
+        .map_err(|e| format!("Failed to read update archive: {}", e))?;
+    
+    let entry_name = |name: &str| name.rsplit(['/', '\\']).next().unwrap_or(name).to_lowercase();
+    let names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    let entry = names
+        .iter()
+        .find(|name| entry_name(name) == exe_name)
+        .or_else(|| {
+            let suffix = env::consts::EXE_SUFFIX;
+            let mut executables = names
+                .iter()
+                .filter(|name| !suffix.is_empty() && entry_name(name).ends_with(suffix));
+            match (executables.next(), executables.next()) {
+                (Some(name), None) => Some(name),
+                _ => None,
+            }
+        })
+        .ok_or_else(|| format!("The update archive contains no {}", exe_name))?;
+    
+    let mut destination = package.as_os_str().to_owned();
+    destination.push(".extracted");
+    let destination = PathBuf::from(destination);
+    artifacts::register(app, &destination, ArtifactKind::Package)?;
+    
+    let mut source = archive
+        .by_name(entry)
+        .map_err(|e| format!("Failed to read {} from the update archive: {}", entry, e))?;
+    let mut target = fs::File::create(&destination)
+        .map_err(|e| format!("Failed to extract update: {}", e))?;
+    io::copy(&mut source, &mut target)
+        .map_err(|e| format!("Failed to extract update: {}", e))?;
+    
+    Ok(destination)
+}
+
+/// A copy of an earlier executable kept by the installer
+#[derive(Debug, Clone, Serialize)]
+pub struct Backup {
+    /// File name in the backups directory, used to pick the backup to restore
+    pub file_name: String,
+    pub path: String,
+    /// When the backup was made, in seconds since the Unix epoch
+    pub created_at: u64,
+    pub size: u64,
+}
+
+/// Directory holding the backups, in the app data directory: the directory of the executable
+/// isn't writable for a per-machine install
+fn backup_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?
+        .join("backups"))
+}
+
+/// Backups of the executable at `app_path`, newest first
+pub fn list_backups(app: &AppHandle, app_path: &Path) -> Result<Vec<Backup>, String> {
+    let backup_dir = backup_dir(app)?;
+    let exe_name = app_path.file_name()
+        .ok_or_else(|| "Failed to get file name".to_string())?
+        .to_string_lossy()
+        .to_string();
+    
+    let entries = match fs::read_dir(&backup_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read backup directory: {}", e)),
+    };
+    
+    let mut backups: Vec<Backup> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            // <executable>.<timestamp>.bak
+            let created_at = file_name
+                .strip_prefix(&exe_name)?
+                .strip_prefix('.')?
+                .strip_suffix(".bak")?
+                .parse()
+                .ok()?;
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            
+            Some(Backup {
+                path: entry.path().to_string_lossy().to_string(),
+                file_name,
+                created_at,
+                size: metadata.len(),
+            })
+        })
+        .collect();
+    
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+    Ok(backups)
+}
+
+/// Delete the backups `retention` doesn't keep, or all of them when it is `None`
+pub fn prune_backups(
+    app: &AppHandle,
+    app_path: &Path,
+    retention: Option<&BackupRetention>,
+) -> Result<CleanupReport, String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| format!("Failed to generate timestamp: {}", e))?
+        .as_secs();
+    
+    let mut report = CleanupReport::default();
+    for (index, backup) in list_backups(app, app_path)?.into_iter().enumerate() {
+        let keep = retention.is_some_and(|retention| {
+            let within_count = retention.keep_count == 0 || index < retention.keep_count;
+            let within_age = retention.keep_days == 0
+                || now.saturating_sub(backup.created_at) <= retention.keep_days * 24 * 60 * 60;
+            within_count && within_age
+        });
+        if keep {
+            continue;
+        }
+        
+        match fs::remove_file(&backup.path) {
+            Ok(()) => {
+                report.freed_bytes += backup.size;
+                report.removed.push(backup.path);
+            }
+            Err(e) => report.failed.push((backup.path, e.to_string())),
+        }
+    }
+    
+    Ok(report)
+}
+
+/// Apply the retention policy from the settings, logging rather than failing
+fn enforce_retention(app: &AppHandle, app_path: &Path) {
+    let retention = match crate::settings::load_settings(app) {
+        Ok(settings) => settings.update_backups,
+        Err(e) => {
+            println!("Failed to load backup retention settings: {}", e);
+            return;
+        }
+    };
+    match prune_backups(app, app_path, Some(&retention)) {
+        Ok(report) if !report.removed.is_empty() => {
+            println!("Pruned {} old backup(s), {} bytes freed", report.removed.len(), report.freed_bytes);
+        }
+        Ok(_) => {}
+        Err(e) => println!("Failed to prune backups: {}", e),
+    }
+}
+
+/// Put back the backup named `file_name`, or the newest one, and restart into it.
+/// The running version is backed up first, so the rollback can itself be undone.
+pub fn rollback(app: &AppHandle, file_name: Option<&str>) -> Result<Backup, String> {
+    let current_exe = application_path()?;
+    
+    // Only backups from the listing are accepted, never an arbitrary path
+    let backups = list_backups(app, &current_exe)?;
+    let backup = match file_name {
+        Some(file_name) => backups
+            .into_iter()
+            .find(|backup| backup.file_name == file_name)
+            .ok_or_else(|| format!("Backup not found: {}", file_name))?,
+        None => backups
+            .into_iter()
+            .next()
+            .ok_or_else(|| "No backups to roll back to".to_string())?,
+    };
+    
+    // The running executable is locked for other sessions just as for an update
+    let other_sessions = other_sessions(&current_exe)?;
+    if !other_sessions.is_empty() {
+        return Err(format!(
+            "The application is running in {} other session(s); close it there first",
+            other_sessions.len()
+        ));
+    }
+    
+    backup_current_version(app, &current_exe)?;
+    // No pruning here: on Windows the backup is only copied back by the script after exit, and
+    // pruning could delete it first. The next update applies the retention policy again.
+    replace_application(app, &current_exe, Path::new(&backup.path), true)?;
+    restart_application(app)?;
+    
+    Ok(backup)
+}
+
 /// Create a backup of the current application
-fn backup_current_version(app_path: &Path) -> Result<String, String> {
+fn backup_current_version(app: &AppHandle, app_path: &Path) -> Result<String, String> {
     // Generate a timestamp for the backup file
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -51,9 +1189,7 @@ fn backup_current_version(app_path: &Path) -> Result<String, String> {
         .as_secs();
     
     // Create the backup path
-    let backup_dir = app_path.parent()
-        .ok_or_else(|| "Failed to get parent directory".to_string())?
-        .join("backups");
+    let backup_dir = backup_dir(app)?;
     
     // Create the backup directory if it doesn't exist
     fs::create_dir_all(&backup_dir)
@@ -76,8 +1212,8 @@ fn backup_current_version(app_path: &Path) -> Result<String, String> {
     Ok(backup_path.to_string_lossy().to_string())
 }
 
-/// Replace the current application with the update
-fn replace_application(app_path: &Path, update_path: &Path) -> Result<(), String> {
+/// Replace the current application with the update, starting it again afterwards with `relaunch`
+fn replace_application(app: &AppHandle, app_path: &Path, update_path: &Path, relaunch: bool) -> Result<(), String> {
     // On Windows, we can't replace a running executable directly
     // So we'll create a batch script to do it after the application exits
     #[cfg(target_os = "windows")]
@@ -88,29 +1224,38 @@ fn replace_application(app_path: &Path, update_path: &Path) -> Result<(), String
             .join("update.bat");
         
         // Write the batch file content
-        // This will wait for the process to exit, then replace the executable
+        // This will wait for the process to exit, then replace the executable.
+        // Only this session is watched, instances of other users are handled before we get here
+        let start = if relaunch {
+            format!("start \"\" \"{}\"\n", app_path.to_string_lossy())
+        } else {
+            String::new()
+        };
         let batch_content = format!(
             "@echo off\n\
              :wait\n\
              timeout /t 1 /nobreak > nul\n\
-             tasklist /fi \"imagename eq {}\" | find /i \"{}\" > nul\n\
+             tasklist /fi \"imagename eq {}\" /fi \"session eq {}\" | find /i \"{}\" > nul\n\
              if not errorlevel 1 goto wait\n\
              copy /y \"{}\" \"{}\"\n\
-             start \"\" \"{}\"\n\
+             {}\
              del \"%~f0\"\n",
             app_path.file_name().unwrap().to_string_lossy(),
+            current_session()?,
             app_path.file_name().unwrap().to_string_lossy(),
             update_path.to_string_lossy(),
             app_path.to_string_lossy(),
-            app_path.to_string_lossy()
+            start
         );
         
+        artifacts::register(app, &batch_path, ArtifactKind::Script)?;
+        
         fs::write(&batch_path, batch_content)
             .map_err(|e| format!("Failed to write update script: {}", e))?;
         
         // Start the batch file
         Command::new("cmd")
-            .args(&["/C", batch_path.to_string_lossy().as_ref()])
+            .args(["/C", batch_path.to_string_lossy().as_ref()])
             .spawn()
             .map_err(|e| format!("Failed to start update script: {}", e))?;
         
@@ -120,23 +1265,129 @@ fn replace_application(app_path: &Path, update_path: &Path) -> Result<(), String
     // On Unix systems, we can replace the executable directly
     #[cfg(not(target_os = "windows"))]
     {
-        fs::copy(update_path, app_path)
+        use std::os::unix::fs::PermissionsExt;
+        
+        // No helper script is needed, so there is nothing to track; restarting is up to the caller
+        let _ = (app, relaunch);
+        
+        // A running executable (or the AppImage it is mounted from) can't be written to, but a new
+        // file can take its name
+        let mut new_path = app_path.as_os_str().to_owned();
+        new_path.push(".new");
+        let new_path = PathBuf::from(new_path);
+        
+        fs::copy(update_path, &new_path)
             .map_err(|e| format!("Failed to replace application: {}", e))?;
         
         // Make the new executable executable
-        let mut permissions = fs::metadata(app_path)
+        let mut permissions = fs::metadata(&new_path)
             .map_err(|e| format!("Failed to get file permissions: {}", e))?
             .permissions();
         
         permissions.set_mode(0o755); // rwxr-xr-x
         
-        fs::set_permissions(app_path, permissions)
+        fs::set_permissions(&new_path, permissions)
             .map_err(|e| format!("Failed to set file permissions: {}", e))?;
         
+        fs::rename(&new_path, app_path)
+            .map_err(|e| format!("Failed to replace application: {}", e))?;
+        
         Ok(())
     }
 }
 
+/// Session ID of this process
+#[cfg(target_os = "windows")]
+fn current_session() -> Result<u32, String> {
+    use windows_sys::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+    
+    let mut session = 0;
+    if unsafe { ProcessIdToSessionId(std::process::id(), &mut session) } == 0 {
+        return Err(format!(
+            "Failed to get session ID: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    
+    Ok(session)
+}
+
+/// Sessions other than ours with a process running the application executable
+#[cfg(target_os = "windows")]
+fn other_sessions(app_path: &Path) -> Result<Vec<u32>, String> {
+    let image = app_path.file_name()
+        .ok_or_else(|| "Failed to get file name".to_string())?
+        .to_string_lossy()
+        .to_string();
+    let current = current_session()?;
+    
+    let output = Command::new("tasklist")
+        .args(["/fi", &format!("imagename eq {}", image), "/fo", "csv", "/nh"])
+        .output()
+        .map_err(|e| format!("Failed to list running processes: {}", e))?;
+    
+    // "Image Name","PID","Session Name","Session#","Mem Usage"
+    let mut sessions: Vec<u32> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split("\",\"").map(|f| f.trim_matches('"')).collect();
+            let matches_image = fields.first()?.eq_ignore_ascii_case(&image);
+            matches_image.then(|| fields.get(3)?.parse().ok()).flatten()
+        })
+        .filter(|&session| session != current)
+        .collect();
+    
+    sessions.sort_unstable();
+    sessions.dedup();
+    Ok(sessions)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn other_sessions(_app_path: &Path) -> Result<Vec<u32>, String> {
+    // Unix replaces the file without waiting, running instances keep their copy
+    Ok(Vec::new())
+}
+
+/// Have Windows replace the executable during the next reboot, before anyone can start it.
+/// Registering a pending rename needs administrator rights.
+#[cfg(target_os = "windows")]
+fn schedule_on_reboot(app_path: &Path, update_path: &Path) -> Result<(), String> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::{
+        MoveFileExW, MOVEFILE_DELAY_UNTIL_REBOOT, MOVEFILE_REPLACE_EXISTING,
+    };
+    
+    // The pending rename must not cross volumes, so stage the update next to the executable
+    let mut staged = app_path.as_os_str().to_owned();
+    staged.push(".pending");
+    fs::copy(update_path, &staged)
+        .map_err(|e| format!("Failed to stage update: {}", e))?;
+    
+    let wide = |path: &std::ffi::OsStr| path.encode_wide().chain(std::iter::once(0)).collect::<Vec<u16>>();
+    let source = wide(&staged);
+    let target = wide(app_path.as_os_str());
+    
+    let ok = unsafe {
+        MoveFileExW(
+            source.as_ptr(),
+            target.as_ptr(),
+            MOVEFILE_REPLACE_EXISTING | MOVEFILE_DELAY_UNTIL_REBOOT,
+        )
+    };
+    if ok == 0 {
+        let error = std::io::Error::last_os_error();
+        let _ = fs::remove_file(&staged);
+        return Err(format!("Failed to schedule update for next reboot: {}", error));
+    }
+    
+    Ok(())
+}
+
+#[cfg(not(target_os = "windows"))]
+fn schedule_on_reboot(_app_path: &Path, _update_path: &Path) -> Result<(), String> {
+    Err("Installing on reboot is only supported on Windows".to_string())
+}
+
 /// Restart the application
 fn restart_application(app: &AppHandle) -> Result<(), String> {
     // Get the path to the current executable
@@ -155,9 +1406,8 @@ fn restart_application(app: &AppHandle) -> Result<(), String> {
     // On Unix systems, we need to restart the application
     #[cfg(not(target_os = "windows"))]
     {
-        // Start a new instance of the application
-        let _current_exe = env::current_exe()
-            .map_err(|e| format!("Failed to get current executable path: {}", e))?;
+        // Start a new instance of the application, from the AppImage when it runs from one
+        let _current_exe = application_path()?;
         
         // Start a new instance of the application
         Command::new(_current_exe)
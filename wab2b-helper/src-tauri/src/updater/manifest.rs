@@ -0,0 +1,168 @@
+/*!
+ * Self-hosted update manifests, for networks that can't reach GitHub
+ *
+ * The manifest is JSON served over HTTPS, either a single release or `{"releases": [...]}`:
+ *
+ * ```json
+ * {
+ *   "version": "1.4.0",
+ *   "notes": "Markdown release notes",
+ *   "published_at": "2025-01-31T12:00:00Z",
+ *   "prerelease": false,
+ *   "minimum_version": "1.2.0",
+ *   "rollout_percentage": 10,
+ *   "assets": [
+ *     { "name": "wab2b-helper_1.4.0_x64-setup.exe", "url": "https://...", "size": 123, "sha256": "..." }
+ *   ]
+ * }
+ * ```
+ *
+ * An asset may carry a `sha512` instead of, or as well as, its `sha256`.
+ */
+
+use serde::Deserialize;
+
+use crate::updater::{version, Asset, ReleaseChannel, ReleaseInfo};
+
+/// Limit for fetching the manifest, which is small
+const MANIFEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Manifest {
+    Releases { releases: Vec<ManifestRelease> },
+    Single(ManifestRelease),
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestRelease {
+    version: String,
+    #[serde(default)]
+    notes: String,
+    #[serde(default)]
+    published_at: String,
+    /// Page describing the release, if there is one
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    prerelease: bool,
+    /// Oldest version that may keep running once this release is out
+    #[serde(default)]
+    minimum_version: Option<String>,
+    /// Share of installs offered the release so far, for a staged rollout
+    #[serde(default)]
+    rollout_percentage: Option<u8>,
+    #[serde(default)]
+    assets: Vec<ManifestAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestAsset {
+    name: String,
+    url: String,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    sha256: String,
+    #[serde(default)]
+    sha512: String,
+}
+
+impl ManifestRelease {
+    fn into_release_info(self) -> Result<ReleaseInfo, String> {
+        let parsed = version::parse(&self.version)?;
+        let channel = if self.prerelease || !parsed.pre.is_empty() {
+            ReleaseChannel::Beta
+        } else {
+            ReleaseChannel::Stable
+        };
+        
+        let minimum_version = self
+            .minimum_version
+            .map(|minimum| version::parse(&minimum).map(|minimum| minimum.to_string()))
+            .transpose()?;
+        
+        let rollout_percentage = self.rollout_percentage;
+        if rollout_percentage.is_some_and(|percentage| percentage > 100) {
+            return Err(format!("Rollout percentage of {} must be between 0 and 100", self.version));
+        }
+        
+        Ok(ReleaseInfo {
+            version: parsed.to_string(),
+            release_notes: self.notes,
+            assets: self
+                .assets
+                .into_iter()
+                .map(|asset| Asset {
+                    name: asset.name,
+                    download_url: asset.url,
+                    size: asset.size,
+                    sha256: asset.sha256.to_ascii_lowercase(),
+                    sha512: asset.sha512.to_ascii_lowercase(),
+                })
+                .collect(),
+            published_at: self.published_at,
+            html_url: self.url,
+            manual_update: None,
+            update_available: false,
+            skipped: false,
+            minimum_version,
+            update_required: false,
+            rollout_percentage,
+            held_back: false,
+            channel,
+            delta: None,
+            install_mode: Default::default(),
+            installer: None,
+            platform_asset: None,
+        })
+    }
+}
+
+/// Fetch the manifest at `url` and return its newest release the channel allows
+pub async fn check_latest_release(url: &str, include_beta: bool) -> Result<ReleaseInfo, String> {
+    let parsed = url::Url::parse(url).map_err(|e| format!("Invalid update manifest URL: {}", e))?;
+    if parsed.scheme() != "https" {
+        return Err("The update manifest must be served over HTTPS".to_string());
+    }
+    
+    let response = reqwest::Client::new()
+        .get(parsed)
+        .header("User-Agent", "WAB2B-Helper-Update-System")
+        .timeout(MANIFEST_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch update manifest: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Update manifest request failed with status: {}",
+            response.status()
+        ));
+    }
+    
+    let manifest = response
+        .json::<Manifest>()
+        .await
+        .map_err(|e| format!("Failed to parse update manifest: {}", e))?;
+    let releases = match manifest {
+        Manifest::Releases { releases } => releases,
+        Manifest::Single(release) => vec![release],
+    };
+    
+    let mut newest: Option<(semver::Version, ReleaseInfo)> = None;
+    for release in releases {
+        let release_info = release.into_release_info()?;
+        if release_info.channel == ReleaseChannel::Beta && !include_beta {
+            continue;
+        }
+        
+        let version = version::parse(&release_info.version)?;
+        if newest.as_ref().is_none_or(|(best, _)| version.cmp_precedence(best).is_gt()) {
+            newest = Some((version, release_info));
+        }
+    }
+    
+    newest
+        .map(|(_, release_info)| release_info)
+        .ok_or_else(|| "The update manifest lists no release for this channel".to_string())
+}
@@ -3,15 +3,43 @@
  * Rust backend implementation
  */
 
+use std::sync::Mutex;
 use serde::{Deserialize, Serialize};
-use tauri::command;
+use tauri::{command, Emitter};
 
+use crate::permissions::{authorize_command, Scope};
+use crate::settings::ReleaseProviderKind;
+use events::{FailureCode, UpdaterEvent};
+use provider::ReleaseProvider;
+
+mod artifacts;
+mod cache;
+mod delta;
+mod events;
+mod gitea;
 mod github;
+mod gitlab;
 mod hash;
 mod installer;
+mod manifest;
+mod provider;
+mod rollout;
+mod scheduler;
+mod signature;
+mod staging;
+mod version;
+
+/// Release the running version is too old for, from the latest check; the frontend blocks
+/// until it is installed
+static REQUIRED_UPDATE: Mutex<Option<ReleaseInfo>> = Mutex::new(None);
+
+/// Repository releases are published to
+const DEFAULT_OWNER: &str = "Asdmir786";
+const DEFAULT_REPO: &str = "helper-wab2b-dashboard-system";
 
 /// Information about a GitHub release
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ReleaseInfo {
     /// Version string (e.g., "1.0.0")
     pub version: String,
@@ -21,10 +49,99 @@ pub struct ReleaseInfo {
     pub assets: Vec<Asset>,
     /// Publication date of the release
     pub published_at: String,
+    /// Web page of the release
+    #[serde(default)]
+    pub html_url: String,
+    /// Set when no asset can be installed automatically on this platform
+    #[serde(default)]
+    pub manual_update: Option<ManualUpdate>,
+    /// Whether this release is newer than the running version
+    #[serde(default)]
+    pub update_available: bool,
+    /// Whether the user skipped this version, or a newer one, and doesn't want to hear about it
+    #[serde(default)]
+    pub skipped: bool,
+    /// Oldest version that may keep running, from the manifest or a `Minimum version:` line in
+    /// the release notes
+    #[serde(default)]
+    pub minimum_version: Option<String>,
+    /// Whether the running version is older than `minimum_version` and has to be updated;
+    /// such a release can't be skipped
+    #[serde(default)]
+    pub update_required: bool,
+    /// Share of installs the release is offered to so far, from the manifest or a `Rollout:` line
+    /// in the release notes; everyone when unset
+    #[serde(default)]
+    pub rollout_percentage: Option<u8>,
+    /// Whether this install is outside the rollout, so the newer release isn't offered yet
+    #[serde(default)]
+    pub held_back: bool,
+    /// Channel the release was published on
+    #[serde(default)]
+    pub channel: ReleaseChannel,
+    /// Patch from the running version to this release, when one is published
+    #[serde(default)]
+    pub delta: Option<DeltaUpdate>,
+    /// How the running copy is installed, which decides the package it is updated from
+    #[serde(default)]
+    pub install_mode: installer::InstallMode,
+    /// Installer the running copy was set up with, on Windows
+    #[serde(default)]
+    pub installer: Option<installer::InstallerKind>,
+    /// The asset this machine installs, picked by the backend for every caller
+    #[serde(default)]
+    pub platform_asset: Option<Asset>,
+}
+
+/// A patch that turns the installed version into a release's package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeltaUpdate {
+    /// The patch asset
+    pub patch: Asset,
+    /// Version the patch applies to
+    pub from_version: String,
+    /// The package the patch produces, downloaded in full if patching fails
+    pub target: Asset,
+}
+
+/// Release channel, chosen with `beta_mode` in the settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseChannel {
+    /// Full releases only
+    #[default]
+    Stable,
+    /// Pre-releases as well
+    Beta,
+}
+
+/// Why an update has to be installed by hand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManualUpdateReason {
+    /// The release has no assets at all
+    NoAssets,
+    /// None of the assets matches this OS and architecture
+    NoCompatibleAsset,
+}
+
+/// Guided fallback for releases the updater can't install
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManualUpdate {
+    pub reason: ManualUpdateReason,
+    /// Release page to download the update from
+    pub release_url: String,
+    /// Platform the updater looked for, e.g. `windows-x86_64`
+    pub platform: String,
+    /// Assets closest to this platform, best match first
+    pub suggestions: Vec<Asset>,
 }
 
 /// Information about a release asset
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Asset {
     /// Asset name
     pub name: String,
@@ -34,17 +151,20 @@ pub struct Asset {
     pub size: u64,
     /// SHA256 hash of the asset
     pub sha256: String,
+    /// SHA512 hash of the asset, when the release publishes one
+    #[serde(default)]
+    pub sha512: String,
 }
 
-/// Download progress information
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct DownloadProgress {
-    /// Number of bytes downloaded
-    pub downloaded: u64,
-    /// Total size in bytes
-    pub total: u64,
-    /// Path to the downloaded file
-    pub file_path: String,
+impl Asset {
+    /// The strongest published hash of the asset, empty when there is none
+    pub fn checksum(&self) -> &str {
+        if self.sha512.is_empty() {
+            &self.sha256
+        } else {
+            &self.sha512
+        }
+    }
 }
 
 /// Check for updates from GitHub
@@ -57,63 +177,400 @@ pub async fn check_for_updates(owner: &str, repo: &str, app_handle: tauri::AppHa
     // Determine whether to include beta releases based on settings
     let should_include_beta = includeBeta.unwrap_or(false) || settings.beta_mode;
     
-    // Call the GitHub API to check for the latest release
-    let mut release_info = github::check_latest_release(owner, repo, should_include_beta).await?;
+    let release_info = check(&app_handle, owner, repo, should_include_beta).await?;
+    if release_info.update_available && !release_info.skipped {
+        events::emit(&app_handle, UpdaterEvent::Available { release: Box::new(release_info.clone()) });
+    }
+    Ok(release_info)
+}
+
+/// Newest release from the configured source: the self-hosted manifest if there is one,
+/// otherwise the GitHub, GitLab or Gitea repository chosen in the settings
+async fn fetch_release(app_handle: &tauri::AppHandle, owner: &str, repo: &str, include_beta: bool) -> Result<ReleaseInfo, String> {
+    let settings = crate::settings::load_settings(app_handle).unwrap_or_default();
+    let manifest_url = settings.update_manifest_url.trim();
+    if !manifest_url.is_empty() {
+        return manifest::check_latest_release(manifest_url, include_beta).await;
+    }
+    
+    let source = settings.release_source;
+    let project = match source.project.trim() {
+        "" => format!("{}/{}", owner, repo),
+        project => project.trim_matches('/').to_string(),
+    };
+    let token = crate::secrets::get(app_handle, crate::secrets::RELEASE_SOURCE_TOKEN)?
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty());
+    
+    match source.provider {
+        ReleaseProviderKind::Github => {
+            let (owner, repo) = project.split_once('/').unwrap_or((owner, repo));
+            github::GitHub {
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+                token: github::api_token(Some(app_handle)),
+            }
+            .latest_release(include_beta)
+            .await
+        }
+        ReleaseProviderKind::Gitlab => {
+            gitlab::GitLab { base_url: source.base_url, project, token }
+                .latest_release(include_beta)
+                .await
+        }
+        ReleaseProviderKind::Gitea => {
+            let (owner, repo) = project
+                .split_once('/')
+                .ok_or_else(|| format!("Gitea repository must be given as owner/repo, not {}", project))?;
+            gitea::Gitea {
+                base_url: source.base_url,
+                owner: owner.to_string(),
+                repo: repo.to_string(),
+                token,
+            }
+            .latest_release(include_beta)
+            .await
+        }
+    }
+}
+
+/// Look up the newest release and work out how it can be installed
+async fn check(app_handle: &tauri::AppHandle, owner: &str, repo: &str, should_include_beta: bool) -> Result<ReleaseInfo, String> {
+    events::emit(app_handle, UpdaterEvent::Checking);
+    let mut release_info = fetch_release(app_handle, owner, repo, should_include_beta)
+        .await
+        .inspect_err(|e| events::emit(app_handle, UpdaterEvent::failed(FailureCode::Check, e)))?;
+    
+    // Compare by semver precedence; tags aren't ordered as plain strings
+    let current_version = app_handle.package_info().version.to_string();
+    release_info.update_available = version::is_newer(&release_info.version, &current_version)?;
+    
+    // A skipped version stays quiet until something newer comes out
+    let settings = crate::settings::load_settings(app_handle).ok();
+    let skipped_version = settings.as_ref().and_then(|settings| settings.skipped_version.clone());
+    let asset_pattern = settings.map(|settings| settings.update_asset_pattern).unwrap_or_default();
+    release_info.skipped = match skipped_version {
+        Some(skipped) => !version::is_newer(&release_info.version, &skipped).unwrap_or(true),
+        None => false,
+    };
+    
+    // A release that breaks older helpers names the oldest one that may keep running
+    if release_info.minimum_version.is_none() {
+        release_info.minimum_version = provider::minimum_version_from_notes(&release_info.release_notes);
+    }
+    release_info.update_required = match &release_info.minimum_version {
+        Some(minimum) => version::is_newer(minimum, &current_version)?,
+        None => false,
+    };
+    if release_info.update_required {
+        release_info.skipped = false;
+    }
+    
+    // A staged rollout only reaches installs in its buckets; a required update reaches everyone
+    if release_info.rollout_percentage.is_none() {
+        release_info.rollout_percentage = provider::rollout_percentage_from_notes(&release_info.release_notes);
+    }
+    if let Some(percentage) = release_info.rollout_percentage {
+        if release_info.update_available && !release_info.update_required
+            && !rollout::includes(percentage, rollout::bucket(app_handle))
+        {
+            println!("Update {} is rolled out to {}% of installs, not this one yet", release_info.version, percentage);
+            release_info.update_available = false;
+            release_info.held_back = true;
+        }
+    }
+    
+    // Keep a snapshot so the manual download screen works without further API calls
+    if let Err(e) = cache::store_snapshot(app_handle, owner, repo, should_include_beta, &release_info) {
+        println!("Failed to cache release information: {}", e);
+    }
+    
+    let release_url = if release_info.html_url.is_empty() {
+        format!("https://github.com/{}/{}/releases", owner, repo)
+    } else {
+        release_info.html_url.clone()
+    };
+    
+    release_info.install_mode = installer::install_mode();
+    release_info.installer = installer::installer_kind();
     
     // Check if there's a suitable asset for the current platform
-    if !release_info.assets.is_empty() {
-        let platform_asset = github::select_platform_asset(&release_info.assets);
+    let manual_reason = if !release_info.assets.is_empty() {
+        let platform_asset = github::select_platform_asset(&release_info.assets, &asset_pattern);
+        
+        // Offer a patch against the running version instead of the full package where there is one
+        release_info.delta = platform_asset.as_ref().and_then(|target| {
+            github::select_patch(&release_info.assets, target, &current_version).map(|patch| DeltaUpdate {
+                patch,
+                from_version: current_version.clone(),
+                target: target.clone(),
+            })
+        });
+        
+        release_info.platform_asset = platform_asset.clone();
         
         // If no suitable asset is found, add a warning to the release notes
         if platform_asset.is_none() {
             let warning = "\n\n**Warning:** No compatible update package was found for your platform. Please download the update manually from the GitHub releases page.";
             release_info.release_notes.push_str(warning);
+            Some(ManualUpdateReason::NoCompatibleAsset)
+        } else {
+            None
         }
     } else {
         // If there are no assets at all, add a warning to the release notes
         let warning = "\n\n**Warning:** No update packages were found for this release. Please check the GitHub releases page.";
         release_info.release_notes.push_str(warning);
+        Some(ManualUpdateReason::NoAssets)
+    };
+    
+    // Give the frontend enough to render a guided manual update
+    release_info.manual_update = manual_reason.map(|reason| ManualUpdate {
+        reason,
+        release_url,
+        platform: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+        suggestions: github::suggest_assets(&release_info.assets),
+    });
+    
+    // Nothing else may happen until the update is installed
+    *REQUIRED_UPDATE.lock().unwrap() = release_info.update_required.then(|| release_info.clone());
+    if let Some(minimum_version) = release_info.minimum_version.clone().filter(|_| release_info.update_required) {
+        println!("Version {} is required, {} is running", minimum_version, current_version);
+        events::emit(app_handle, UpdaterEvent::UpdateRequired {
+            release: Box::new(release_info.clone()),
+            minimum_version,
+        });
     }
     
     // Return the release information
     Ok(release_info)
 }
 
-/// Download a release asset
+/// Stop announcing `version` (and anything older); a newer release is announced again
+#[command]
+pub fn skip_version(
+    version: &str,
+    token: Option<String>,
+    app_handle: tauri::AppHandle,
+    webview: tauri::Webview,
+) -> Result<(), String> {
+    authorize_command(&webview, token.as_deref(), Scope::Admin)?;
+    // Stored normalised, so `v1.2` and `1.2.0` are the same skip
+    let version = version::parse(version)?.to_string();
+    if REQUIRED_UPDATE.lock().unwrap().as_ref().is_some_and(|required| required.version == version) {
+        return Err(format!("Version {} is required and can't be skipped", version));
+    }
+    
+    let mut settings = crate::settings::load_settings(&app_handle)
+        .map_err(|e| format!("Failed to load settings: {}", e))?;
+    settings.skipped_version = Some(version);
+    crate::settings::save_settings(&app_handle, &settings)
+        .map_err(|e| format!("Failed to save settings: {}", e))
+}
+
+/// Compare two version strings by semver precedence: -1, 0 or 1 when `a` is older, the same or newer than `b`
+#[command]
+pub fn compare_versions(a: &str, b: &str) -> Result<i32, String> {
+    Ok(version::compare(a, b)? as i32)
+}
+
+/// List the assets of the latest release from the cached snapshot
+#[command]
+pub async fn get_available_downloads(
+    app_handle: tauri::AppHandle,
+    owner: Option<String>,
+    repo: Option<String>,
+) -> Result<cache::AvailableDownloads, String> {
+    cache::available_downloads(&app_handle, owner.as_deref(), repo.as_deref()).await
+}
+
+/// Download a release asset to `destination`, a file name in the updates directory
 #[command]
 pub async fn download_asset(
     url: &str, 
     destination: &str,
-    app_handle: tauri::AppHandle
+    token: Option<String>,
+    app_handle: tauri::AppHandle,
+    webview: tauri::Webview,
 ) -> Result<String, String> {
+    authorize_command(&webview, token.as_deref(), Scope::Admin)?;
     // Call the GitHub API to download the asset
-    let file_path = github::download_asset(url, destination, Some(&app_handle)).await?;
+    let destination = artifacts::resolve_destination(&app_handle, destination)?;
+    let file_path = github::download_asset(url, &destination, Some(&app_handle))
+        .await
+        .inspect_err(|e| events::emit(&app_handle, UpdaterEvent::failed(FailureCode::Download, e)))?;
     
     // Return the path to the downloaded file
     Ok(file_path)
 }
 
-/// Verify the integrity of a downloaded file using SHA256 hash
+/// Download an update as a patch against the running version, falling back to the full package.
+/// `destination` is a file name in the updates directory.
+#[command]
+pub async fn download_delta_update(
+    delta: DeltaUpdate,
+    destination: &str,
+    token: Option<String>,
+    app_handle: tauri::AppHandle,
+    webview: tauri::Webview,
+) -> Result<String, String> {
+    authorize_command(&webview, token.as_deref(), Scope::Admin)?;
+    let destination = artifacts::resolve_destination(&app_handle, destination)?;
+    match delta::download(&app_handle, &delta, &destination).await {
+        Ok(()) => Ok(destination),
+        Err(e) => {
+            println!("Delta update failed, downloading the full package: {}", e);
+            github::download_asset(&delta.target.download_url, &destination, Some(&app_handle))
+                .await
+                .inspect_err(|e| events::emit(&app_handle, UpdaterEvent::failed(FailureCode::Download, e)))
+        }
+    }
+}
+
+/// Verify the integrity of a downloaded file using its SHA256 or SHA512 hash
 #[command]
-pub fn verify_file_hash(file_path: &str, expected_hash: &str) -> Result<bool, String> {
+pub fn verify_file_hash(file_path: &str, expected_hash: &str, app_handle: tauri::AppHandle) -> Result<bool, String> {
+    events::emit(&app_handle, UpdaterEvent::Verifying { path: file_path.to_string() });
+    
     // Call the hash verification function
-    hash::verify_file_hash(file_path, expected_hash)
+    let result = hash::verify_file_hash(file_path, expected_hash);
+    let event = match &result {
+        Ok(true) => UpdaterEvent::ReadyToInstall {
+            path: file_path.to_string(),
+            version: None,
+            staged: false,
+        },
+        Ok(false) => UpdaterEvent::failed(FailureCode::Verification, "The downloaded file does not match its checksum"),
+        Err(e) => UpdaterEvent::failed(FailureCode::Verification, e),
+    };
+    events::emit(&app_handle, event);
+    result
 }
 
-/// Install an update and restart the application
+/// Install an update and restart the application, reporting how it was applied.
+/// `ui` overrides the installer UI from the settings for this install. With `dry_run`, or
+/// `WAB2B_UPDATE_DRY_RUN` set, the update is checked but nothing is replaced.
 #[command]
-pub fn install_update(update_path: &str, app_handle: tauri::AppHandle) -> Result<bool, String> {
+pub fn install_update(
+    update_path: &str,
+    on_other_sessions: Option<installer::OtherSessionAction>,
+    ui: Option<crate::settings::InstallerUi>,
+    dry_run: Option<bool>,
+    token: Option<String>,
+    app_handle: tauri::AppHandle,
+    webview: tauri::Webview,
+) -> Result<installer::InstallReport, String> {
+    authorize_command(&webview, token.as_deref(), Scope::Admin)?;
+    let ui = match ui {
+        Some(ui) => ui,
+        None => crate::settings::load_settings(&app_handle)
+            .map(|settings| settings.installer_ui)
+            .unwrap_or_default(),
+    };
+    
     // Call the installer function
-    installer::install_update(&app_handle, update_path)
+    let dry_run = dry_run.unwrap_or_else(installer::dry_run_requested);
+    installer::install_update(&app_handle, update_path, on_other_sessions.unwrap_or_default(), ui, true, dry_run)
+        .inspect_err(|e| events::emit(&app_handle, UpdaterEvent::failed(FailureCode::Install, e)))
+}
+
+/// The release this version has to be updated to before it may be used, if the latest check
+/// found one
+#[command]
+pub fn get_required_update() -> Option<ReleaseInfo> {
+    REQUIRED_UPDATE.lock().unwrap().clone()
+}
+
+/// The update downloaded in the background for installation on exit, if any
+#[command]
+pub fn get_staged_update(app_handle: tauri::AppHandle) -> Option<staging::StagedUpdate> {
+    staging::load(&app_handle)
+}
+
+/// Drop the update downloaded in the background, returning it if there was one
+#[command]
+pub fn discard_staged_update(app_handle: tauri::AppHandle) -> Result<Option<staging::StagedUpdate>, String> {
+    staging::discard(&app_handle)
+}
+
+/// Install the update staged in the background while the application exits
+pub fn install_staged_update(app_handle: &tauri::AppHandle) {
+    staging::apply(app_handle, false);
+}
+
+/// List the backups of earlier versions, newest first
+#[command]
+pub fn list_backups(app_handle: tauri::AppHandle) -> Result<Vec<installer::Backup>, String> {
+    // Backups are named after the AppImage, not the binary inside its read-only mount
+    let current_exe = installer::application_path()?;
+    installer::list_backups(&app_handle, &current_exe)
+}
+
+/// Delete backups of earlier versions: those the retention settings don't keep, or all with `all`
+#[command]
+pub fn purge_backups(
+    all: Option<bool>,
+    token: Option<String>,
+    app_handle: tauri::AppHandle,
+    webview: tauri::Webview,
+) -> Result<artifacts::CleanupReport, String> {
+    authorize_command(&webview, token.as_deref(), Scope::Admin)?;
+    let current_exe = installer::application_path()?;
+    
+    if all.unwrap_or(false) {
+        return installer::prune_backups(&app_handle, &current_exe, None);
+    }
+    let settings = crate::settings::load_settings(&app_handle)
+        .map_err(|e| format!("Failed to load settings: {}", e))?;
+    installer::prune_backups(&app_handle, &current_exe, Some(&settings.update_backups))
+}
+
+/// Restore a backup of an earlier version, the newest if none is named, and restart into it
+#[command]
+pub fn rollback_update(
+    backup: Option<String>,
+    token: Option<String>,
+    app_handle: tauri::AppHandle,
+    webview: tauri::Webview,
+) -> Result<installer::Backup, String> {
+    authorize_command(&webview, token.as_deref(), Scope::Admin)?;
+    installer::rollback(&app_handle, backup.as_deref())
+}
+
+/// Remove leftover installers and update scripts, reporting what was purged
+#[command]
+pub fn cleanup_update_artifacts(
+    token: Option<String>,
+    app_handle: tauri::AppHandle,
+    webview: tauri::Webview,
+) -> Result<artifacts::CleanupReport, String> {
+    authorize_command(&webview, token.as_deref(), Scope::Admin)?;
+    artifacts::cleanup(&app_handle, None)
 }
 
 /// Register all update-related commands with Tauri
-pub fn init(_app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
+pub fn init(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     // Log that the updater module is being initialized
     println!("Initializing GitHub update system");
     
-    // You could add additional initialization here if needed
-    // For example, checking for updates on startup if enabled in settings
+    // Purge whatever a previous update (successful or not) left behind, short of a staged
+    // update and downloads that can still be resumed
+    let report = artifacts::purge_stale(app.handle(), &staging::files(app.handle()))?;
+    if !report.removed.is_empty() || !report.failed.is_empty() {
+        println!(
+            "Purged {} update artifact(s), {} bytes freed, {} failed",
+            report.removed.len(),
+            report.freed_bytes,
+            report.failed.len()
+        );
+        let _ = app.emit("update-artifacts-purged", &report);
+    }
+    
+    // A staged update the last exit didn't get to is installed now
+    staging::apply(app.handle(), true);
+    
+    // Re-check for updates in the background for as long as the application runs
+    scheduler::start(app.handle().clone());
     
     Ok(())
 }
\ No newline at end of file
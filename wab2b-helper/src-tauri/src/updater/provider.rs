@@ -0,0 +1,65 @@
+/*!
+ * Release sources the updater can check, chosen in the settings
+ */
+
+use std::future::Future;
+use std::sync::LazyLock;
+use regex::Regex;
+
+use crate::updater::{version, ReleaseChannel, ReleaseInfo};
+
+/// `Minimum version: 1.4.0` on a line of its own, also inside an HTML comment so it needn't show
+static MINIMUM_VERSION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?im)^\s*(?:<!--\s*)?minimum[ _-]version\s*[:=]\s*(v?\d+(?:\.\d+){0,2}(?:-[0-9a-z.]+)?)").unwrap()
+});
+
+/// `Rollout: 10%` on a line of its own, also inside an HTML comment
+static ROLLOUT_PERCENTAGE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?im)^\s*(?:<!--\s*)?rollout(?:[ _-]percentage)?\s*[:=]\s*(\d{1,3})\s*%?").unwrap()
+});
+
+/// Something that publishes releases of the helper
+pub trait ReleaseProvider {
+    /// Newest release, pre-releases included when `include_beta` is set
+    fn latest_release(&self, include_beta: bool) -> impl Future<Output = Result<ReleaseInfo, String>> + Send;
+}
+
+/// Releases look the same on every provider, but each lists them in its own order;
+/// pick the newest by version that the channel allows
+pub fn newest_release(
+    releases: impl IntoIterator<Item = ReleaseInfo>,
+    include_beta: bool,
+) -> Result<ReleaseInfo, String> {
+    let mut newest: Option<(semver::Version, ReleaseInfo)> = None;
+    
+    for release_info in releases {
+        if release_info.channel == ReleaseChannel::Beta && !include_beta {
+            continue;
+        }
+        
+        let version = version::parse(&release_info.version)?;
+        if newest.as_ref().is_none_or(|(best, _)| version.cmp_precedence(best).is_gt()) {
+            newest = Some((version, release_info));
+        }
+    }
+    
+    newest
+        .map(|(_, release_info)| release_info)
+        .ok_or_else(|| if include_beta {
+            "No releases found".to_string()
+        } else {
+            "No stable releases found".to_string()
+        })
+}
+
+/// Oldest version the release notes allow to keep running, normalised
+pub fn minimum_version_from_notes(notes: &str) -> Option<String> {
+    let captures = MINIMUM_VERSION.captures(notes)?;
+    version::parse(&captures[1]).ok().map(|version| version.to_string())
+}
+
+/// Share of installs the release notes roll the release out to, if they name a valid one
+pub fn rollout_percentage_from_notes(notes: &str) -> Option<u8> {
+    let captures = ROLLOUT_PERCENTAGE.captures(notes)?;
+    captures[1].parse().ok().filter(|percentage| *percentage <= 100)
+}
@@ -0,0 +1,30 @@
+/*!
+ * Staged rollouts, offering a release to a share of installs before everyone gets it
+ */
+
+use rand::Rng;
+use tauri::AppHandle;
+
+/// Number of buckets installs are spread over; a rollout percentage admits that many of them
+const BUCKETS: u8 = 100;
+
+/// Bucket of this install, picked at random on first use and kept in the settings so the same
+/// installs stay in a rollout as it widens
+pub fn bucket(app: &AppHandle) -> u8 {
+    let mut settings = crate::settings::load_settings(app).unwrap_or_default();
+    if let Some(bucket) = settings.update_rollout_bucket.filter(|bucket| *bucket < BUCKETS) {
+        return bucket;
+    }
+
+    let bucket = rand::thread_rng().gen_range(0..BUCKETS);
+    settings.update_rollout_bucket = Some(bucket);
+    if let Err(e) = crate::settings::save_settings(app, &settings) {
+        println!("Failed to save update rollout bucket: {}", e);
+    }
+    bucket
+}
+
+/// Whether an install in `bucket` is part of a rollout to `percentage` of installs
+pub fn includes(percentage: u8, bucket: u8) -> bool {
+    bucket < percentage
+}
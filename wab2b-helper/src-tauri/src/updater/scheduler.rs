@@ -0,0 +1,164 @@
+/*!
+ * Periodic background update checks
+ */
+
+use std::time::{Duration, Instant};
+use chrono::Timelike;
+use tauri::AppHandle;
+
+use crate::settings::{self, AppSettings, ReleaseProviderKind, UpdateCheckSchedule};
+use crate::updater::events::{self, UpdaterEvent};
+
+/// Wait before the first check, so it doesn't compete with startup
+const STARTUP_DELAY: Duration = Duration::from_secs(10);
+
+/// How often the settings are re-read while no check is due
+const IDLE_POLL: Duration = Duration::from_secs(5 * 60);
+
+/// Lower bound for the check interval, whatever the settings say
+const MIN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Wait before retrying a failed check, doubled after every further failure
+const RETRY_INITIAL: Duration = Duration::from_secs(30);
+
+/// Upper bound for the wait between retries
+const RETRY_MAX: Duration = Duration::from_secs(30 * 60);
+
+/// How often reachability of the release server is probed while waiting to retry
+const NETWORK_POLL: Duration = Duration::from_secs(15);
+
+/// Time a reachability probe may take
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Start the background task that checks for updates and emits `update-available`
+pub fn start(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(STARTUP_DELAY).await;
+        let mut last_check: Option<Instant> = None;
+        // Failed checks in a row; a retry is due whenever the previous check failed
+        let mut failures: u32 = 0;
+
+        loop {
+            let settings = settings::load_settings(&app_handle).unwrap_or_default();
+            let schedule = &settings.update_checks;
+
+            let due = match last_check {
+                None => true,
+                Some(_) if failures > 0 => true,
+                Some(_) if schedule.interval_hours == 0 => false,
+                Some(last) => {
+                    let interval = Duration::from_secs(schedule.interval_hours * 60 * 60).max(MIN_INTERVAL);
+                    last.elapsed() >= interval
+                }
+            };
+            if !settings.auto_update || !due || in_quiet_hours(schedule, chrono::Local::now().hour()) {
+                tokio::time::sleep(IDLE_POLL).await;
+                continue;
+            }
+
+            last_check = Some(Instant::now());
+            let result = super::check(&app_handle, super::DEFAULT_OWNER, super::DEFAULT_REPO, settings.beta_mode).await;
+            if result.is_ok() {
+                failures = 0;
+            }
+            match result {
+                Ok(release_info) if release_info.update_available && !release_info.skipped => {
+                    println!("Update {} is available", release_info.version);
+                    
+                    // Rather than interrupt, get the update ready to install when the helper exits
+                    if settings.install_updates_on_exit {
+                        match super::staging::stage(&app_handle, &release_info).await {
+                            Ok(staged) => {
+                                events::emit(&app_handle, UpdaterEvent::ReadyToInstall {
+                                    path: staged.path,
+                                    version: Some(staged.version),
+                                    staged: true,
+                                });
+                                continue;
+                            }
+                            Err(e) => println!("Failed to stage update {}: {}", release_info.version, e),
+                        }
+                    }
+                    events::emit(&app_handle, UpdaterEvent::Available { release: Box::new(release_info) });
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    // Offline at startup is the usual cause, so don't wait for the next interval
+                    failures += 1;
+                    let delay = retry_delay(failures);
+                    println!("Background update check failed, retrying within {}s: {}", delay.as_secs(), e);
+                    wait_for_retry(&settings, delay).await;
+                }
+            }
+        }
+    });
+}
+
+/// Wait before retry number `failures`
+fn retry_delay(failures: u32) -> Duration {
+    RETRY_INITIAL
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(RETRY_MAX)
+}
+
+/// Sleep for `delay`, cut short once the release server becomes reachable after being unreachable
+async fn wait_for_retry(settings: &AppSettings, delay: Duration) {
+    let (host, port) = probe_target(settings);
+    let deadline = Instant::now() + delay;
+    let mut offline = !reachable(&host, port).await;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return;
+        }
+        tokio::time::sleep(remaining.min(NETWORK_POLL)).await;
+
+        let online = reachable(&host, port).await;
+        if offline && online {
+            println!("Network is back, retrying the update check");
+            return;
+        }
+        offline = !online;
+    }
+}
+
+/// Host and port the configured release source is fetched from
+fn probe_target(settings: &AppSettings) -> (String, u16) {
+    let source_url = if !settings.update_manifest_url.trim().is_empty() {
+        settings.update_manifest_url.trim()
+    } else {
+        match settings.release_source.provider {
+            ReleaseProviderKind::Github => "https://api.github.com",
+            ReleaseProviderKind::Gitlab if settings.release_source.base_url.trim().is_empty() => "https://gitlab.com",
+            _ => settings.release_source.base_url.trim(),
+        }
+    };
+
+    url::Url::parse(source_url)
+        .ok()
+        .and_then(|url| Some((url.host_str()?.to_string(), url.port_or_known_default()?)))
+        .unwrap_or_else(|| ("api.github.com".to_string(), 443))
+}
+
+/// Whether a TCP connection to `host` can be opened
+async fn reachable(host: &str, port: u16) -> bool {
+    matches!(
+        tokio::time::timeout(PROBE_TIMEOUT, tokio::net::TcpStream::connect((host, port))).await,
+        Ok(Ok(_))
+    )
+}
+
+/// Whether `hour` falls in the quiet hours, which may span midnight
+fn in_quiet_hours(schedule: &UpdateCheckSchedule, hour: u32) -> bool {
+    let start = u32::from(schedule.quiet_hours_start);
+    let end = u32::from(schedule.quiet_hours_end);
+
+    if start == end {
+        false
+    } else if start < end {
+        (start..end).contains(&hour)
+    } else {
+        hour >= start || hour < end
+    }
+}
@@ -0,0 +1,68 @@
+/**
+ * Minisign signature verification for downloaded updates
+ */
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use base64::Engine;
+use minisign_verify::{PublicKey, Signature};
+
+/// Minisign public key (the base64 line of the `.pub` file) that releases are signed with,
+/// embedded at build time
+const PUBLIC_KEY: Option<&str> = option_env!("WAB2B_UPDATE_PUBLIC_KEY");
+
+/// Where the signature of a downloaded update is kept: next to it, with `.sig` appended
+pub fn signature_path(update_path: &Path) -> PathBuf {
+    let mut path = update_path.as_os_str().to_owned();
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+/// Read a signature file, either as minisign writes it or base64-encoded as a whole
+fn decode_signature(text: &str) -> Result<Signature, String> {
+    if let Ok(signature) = Signature::decode(text) {
+        return Ok(signature);
+    }
+    
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(text.trim())
+        .map_err(|_| "Update signature is not a minisign signature".to_string())?;
+    let decoded = String::from_utf8(decoded)
+        .map_err(|_| "Update signature is not a minisign signature".to_string())?;
+    Signature::decode(&decoded).map_err(|e| format!("Invalid update signature: {}", e))
+}
+
+/// Check the update at `update_path` against its `.sig` file and the embedded public key
+pub fn verify_update(update_path: &Path) -> Result<(), String> {
+    let public_key = PUBLIC_KEY
+        .ok_or_else(|| "This build has no update signing key; please install the update manually".to_string())?;
+    let public_key = PublicKey::from_base64(public_key.trim())
+        .map_err(|e| format!("Invalid update signing key: {}", e))?;
+    
+    let signature_path = signature_path(update_path);
+    let signature_text = std::fs::read_to_string(&signature_path).map_err(|e| {
+        format!("Missing signature {}: {}", signature_path.display(), e)
+    })?;
+    let signature = decode_signature(&signature_text)?;
+    
+    let mut verifier = public_key
+        .verify_stream(&signature)
+        .map_err(|e| format!("Update signature can't be checked: {}", e))?;
+    
+    let mut file = File::open(update_path)
+        .map_err(|e| format!("Failed to open update: {}", e))?;
+    let mut buffer = [0; 1024 * 1024];
+    loop {
+        let bytes_read = file.read(&mut buffer)
+            .map_err(|e| format!("Failed to read update: {}", e))?;
+        if bytes_read == 0 {
+            break;
+        }
+        verifier.update(&buffer[..bytes_read]);
+    }
+    
+    verifier
+        .finalize()
+        .map_err(|_| "Update signature verification failed; the update was not installed".to_string())
+}
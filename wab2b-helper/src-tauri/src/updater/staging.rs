@@ -0,0 +1,181 @@
+/*!
+ * Updates downloaded in the background and installed when the application exits
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::updater::events::{self, UpdaterEvent};
+use crate::updater::installer::{self, InstallStrategy, OtherSessionAction};
+use crate::updater::{artifacts, delta, github, hash, signature, version, ReleaseInfo};
+
+/// Name of the staged update record in the app data directory
+const STAGED_FILE: &str = "staged-update.json";
+
+/// An update downloaded and verified, waiting for the application to exit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StagedUpdate {
+    pub version: String,
+    /// Downloaded package
+    pub path: String,
+    pub sha256: String,
+    /// Time of staging in seconds since the Unix epoch
+    pub staged_at: u64,
+    /// Set once installation was started, so a failing update isn't retried on every launch
+    #[serde(default)]
+    pub attempted: bool,
+}
+
+fn app_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    Ok(app_dir)
+}
+
+/// The staged update, if there is one whose package is still on disk
+pub fn load(app: &AppHandle) -> Option<StagedUpdate> {
+    let json = fs::read_to_string(app_dir(app).ok()?.join(STAGED_FILE)).ok()?;
+    let staged: StagedUpdate = serde_json::from_str(&json).ok()?;
+    Path::new(&staged.path).is_file().then_some(staged)
+}
+
+fn save(app: &AppHandle, staged: &StagedUpdate) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(staged)
+        .map_err(|e| format!("Failed to serialize staged update: {}", e))?;
+
+    fs::write(app_dir(app)?.join(STAGED_FILE), json)
+        .map_err(|e| format!("Failed to write staged update: {}", e))
+}
+
+/// Files of the staged update that cleanups have to leave alone
+pub fn files(app: &AppHandle) -> Vec<PathBuf> {
+    load(app)
+        .map(|staged| {
+            let path = PathBuf::from(staged.path);
+            vec![signature::signature_path(&path), path]
+        })
+        .unwrap_or_default()
+}
+
+/// Forget the staged update and delete its package, returning what was discarded
+pub fn discard(app: &AppHandle) -> Result<Option<StagedUpdate>, String> {
+    let staged = load(app);
+    if let Some(staged) = &staged {
+        let path = Path::new(&staged.path);
+        let _ = fs::remove_file(signature::signature_path(path));
+        fs::remove_file(path)
+            .map_err(|e| format!("Failed to delete staged update: {}", e))?;
+    }
+
+    match fs::remove_file(app_dir(app)?.join(STAGED_FILE)) {
+        Ok(()) => Ok(staged),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(staged),
+        Err(e) => Err(format!("Failed to delete staged update: {}", e)),
+    }
+}
+
+/// Download and verify the package of `release_info` for installation on exit; a release
+/// staged before is reused
+pub async fn stage(app: &AppHandle, release_info: &ReleaseInfo) -> Result<StagedUpdate, String> {
+    if let Some(staged) = load(app) {
+        if staged.version == release_info.version {
+            return Ok(staged);
+        }
+    }
+    // An older staged release is superseded
+    discard(app)?;
+
+    // Picked when the release was checked, like for an interactive update
+    let asset = release_info
+        .platform_asset
+        .clone()
+        .ok_or_else(|| "No compatible update found for this platform".to_string())?;
+    let destination = artifacts::updates_dir(app)?
+        .join(format!("staged-update-{}.bin", release_info.version))
+        .to_string_lossy()
+        .to_string();
+
+    // Same order as an interactive update: the patch when there is one, the full package otherwise
+    let patched = match &release_info.delta {
+        Some(delta) => match delta::download(app, delta, &destination).await {
+            Ok(()) => true,
+            Err(e) => {
+                println!("Delta update failed, downloading the full package: {}", e);
+                false
+            }
+        },
+        None => false,
+    };
+    if !patched {
+        github::download_asset(&asset.download_url, &destination, Some(app)).await?;
+    }
+
+    events::emit(app, UpdaterEvent::Verifying { path: destination.clone() });
+    if !asset.checksum().is_empty() && !hash::verify_file_hash(&destination, asset.checksum())? {
+        let _ = fs::remove_file(&destination);
+        return Err("Update verification failed. The downloaded file may be corrupted.".to_string());
+    }
+
+    let staged = StagedUpdate {
+        version: release_info.version.clone(),
+        path: destination,
+        sha256: asset.sha256,
+        staged_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        attempted: false,
+    };
+    save(app, &staged)?;
+
+    Ok(staged)
+}
+
+/// Install the staged update, once. With `restart` the application restarts into it,
+/// otherwise it is replaced after exiting.
+pub fn apply(app: &AppHandle, restart: bool) {
+    let Some(mut staged) = load(app) else {
+        return;
+    };
+
+    let current_version = app.package_info().version.to_string();
+    if !version::is_newer(&staged.version, &current_version).unwrap_or(false) {
+        // Installed by now, or older than what runs; either way no longer needed
+        let _ = discard(app);
+        return;
+    }
+    if staged.attempted {
+        println!("Staged update {} did not install, discarding it", staged.version);
+        let _ = discard(app);
+        return;
+    }
+
+    staged.attempted = true;
+    if let Err(e) = save(app, &staged) {
+        println!("Failed to record staged update attempt: {}", e);
+        return;
+    }
+
+    let ui = crate::settings::load_settings(app)
+        .map(|settings| settings.installer_ui)
+        .unwrap_or_default();
+    match installer::install_update(app, &staged.path, OtherSessionAction::Ask, ui, restart, installer::dry_run_requested()) {
+        // Other users still run the application, or nothing was installed on purpose; try again
+        // on a later exit
+        Ok(report) if report.dry_run || matches!(report.strategy, InstallStrategy::Deferred) => {
+            staged.attempted = false;
+            let _ = save(app, &staged);
+        }
+        Ok(_) => println!("Installing staged update {}", staged.version),
+        Err(e) => println!("Failed to install staged update {}: {}", staged.version, e),
+    }
+}
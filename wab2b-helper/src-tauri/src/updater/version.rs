@@ -0,0 +1,43 @@
+/**
+ * Semantic version comparison for update checks
+ */
+
+use std::cmp::Ordering;
+use semver::Version;
+
+/// Parse a release tag or version string such as `v1.10.0`, `1.2.0-beta.3` or `1.4`
+pub fn parse(version: &str) -> Result<Version, String> {
+    let trimmed = version.trim();
+    let trimmed = trimmed
+        .strip_prefix('v')
+        .or_else(|| trimmed.strip_prefix('V'))
+        .unwrap_or(trimmed);
+    
+    if let Ok(parsed) = Version::parse(trimmed) {
+        return Ok(parsed);
+    }
+    
+    // Tags like `1.4` or `2` leave out the trailing zeros
+    let core_end = trimmed.find(['-', '+']).unwrap_or(trimmed.len());
+    let (core, suffix) = trimmed.split_at(core_end);
+    let parts = core.split('.').count();
+    if (1..3).contains(&parts) {
+        let padded = format!("{}{}{}", core, ".0".repeat(3 - parts), suffix);
+        if let Ok(parsed) = Version::parse(&padded) {
+            return Ok(parsed);
+        }
+    }
+    
+    Err(format!("Invalid version: {}", version))
+}
+
+/// Order two versions by semver precedence: pre-releases come before their release and
+/// build metadata is ignored
+pub fn compare(a: &str, b: &str) -> Result<Ordering, String> {
+    Ok(parse(a)?.cmp_precedence(&parse(b)?))
+}
+
+/// Whether `candidate` is a newer version than `current`
+pub fn is_newer(candidate: &str, current: &str) -> Result<bool, String> {
+    Ok(compare(candidate, current)? == Ordering::Greater)
+}
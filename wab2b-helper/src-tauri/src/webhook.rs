@@ -0,0 +1,281 @@
+/*!
+ * Outbound webhooks for download job events, signed with HMAC-SHA256
+ */
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tauri::{AppHandle, Manager};
+
+use crate::settings::{self, AppSettings};
+
+/// Name of the delivery log in the app data directory
+const LOG_FILE: &str = "webhook-deliveries.json";
+
+/// Number of deliveries kept in the log
+const MAX_DELIVERIES: usize = 200;
+
+/// Serializes access to the delivery log
+static LOG_LOCK: Mutex<()> = Mutex::new(());
+
+/// A webhook delivery as recorded in the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    /// Monotonically increasing, also across restarts; receivers use it to drop duplicates
+    pub id: u64,
+    pub event: String,
+    /// Time the event happened, in seconds since the Unix epoch
+    pub created_at: u64,
+    pub data: serde_json::Value,
+    pub attempts: u32,
+    /// Timestamp sent with the last attempt
+    pub last_attempt_at: Option<u64>,
+    pub last_status: Option<u16>,
+    pub last_error: Option<String>,
+    pub delivered: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DeliveryLog {
+    next_id: u64,
+    deliveries: Vec<WebhookDelivery>,
+}
+
+/// Request body; the same JSON string is what gets signed
+#[derive(Serialize)]
+struct WebhookBody<'a> {
+    delivery_id: u64,
+    event: &'a str,
+    timestamp: u64,
+    data: &'a serde_json::Value,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn log_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    Ok(app_dir.join(LOG_FILE))
+}
+
+fn load_log(app: &AppHandle) -> Result<DeliveryLog, String> {
+    let path = log_path(app)?;
+    if !path.exists() {
+        return Ok(DeliveryLog::default());
+    }
+
+    let json = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read webhook log: {}", e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse webhook log: {}", e))
+}
+
+fn save_log(app: &AppHandle, log: &DeliveryLog) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(log)
+        .map_err(|e| format!("Failed to serialize webhook log: {}", e))?;
+
+    fs::write(log_path(app)?, json)
+        .map_err(|e| format!("Failed to write webhook log: {}", e))
+}
+
+/// Apply `f` to delivery `id` and store the result
+fn update_delivery(
+    app: &AppHandle,
+    id: u64,
+    f: impl FnOnce(&mut WebhookDelivery),
+) -> Result<WebhookDelivery, String> {
+    let _guard = LOG_LOCK.lock().unwrap();
+    let mut log = load_log(app)?;
+
+    let delivery = log
+        .deliveries
+        .iter_mut()
+        .find(|d| d.id == id)
+        .ok_or_else(|| format!("Unknown webhook delivery: {}", id))?;
+    f(delivery);
+    let delivery = delivery.clone();
+
+    save_log(app, &log)?;
+    Ok(delivery)
+}
+
+/// Hex HMAC-SHA256 of `{timestamp}.{body}`; the timestamp is signed so old requests can't be replayed
+fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Record a job event and deliver it in the background, if webhooks are enabled
+pub fn notify<T: Serialize>(app_handle: &AppHandle, event: &str, data: &T) {
+    let enabled = settings::load_settings(app_handle)
+        .map(|s| s.webhook.enabled && !s.webhook.url.is_empty())
+        .unwrap_or(false);
+    if !enabled {
+        return;
+    }
+
+    let data = match serde_json::to_value(data) {
+        Ok(data) => data,
+        Err(e) => {
+            println!("Failed to serialize webhook event {}: {}", event, e);
+            return;
+        }
+    };
+
+    let recorded = {
+        let _guard = LOG_LOCK.lock().unwrap();
+        load_log(app_handle).and_then(|mut log| {
+            log.next_id += 1;
+            let id = log.next_id;
+            log.deliveries.push(WebhookDelivery {
+                id,
+                event: event.to_string(),
+                created_at: now_secs(),
+                data,
+                attempts: 0,
+                last_attempt_at: None,
+                last_status: None,
+                last_error: None,
+                delivered: false,
+            });
+
+            let overflow = log.deliveries.len().saturating_sub(MAX_DELIVERIES);
+            log.deliveries.drain(..overflow);
+
+            save_log(app_handle, &log).map(|_| id)
+        })
+    };
+
+    match recorded {
+        Ok(id) => {
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = deliver(&app_handle, id).await {
+                    println!("Webhook delivery {} failed: {}", id, e);
+                }
+            });
+        }
+        Err(e) => println!("Failed to record webhook event {}: {}", event, e),
+    }
+}
+
+/// Sign `body` and post it to the configured webhook URL, returning the receiver's status
+async fn post(settings: &AppSettings, id: u64, timestamp: u64, secret: &str, body: String) -> Result<reqwest::StatusCode, String> {
+    // Held to the same address rules as downloads, so a webhook can't reach the local network;
+    // a redirect would get around the check on the URL
+    let url = url::Url::parse(&settings.webhook.url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+    crate::network::check_url(settings, &url).map_err(|e| e.to_string())?;
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .dns_resolver(Arc::new(crate::network::GuardedResolver::new(settings)))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("X-Wab2b-Delivery", id.to_string())
+        .header("X-Wab2b-Timestamp", timestamp.to_string())
+        .header("X-Wab2b-Signature", format!("sha256={}", sign(secret, timestamp, &body)))
+        .body(body)
+        .send()
+        .await
+        .map(|response| response.status())
+        .map_err(|e| e.to_string())
+}
+
+/// Send delivery `id` with a fresh timestamp and signature
+async fn deliver(app_handle: &AppHandle, id: u64) -> Result<WebhookDelivery, String> {
+    let settings = settings::load_settings(app_handle).map_err(|e| e.to_string())?;
+    let config = &settings.webhook;
+    let secret = crate::secrets::get(app_handle, crate::secrets::WEBHOOK_SECRET)?.unwrap_or_default();
+
+    let delivery = {
+        let _guard = LOG_LOCK.lock().unwrap();
+        load_log(app_handle)?
+            .deliveries
+            .into_iter()
+            .find(|d| d.id == id)
+            .ok_or_else(|| format!("Unknown webhook delivery: {}", id))?
+    };
+
+    let timestamp = now_secs();
+    let result = if config.url.is_empty() {
+        Err("No webhook URL configured".to_string())
+    } else if secret.is_empty() {
+        // Receivers can't tell an unsigned request from a forged one
+        Err("No webhook secret configured".to_string())
+    } else {
+        let body = serde_json::to_string(&WebhookBody {
+            delivery_id: id,
+            event: &delivery.event,
+            timestamp,
+            data: &delivery.data,
+        })
+        .map_err(|e| format!("Failed to serialize webhook body: {}", e))?;
+
+        post(&settings, id, timestamp, &secret, body).await
+    };
+
+    update_delivery(app_handle, id, |delivery| {
+        delivery.attempts += 1;
+        delivery.last_attempt_at = Some(timestamp);
+        match result {
+            Ok(status) => {
+                delivery.last_status = Some(status.as_u16());
+                delivery.delivered = status.is_success();
+                delivery.last_error = (!status.is_success())
+                    .then(|| format!("Receiver answered with status {}", status));
+            }
+            Err(e) => {
+                delivery.last_status = None;
+                delivery.delivered = false;
+                delivery.last_error = Some(e);
+            }
+        }
+    })
+}
+
+// Command to list recorded webhook deliveries, newest first
+#[tauri::command]
+pub fn list_webhook_deliveries(app_handle: AppHandle) -> Result<Vec<WebhookDelivery>, String> {
+    let _guard = LOG_LOCK.lock().unwrap();
+    let mut deliveries = load_log(&app_handle)?.deliveries;
+    deliveries.reverse();
+    Ok(deliveries)
+}
+
+// Command to send a recorded webhook delivery again, keeping its delivery ID
+#[tauri::command]
+pub async fn redeliver(
+    app_handle: AppHandle,
+    webview: tauri::Webview,
+    id: u64,
+    token: Option<String>,
+) -> Result<WebhookDelivery, String> {
+    crate::permissions::authorize_command(&webview, token.as_deref(), crate::permissions::Scope::Admin)?;
+    deliver(&app_handle, id).await
+}
@@ -14,7 +14,7 @@ import SettingsModal from "./components/SettingsModal";
 const ProgressBar = React.lazy(() => import("./components/ProgressBar"));
 import packageJson from "../package.json";
 import { openPath } from '@tauri-apps/plugin-opener';
-import { UpdateManager, UpdateNotificationModal } from './updater';
+import { UpdateManager, UpdateNotificationModal, UpdaterEvent, getRequiredUpdate } from './updater';
 // (removed plugin-clipboard-manager import)
 
 // Types
@@ -27,6 +27,15 @@ interface FileInfo {
   size: number;
 }
 
+interface TransferProgress {
+  id: string;
+  downloaded: number;
+  total: number;
+  percent: number | null;
+  bytes_per_sec: number;
+  eta_secs: number | null;
+}
+
 export const isTauri = !!(window as any).__TAURI__;
 
 function App() {
@@ -76,25 +85,22 @@ function App() {
       }
     });
 
-    // Check for updates on app start based on auto-update setting
-    const checkUpdatesOnStart = async () => {
-      try {
-        // Get settings from backend
-        const appSettings = await invoke<{ autoUpdate: boolean, betaMode: boolean }>('get_settings');
-
-        // Only check for updates if auto-update is enabled
-        if (appSettings.autoUpdate && updateManager.getUpdateState().status === 'idle') {
-          // Pass the beta mode setting to the update check
-          updateManager.checkForUpdates(false, appSettings.betaMode).catch(console.error);
-        }
-      } catch (error) {
-        console.error('Failed to load settings for update check:', error);
+    // The backend checks at startup and then periodically, as the settings say
+    const unlistenUpdates = listen<UpdaterEvent>('updater-event', (event) => {
+      if (event.payload.type === 'available' || event.payload.type === 'update_required') {
+        updateManager.handleAvailableUpdate(event.payload.release);
       }
-    };
+    });
 
-    checkUpdatesOnStart();
+    // A required update found before the listener was up still has to block the app
+    getRequiredUpdate()
+      .then(release => release && updateManager.handleAvailableUpdate(release))
+      .catch(error => console.error('Failed to get required update:', error));
 
-    return unsubscribe;
+    return () => {
+      unsubscribe();
+      unlistenUpdates.then(unlisten => unlisten());
+    };
   }, [updateManager]);
 
   // Initialize theme based on system preference
@@ -334,8 +340,19 @@ function App() {
 
   useEffect(() => {
     if (!isTauri) return;
-    const unlisten = listen<number>("download-progress", (event) => {
-      setProgress(event.payload);
+    const unlisten = listen<TransferProgress>("download-progress", (event) => {
+      setProgress(event.payload.percent ?? 0);
+    });
+
+    return () => {
+      void unlisten.then(unlistenFn => unlistenFn()).catch(console.error);
+    };
+  }, []);
+
+  useEffect(() => {
+    if (!isTauri) return;
+    const unlisten = listen<string>("deep-link-rejected", (event) => {
+      setError(`Download link blocked: ${event.payload}`);
     });
 
     return () => {
@@ -597,6 +614,15 @@ function App() {
 
   // Handle update installation
   const handleInstallUpdate = async () => {
+    // A required update stays in its modal, which also offers installing once downloaded and retrying
+    if (updateState.status === 'ready') {
+      await updateManager.installUpdate();
+      return;
+    }
+    if (updateState.status === 'error' && updateState.required) {
+      await updateManager.checkForUpdates(true);
+      return;
+    }
     if (updateState.status !== 'available') return;
 
     try {
@@ -620,6 +646,15 @@ function App() {
     setShowUpdateModal(false);
   };
 
+  const handleSkipUpdate = async () => {
+    setShowUpdateModal(false);
+    try {
+      await updateManager.skipVersion();
+    } catch (error) {
+      console.error('Failed to skip version:', error);
+    }
+  };
+
   // State to control the visibility of the update modal
   const [showUpdateModal, setShowUpdateModal] = useState(false);
 
@@ -646,12 +681,13 @@ function App() {
         <TitleBar version={packageJson.version} isDarkMode={theme === 'dark'} updateManager={updateManager} />
 
         {/* Update notification modal */}
-        {showUpdateModal && updateState.status === 'available' && (
+        {((showUpdateModal && updateState.status === 'available') || updateState.required) && (
           <UpdateNotificationModal
             updateState={updateState}
             onInstall={handleInstallUpdate}
             onLater={handleDismissUpdate}
             onClose={handleDismissUpdate}
+            onSkip={updateState.required ? undefined : handleSkipUpdate}
           />
         )}
 
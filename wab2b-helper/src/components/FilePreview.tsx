@@ -9,6 +9,8 @@ interface FileInfo {
   file_path: string;
   file_name: string;
   mime_type: string;
+  detected_mime_type?: string | null;
+  extension_mime_type?: string | null;
   size: number;
 }
 
@@ -8,6 +8,7 @@ interface UpdateNotificationModalProps {
   onInstall: () => void;
   onLater: () => void;
   onClose: () => void;
+  onSkip?: () => void;
 }
 
 export function UpdateNotificationModal({
@@ -15,28 +16,40 @@ export function UpdateNotificationModal({
   onInstall,
   onLater,
   onClose,
+  onSkip,
 }: UpdateNotificationModalProps): JSX.Element {
-  if (updateState.status !== 'available') {
+  // A required update can't be dismissed and stays up while it downloads and installs
+  const required = updateState.required === true;
+  if (updateState.status !== 'available' && !(required && updateState.status !== 'idle')) {
     return <></>;
   }
 
+  const busyLabel: Partial<Record<UpdateState['status'], string>> = {
+    checking: 'Checking...',
+    downloading: `Downloading... ${updateState.downloadProgress ?? 0}%`,
+    installing: 'Installing...'
+  };
+  const busy = busyLabel[updateState.status];
+
   return (
     <div className="fixed inset-0 bg-black bg-opacity-50 flex items-center justify-center z-50">
       <div className="bg-white dark:bg-gray-800 rounded-lg shadow-xl max-w-md w-full p-6 m-4">
         <div className="flex justify-between items-center mb-4">
           <h3 className="text-lg font-medium text-gray-900 dark:text-white">
-            Update Available
+            {required ? 'Update Required' : 'Update Available'}
           </h3>
-          <button
-            onClick={onClose}
-            className="text-gray-400 hover:text-gray-500 dark:hover:text-gray-300"
-            aria-label="Close"
-          >
-            <svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2" strokeLinecap="round" strokeLinejoin="round">
-              <line x1="18" y1="6" x2="6" y2="18"></line>
-              <line x1="6" y1="6" x2="18" y2="18"></line>
-            </svg>
-          </button>
+          {!required && (
+            <button
+              onClick={onClose}
+              className="text-gray-400 hover:text-gray-500 dark:hover:text-gray-300"
+              aria-label="Close"
+            >
+              <svg xmlns="http://www.w3.org/2000/svg" width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2" strokeLinecap="round" strokeLinejoin="round">
+                <line x1="18" y1="6" x2="6" y2="18"></line>
+                <line x1="6" y1="6" x2="18" y2="18"></line>
+              </svg>
+            </button>
+          )}
         </div>
         
         <div className="mb-4">
@@ -45,9 +58,15 @@ export function UpdateNotificationModal({
               <path d="M12 2v4M12 18v4M4.93 4.93l2.83 2.83M16.24 16.24l2.83 2.83M2 12h4M18 12h4M4.93 19.07l2.83-2.83M16.24 7.76l2.83-2.83"></path>
             </svg>
             <span className="text-gray-700 dark:text-gray-300">
-              Version {updateState.latestVersion} is now available. You have {updateState.currentVersion}.
+              {required
+                ? `Version ${updateState.latestVersion} has to be installed; ${updateState.currentVersion} no longer works with the dashboard.`
+                : `Version ${updateState.latestVersion} is now available. You have ${updateState.currentVersion}.`}
             </span>
           </div>
+
+          {required && updateState.error && (
+            <p className="text-sm text-red-600 dark:text-red-400 mt-2">{updateState.error}</p>
+          )}
           
           <div className="bg-gray-50 dark:bg-gray-700 rounded-md p-3 mt-3 max-h-60 overflow-y-auto">
             <h4 className="text-sm font-medium text-gray-700 dark:text-gray-300 mb-2">Release Notes:</h4>
@@ -64,18 +83,38 @@ export function UpdateNotificationModal({
         </div>
         
         <div className="flex justify-end space-x-3">
-          <button
-            onClick={onLater}
-            className="px-4 py-2 text-sm font-medium text-gray-700 bg-gray-100 hover:bg-gray-200 dark:text-gray-300 dark:bg-gray-700 dark:hover:bg-gray-600 rounded-md transition-colors"
-          >
-            Later
-          </button>
-          <button
-            onClick={onInstall}
-            className="px-4 py-2 text-sm font-medium text-white bg-blue-500 hover:bg-blue-600 rounded-md transition-colors"
-          >
-            Install Update
-          </button>
+          {required ? (
+            <button
+              onClick={onInstall}
+              disabled={busy !== undefined}
+              className="px-4 py-2 text-sm font-medium text-white bg-blue-500 hover:bg-blue-600 disabled:opacity-60 disabled:cursor-not-allowed rounded-md transition-colors"
+            >
+              {busy ?? (updateState.status === 'error' ? 'Try Again' : 'Install Update')}
+            </button>
+          ) : (
+            <>
+            {onSkip && (
+              <button
+                onClick={onSkip}
+                className="px-4 py-2 text-sm font-medium text-gray-500 hover:text-gray-700 dark:text-gray-400 dark:hover:text-gray-200 rounded-md transition-colors"
+              >
+                Skip This Version
+              </button>
+            )}
+            <button
+              onClick={onLater}
+              className="px-4 py-2 text-sm font-medium text-gray-700 bg-gray-100 hover:bg-gray-200 dark:text-gray-300 dark:bg-gray-700 dark:hover:bg-gray-600 rounded-md transition-colors"
+            >
+              Later
+            </button>
+            <button
+              onClick={onInstall}
+              className="px-4 py-2 text-sm font-medium text-white bg-blue-500 hover:bg-blue-600 rounded-md transition-colors"
+            >
+              Install Update
+            </button>
+            </>
+          )}
         </div>
       </div>
     </div>
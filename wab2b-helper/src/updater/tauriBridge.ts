@@ -5,7 +5,7 @@
 
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
-import { ReleaseInfo, DownloadProgress } from './types';
+import { ReleaseInfo, DeltaUpdate, InstallReport, InstallerUi, OtherSessionAction, StagedUpdate, UpdaterEvent } from './types';
 
 /**
  * Check for updates from GitHub
@@ -31,7 +31,7 @@ export async function checkForUpdates(
 /**
  * Download an asset with progress tracking
  * @param url Asset download URL
- * @param destination Path to save the downloaded file
+ * @param destination Path to save the downloaded file; a relative path is kept in the updates directory
  * @param onProgress Optional callback for download progress
  * @returns Promise with the path to the downloaded file
  */
@@ -45,9 +45,10 @@ export async function downloadAsset(
   try {
     // Set up progress listener if callback provided
     if (onProgress) {
-      const unlistenPromise = await listen<DownloadProgress>('download-progress', (event) => {
-        const { downloaded, total } = event.payload;
-        onProgress(downloaded, total);
+      const unlistenPromise = await listen<UpdaterEvent>('updater-event', (event) => {
+        if (event.payload.type === 'downloading') {
+          onProgress(event.payload.downloaded, event.payload.total);
+        }
       });
       unlisten = unlistenPromise;
     }
@@ -71,9 +72,42 @@ export async function downloadAsset(
 }
 
 /**
- * Verify the integrity of a downloaded file using SHA256 hash
+ * Download an update as a patch against the running version; the backend falls back to the full package
+ * @param delta Patch offered with the release
+ * @param destination Path to save the rebuilt package; a relative path is kept in the updates directory
+ * @param onProgress Optional callback for download progress
+ * @returns Promise with the path to the downloaded file
+ */
+export async function downloadDeltaUpdate(
+  delta: DeltaUpdate,
+  destination: string,
+  onProgress?: (downloaded: number, total: number) => void
+): Promise<string> {
+  let unlisten: (() => void) | undefined;
+  
+  try {
+    if (onProgress) {
+      unlisten = await listen<UpdaterEvent>('updater-event', (event) => {
+        if (event.payload.type === 'downloading') {
+          onProgress(event.payload.downloaded, event.payload.total);
+        }
+      });
+    }
+    
+    return await invoke<string>('download_delta_update', { delta, destination });
+  } catch (error) {
+    throw new Error(`Failed to download update: ${error instanceof Error ? error.message : String(error)}`);
+  } finally {
+    if (unlisten) {
+      unlisten();
+    }
+  }
+}
+
+/**
+ * Verify the integrity of a downloaded file using its SHA256 or SHA512 hash
  * @param filePath Path to the downloaded file
- * @param expectedHash Expected SHA256 hash
+ * @param expectedHash Expected SHA256 or SHA512 hash
  * @returns Promise with boolean indicating if verification passed
  */
 export async function verifyFileHash(
@@ -90,12 +124,56 @@ export async function verifyFileHash(
 /**
  * Install an update and restart the application
  * @param updatePath Path to the update file
- * @returns Promise that resolves when installation begins
+ * @param onOtherSessions What to do if another user session runs the application
+ * @param ui Installer UI for this install, instead of the one from the settings
+ * @param dryRun Check the update and report what would happen, without installing it
+ * @returns Promise that resolves with how the update was applied
  */
-export async function installUpdate(updatePath: string): Promise<boolean> {
+export async function installUpdate(
+  updatePath: string,
+  onOtherSessions?: OtherSessionAction,
+  ui?: InstallerUi,
+  dryRun?: boolean
+): Promise<InstallReport> {
   try {
-    return await invoke<boolean>('install_update', { updatePath });
+    return await invoke<InstallReport>('install_update', { updatePath, onOtherSessions, ui, dryRun });
   } catch (error) {
     throw new Error(`Failed to install update: ${error instanceof Error ? error.message : String(error)}`);
   }
+}
+
+/**
+ * Get the release this version has to be updated to before it may be used
+ * @returns Promise with the required release, or null if the running version may be used
+ */
+export async function getRequiredUpdate(): Promise<ReleaseInfo | null> {
+  try {
+    return await invoke<ReleaseInfo | null>('get_required_update');
+  } catch (error) {
+    throw new Error(`Failed to get required update: ${error instanceof Error ? error.message : String(error)}`);
+  }
+}
+
+/**
+ * Get the update downloaded in the background for installation on exit
+ * @returns Promise with the staged update, or null if there is none
+ */
+export async function getStagedUpdate(): Promise<StagedUpdate | null> {
+  try {
+    return await invoke<StagedUpdate | null>('get_staged_update');
+  } catch (error) {
+    throw new Error(`Failed to get staged update: ${error instanceof Error ? error.message : String(error)}`);
+  }
+}
+
+/**
+ * Drop the update downloaded in the background so it isn't installed on exit
+ * @returns Promise with the discarded update, or null if there was none
+ */
+export async function discardStagedUpdate(): Promise<StagedUpdate | null> {
+  try {
+    return await invoke<StagedUpdate | null>('discard_staged_update');
+  } catch (error) {
+    throw new Error(`Failed to discard staged update: ${error instanceof Error ? error.message : String(error)}`);
+  }
 }
\ No newline at end of file
@@ -32,6 +32,8 @@ export interface UpdateState {
   error?: string;
   /** Path to the downloaded update file (when status is 'ready') */
   downloadedFilePath?: string;
+  /** Whether the running version is too old to be used until the update is installed */
+  required?: boolean;
 }
 
 /**
@@ -46,6 +48,58 @@ export interface ReleaseInfo {
   assets: Asset[];
   /** Publication date of the release */
   publishedAt: string;
+  /** Web page of the release */
+  htmlUrl?: string;
+  /** Guided fallback when no asset can be installed automatically */
+  manualUpdate?: ManualUpdate | null;
+  /** Whether the release is newer than the running version */
+  updateAvailable?: boolean;
+  /** Channel the release was published on */
+  channel?: 'stable' | 'beta';
+  /** Whether the user skipped this version */
+  skipped?: boolean;
+  /** Oldest version that may keep running */
+  minimumVersion?: string | null;
+  /** Whether the running version is below minimumVersion and has to be updated */
+  updateRequired?: boolean;
+  /** Share of installs the release is offered to so far; everyone when unset */
+  rolloutPercentage?: number | null;
+  /** Whether this install is outside the rollout and isn't offered the release yet */
+  heldBack?: boolean;
+  /** Patch from the running version to this release, if one is published */
+  delta?: DeltaUpdate | null;
+  /** How the running copy is installed; portable copies update from the zip package, AppImages from the AppImage */
+  installMode?: 'installed' | 'portable' | 'app_image';
+  /** Installer or Linux package manager the running copy was set up with */
+  installer?: 'msi' | 'nsis' | 'deb' | 'rpm' | null;
+  /** The asset the backend picked for this machine */
+  platformAsset?: Asset | null;
+}
+
+/**
+ * A patch that turns the installed version into a release's package
+ */
+export interface DeltaUpdate {
+  /** The patch asset */
+  patch: Asset;
+  /** Version the patch applies to */
+  fromVersion: string;
+  /** The package the patch produces */
+  target: Asset;
+}
+
+/**
+ * Instructions for installing a release by hand
+ */
+export interface ManualUpdate {
+  /** Why the update can't be installed automatically */
+  reason: 'no_assets' | 'no_compatible_asset';
+  /** Release page to download the update from */
+  releaseUrl: string;
+  /** Platform the updater looked for (e.g., "windows-x86_64") */
+  platform: string;
+  /** Assets closest to this platform, best match first */
+  suggestions: Asset[];
 }
 
 /**
@@ -60,16 +114,63 @@ export interface Asset {
   size: number;
   /** SHA256 hash of the asset */
   sha256: string;
+  /** SHA512 hash of the asset, when the release publishes one */
+  sha512?: string;
 }
 
 /**
- * Download progress information
+ * Where in the update process something went wrong
+ */
+export type FailureCode = 'check' | 'download' | 'verification' | 'install';
+
+/**
+ * A step of the update process, sent by the backend as `updater-event`
+ */
+export type UpdaterEvent =
+  | { type: 'checking' }
+  | { type: 'available'; release: ReleaseInfo }
+  | { type: 'update_required'; release: ReleaseInfo; minimumVersion: string }
+  | { type: 'downloading'; pct: number; downloaded: number; total: number }
+  | { type: 'verifying'; path: string }
+  | { type: 'ready_to_install'; path: string; version: string | null; staged: boolean }
+  | { type: 'failed'; code: FailureCode; message: string; exitCode: number | null; logPath: string | null };
+
+/**
+ * What to do when another user session runs the application
  */
-export interface DownloadProgress {
-  /** Number of bytes downloaded */
-  downloaded: number;
-  /** Total size in bytes */
-  total: number;
-  /** Path to the downloaded file */
-  filePath: string;
-}
\ No newline at end of file
+export type OtherSessionAction = 'ask' | 'schedule_on_reboot';
+
+/**
+ * How much of the MSI or NSIS installer the user sees
+ */
+export type InstallerUi = 'silent' | 'passive' | 'interactive';
+
+/**
+ * Outcome of an install request
+ */
+export interface InstallReport {
+  /** How the update was applied */
+  strategy: 'restart' | 'scheduled_on_reboot' | 'deferred' | 'installer';
+  /** IDs of other user sessions running the application */
+  other_sessions: number[];
+  /** Whether the update was only checked; strategy and actions tell what would have happened */
+  dry_run: boolean;
+  /** Steps a dry run skipped */
+  actions: string[];
+}
+
+/**
+ * An update downloaded in the background, installed when the application exits
+ */
+export interface StagedUpdate {
+  /** Version of the staged release */
+  version: string;
+  /** Path to the downloaded package */
+  path: string;
+  /** SHA256 hash of the package */
+  sha256: string;
+  /** When the update was staged, in seconds since the Unix epoch */
+  staged_at: number;
+  /** Whether installing it was already tried */
+  attempted: boolean;
+}
@@ -4,11 +4,10 @@
  */
 
 import { getVersion } from '@tauri-apps/api/app';
-import { checkForUpdates, downloadAsset, verifyFileHash, installUpdate } from './tauriBridge';
-import { UpdateSettings, UpdateState, Asset } from './types';
-import { appDataDir } from '@tauri-apps/api/path';
-import { join } from '@tauri-apps/api/path';
+import { checkForUpdates, downloadAsset, downloadDeltaUpdate, verifyFileHash, installUpdate } from './tauriBridge';
+import { UpdateSettings, UpdateState, ReleaseInfo, UpdaterEvent } from './types';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 
 /**
  * Default update settings
@@ -38,6 +37,8 @@ const SETTINGS_STORAGE_KEY = 'wab2b-helper-update-settings';
 export class UpdateManager {
   private settings: UpdateSettings;
   private state: UpdateState;
+  /** Release behind the 'available' state, downloaded as it was announced */
+  private releaseInfo?: ReleaseInfo;
   private stateChangeListeners: ((state: UpdateState) => void)[] = [];
 
   /**
@@ -148,14 +149,16 @@ export class UpdateManager {
         shouldIncludeBeta
       );
       
-      // Compare versions
-      const hasUpdate = this.compareVersions(releaseInfo.version, currentVersion) > 0;
+      // The backend compared the versions; automatic checks stay quiet about a skipped version
+      const hasUpdate = releaseInfo.updateAvailable === true && (manual || !releaseInfo.skipped);
       
       if (hasUpdate) {
+        this.releaseInfo = releaseInfo;
         this.setState({
           status: 'available',
           latestVersion: releaseInfo.version,
-          releaseNotes: releaseInfo.releaseNotes
+          releaseNotes: releaseInfo.releaseNotes,
+          required: releaseInfo.updateRequired === true
         });
         return true;
       } else {
@@ -171,71 +174,27 @@ export class UpdateManager {
     }
   }
 
-  /**
-   * Compare two semantic version strings
-   * @param v1 First version
-   * @param v2 Second version
-   * @returns 1 if v1 > v2, -1 if v1 < v2, 0 if equal
-   */
-  private compareVersions(v1: string, v2: string): number {
-    // Handle pre-release versions (e.g., 1.0.0-beta.1)
-    const [v1Base, v1PreRelease] = v1.split('-');
-    const [v2Base, v2PreRelease] = v2.split('-');
-    
-    // Compare base versions first
-    const parts1 = v1Base.split('.').map(Number);
-    const parts2 = v2Base.split('.').map(Number);
-    
-    for (let i = 0; i < Math.max(parts1.length, parts2.length); i++) {
-      const part1 = parts1[i] || 0;
-      const part2 = parts2[i] || 0;
-      
-      if (part1 > part2) return 1;
-      if (part1 < part2) return -1;
-    }
-    
-    // If base versions are equal, compare pre-release versions
-    // Pre-release versions are lower priority than regular versions
-    if (!v1PreRelease && v2PreRelease) return 1;
-    if (v1PreRelease && !v2PreRelease) return -1;
-    if (!v1PreRelease && !v2PreRelease) return 0;
-    
-    // Compare pre-release versions
-    // For simplicity, we'll just compare them lexicographically
-    // In a real implementation, you might want to parse them more carefully
-    if (v1PreRelease! > v2PreRelease!) return 1;
-    if (v1PreRelease! < v2PreRelease!) return -1;
-    
-    return 0;
-  }
-
   /**
    * Download the latest update
    * @returns Promise with boolean indicating if download was successful
    */
   public async downloadUpdate(): Promise<boolean> {
     try {
-      if (this.state.status !== 'available') {
+      const releaseInfo = this.releaseInfo;
+      if (this.state.status !== 'available' || !releaseInfo) {
         throw new Error('No update available to download');
       }
 
       this.setState({ status: 'downloading', downloadProgress: 0 });
 
-      // Get the appropriate asset for the current platform
-      const releaseInfo = await checkForUpdates(
-        this.settings.githubOwner,
-        this.settings.githubRepo
-      );
-
-      // Find the appropriate asset for the current platform
-      const asset = this.selectPlatformAsset(releaseInfo.assets);
+      // The backend picks the asset for this machine, as it does for background updates
+      const asset = releaseInfo.platformAsset;
       if (!asset) {
         throw new Error('No compatible update found for your platform');
       }
 
-      // Create a destination path in the app data directory
-      const appData = await appDataDir();
-      const downloadPath = await join(appData, `update-${releaseInfo.version}.bin`);
+      // A bare file name, the backend keeps it in its updates directory
+      const downloadPath = `update-${releaseInfo.version}.bin`;
 
       // Download the asset with progress tracking
       const onProgress = (downloaded: number, total: number) => {
@@ -243,11 +202,14 @@ export class UpdateManager {
         this.setState({ downloadProgress: progress });
       };
 
-      // Download the asset
-      const filePath = await downloadAsset(asset.downloadUrl, downloadPath, onProgress);
+      // Download a patch against this version when the release has one, otherwise the whole asset
+      const filePath = releaseInfo.delta
+        ? await downloadDeltaUpdate(releaseInfo.delta, downloadPath, onProgress)
+        : await downloadAsset(asset.downloadUrl, downloadPath, onProgress);
 
       // Verify the downloaded file
-      const isValid = await verifyFileHash(filePath, asset.sha256);
+      // The stronger hash when both are published
+      const isValid = await verifyFileHash(filePath, asset.sha512 || asset.sha256);
       if (!isValid) {
         throw new Error('Update verification failed. The downloaded file may be corrupted.');
       }
@@ -268,11 +230,25 @@ export class UpdateManager {
     }
   }
 
+  /**
+   * Stop announcing the available version until a newer one is released
+   */
+  public async skipVersion(): Promise<void> {
+    // A required update can't be put off
+    if (!this.state.latestVersion || this.state.required) {
+      return;
+    }
+
+    await invoke('skip_version', { version: this.state.latestVersion });
+    this.setState({ status: 'idle' });
+  }
+
   /**
    * Install the downloaded update
+   * @param dryRun Only check the update and report what installing it would do
    * @returns Promise that resolves when installation begins
    */
-  public async installUpdate(): Promise<void> {
+  public async installUpdate(dryRun?: boolean): Promise<void> {
     try {
       if (this.state.status !== 'ready' || !this.state.downloadedFilePath) {
         throw new Error('No update ready to install');
@@ -281,7 +257,46 @@ export class UpdateManager {
       this.setState({ status: 'installing' });
 
       // Install the update
-      await installUpdate(this.state.downloadedFilePath);
+      const report = await installUpdate(this.state.downloadedFilePath, undefined, undefined, dryRun);
+
+      // Nothing was installed, the update stays ready
+      if (report.dry_run) {
+        this.setState({
+          status: 'ready',
+          error: `Dry run: ${report.actions.join('; ')}`
+        });
+        return;
+      }
+
+      // Another user has the helper open, keep the update until they close it or the user schedules it
+      if (report.strategy === 'deferred') {
+        this.setState({
+          status: 'ready',
+          error: `The helper is running in ${report.other_sessions.length} other user session(s). Close it there or install on next reboot.`
+        });
+        return;
+      }
+
+      if (report.strategy === 'scheduled_on_reboot') {
+        this.setState({ status: 'idle' });
+        return;
+      }
+
+      // An MSI package is waited for; the app restarts if it succeeds and hears about it if not
+      if (report.strategy === 'installer') {
+        const unlisten = await listen<UpdaterEvent>('updater-event', (event) => {
+          const payload = event.payload;
+          if (payload.type !== 'failed' || payload.code !== 'install') {
+            return;
+          }
+          unlisten();
+          this.setState({
+            status: 'ready',
+            error: payload.logPath ? `${payload.message} (log: ${payload.logPath})` : payload.message
+          });
+        });
+        return;
+      }
 
       // Note: The app will restart after installation, so we don't need to update the state
     } catch (error) {
@@ -293,34 +308,26 @@ export class UpdateManager {
   }
 
   /**
-   * Select the appropriate asset for the current platform
-   * @param assets List of available assets
-   * @returns The selected asset or undefined if none found
+   * Show an update found by the backend's periodic check; a required one blocks the app
+   * @param releaseInfo The newer release
    */
-  private selectPlatformAsset(assets: Asset[]): Asset | undefined {
-    // Get platform information
-    const platform = navigator.platform.toLowerCase();
-    const isWindows = platform.includes('win');
-    const isMac = platform.includes('mac');
-    const isLinux = platform.includes('linux');
-    
-    // Find the appropriate asset based on platform
-    return assets.find(asset => {
-      const name = asset.name.toLowerCase();
-      
-      if (isWindows && (name.includes('windows') || name.includes('win') || name.endsWith('.msi') || name.endsWith('.exe'))) {
-        return true;
-      }
-      
-      if (isMac && (name.includes('mac') || name.includes('darwin') || name.endsWith('.dmg') || name.endsWith('.pkg'))) {
-        return true;
-      }
-      
-      if (isLinux && (name.includes('linux') || name.endsWith('.AppImage') || name.endsWith('.deb') || name.endsWith('.rpm'))) {
-        return true;
+  public handleAvailableUpdate(releaseInfo: ReleaseInfo): void {
+    const required = releaseInfo.updateRequired === true;
+
+    // Don't interrupt a download or install already under way, but do block while it runs
+    if (this.state.status !== 'idle' && this.state.status !== 'error') {
+      if (required) {
+        this.setState({ required });
       }
-      
-      return false;
+      return;
+    }
+
+    this.releaseInfo = releaseInfo;
+    this.setState({
+      status: 'available',
+      latestVersion: releaseInfo.version,
+      releaseNotes: releaseInfo.releaseNotes,
+      required
     });
   }
 